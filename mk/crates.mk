@@ -64,7 +64,7 @@ DEPS_native := std
 DEPS_syntax := std term serialize collections log fmt_macros
 DEPS_rustc := syntax native:rustllvm flate arena serialize sync getopts \
               collections time log
-DEPS_rustdoc := rustc native:hoedown serialize sync getopts collections \
+DEPS_rustdoc := rustc native:hoedown serialize sync getopts collections flate \
                 test time
 DEPS_flate := std native:miniz
 DEPS_arena := std collections
@@ -160,12 +160,14 @@ impl Item {
         return None;
     }
 
-    pub fn is_hidden_from_doc(&self) -> bool {
+    /// Whether `word` is one of the words of the `doc` attribute, as with
+    /// `#[doc(hidden)]`.
+    fn has_doc_word(&self, word: &str) -> bool {
         match self.doc_list() {
             Some(ref l) => {
                 for innerattr in l.iter() {
                     match *innerattr {
-                        Word(ref s) if "hidden" == s.as_slice() => {
+                        Word(ref s) if word == s.as_slice() => {
                             return true
                         }
                         _ => (),
@@ -177,6 +179,38 @@ impl Item {
         return false;
     }
 
+    pub fn is_hidden_from_doc(&self) -> bool {
+        self.has_doc_word("hidden")
+    }
+
+    /// Whether the source of the item is left out of the documentation with
+    /// `#[doc(no_source)]`, as for machine-generated code.
+    pub fn is_source_hidden(&self) -> bool {
+        self.has_doc_word("no_source")
+    }
+
+    /// The other names the item can be searched for by, each given with
+    /// `#[doc(alias = "name")]`.
+    pub fn doc_aliases(&self) -> Vec<StrBuf> {
+        let mut aliases = Vec::new();
+        for attr in self.attrs.iter() {
+            match *attr {
+                List(ref x, ref list) if "doc" == x.as_slice() => {
+                    for innerattr in list.iter() {
+                        match *innerattr {
+                            NameValue(ref name, ref alias) if "alias" == name.as_slice() => {
+                                aliases.push(alias.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        aliases
+    }
+
     pub fn is_mod(&self) -> bool {
         match self.inner { ModuleItem(..) => true, _ => false }
     }
@@ -192,6 +226,42 @@ impl Item {
     pub fn is_fn(&self) -> bool {
         match self.inner { FunctionItem(..) => true, _ => false }
     }
+
+    /// Finds the stability attribute of this item, given either as
+    /// `#[unstable = "reason"]` or as
+    /// `#[unstable(since = "0.11", reason = "...")]`.
+    pub fn stability(&self) -> Option<Stability> {
+        for attr in self.attrs.iter() {
+            let level = match attr::find_stability(Some(attr).move_iter()) {
+                Some(stab) => stab,
+                None => continue,
+            };
+            let mut since = None;
+            let mut reason = level.text.map(|s| s.get().to_strbuf());
+            match *attr {
+                List(_, ref list) => {
+                    for meta in list.iter() {
+                        match *meta {
+                            NameValue(ref n, ref v) if "since" == n.as_slice() => {
+                                since = Some(v.clone());
+                            }
+                            NameValue(ref n, ref v) if "reason" == n.as_slice() => {
+                                reason = Some(v.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            return Some(Stability {
+                level: level.level,
+                since: since,
+                reason: reason,
+            });
+        }
+        None
+    }
 }
 
 #[deriving(Clone, Encodable, Decodable)]
@@ -336,6 +406,15 @@ impl<'a> attr::AttrMetaMethods for &'a Attribute {
     }
 }
 
+/// The stability of an item, along with the version it attained it in and
+/// why, if given.
+#[deriving(Clone)]
+pub struct Stability {
+    pub level: attr::StabilityLevel,
+    pub since: Option<StrBuf>,
+    pub reason: Option<StrBuf>,
+}
+
 #[deriving(Clone, Encodable, Decodable)]
 pub struct TyParam {
     pub name: StrBuf,
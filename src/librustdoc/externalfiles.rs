@@ -0,0 +1,293 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{io, str};
+use std::strbuf::StrBuf;
+
+macro_rules! load_or_return {
+    ($input: expr, $cant_read: expr, $not_utf8: expr) => {
+        {
+            let input = Path::new($input);
+            match ::externalfiles::load_string(&input) {
+                Err(e) => {
+                    let _ = writeln!(&mut io::stderr(),
+                                     "error reading `{}`: {}", input.display(), e);
+                    return $cant_read;
+                }
+                Ok(None) => {
+                    let _ = writeln!(&mut io::stderr(),
+                                     "error reading `{}`: not UTF-8", input.display());
+                    return $not_utf8;
+                }
+                Ok(Some(s)) => s
+            }
+        }
+    }
+}
+
+/// Snippets of HTML supplied by the user which are spliced into every page
+/// that rustdoc generates.
+#[deriving(Clone)]
+pub struct ExternalHtml {
+    /// Content inserted inline into the `<head>` of each page
+    pub in_header: StrBuf,
+    /// Content inserted between `<body>` and the rendered page contents
+    pub before_content: StrBuf,
+    /// Content inserted between the rendered page contents and `</body>`
+    pub after_content: StrBuf,
+    /// Content only inserted into pages of a particular kind, after the
+    /// content for every page
+    pub pages: Vec<(PageKind, ExternalHtml)>,
+}
+
+/// The kinds of pages which may be given HTML of their own.
+#[deriving(Clone, Eq, Show)]
+pub enum PageKind {
+    /// The page of a module, including the root of a crate
+    ModulePage,
+    /// The page of any other item
+    ItemPage,
+    /// A page showing a source file
+    SourcePage,
+}
+
+impl PageKind {
+    /// The kind of a page whose `layout::Page::ty` is `ty`.
+    pub fn of(ty: &str) -> PageKind {
+        match ty {
+            "mod" => ModulePage,
+            "source" => SourcePage,
+            _ => ItemPage,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<PageKind> {
+        match name {
+            "module" => Some(ModulePage),
+            "item" => Some(ItemPage),
+            "source" => Some(SourcePage),
+            _ => None,
+        }
+    }
+}
+
+impl ExternalHtml {
+    /// An `ExternalHtml` which injects nothing into the generated pages.
+    pub fn empty() -> ExternalHtml {
+        ExternalHtml {
+            in_header: StrBuf::new(),
+            before_content: StrBuf::new(),
+            after_content: StrBuf::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Loads the contents of each list of files, concatenating all files in
+    /// a list in order. Returns `None` (after printing an error) if any of
+    /// the files could not be read.
+    pub fn load(in_header: &[StrBuf], before_content: &[StrBuf],
+                after_content: &[StrBuf]) -> Option<ExternalHtml> {
+        load_external_files(in_header)
+            .and_then(|ih|
+                load_external_files(before_content)
+                    .map(|bc| (ih, bc))
+            )
+            .and_then(|(ih, bc)|
+                load_external_files(after_content)
+                    .map(|ac| (ih, bc, ac))
+            )
+            .map(|(ih, bc, ac)|
+                ExternalHtml {
+                    in_header: ih,
+                    before_content: bc,
+                    after_content: ac,
+                    pages: Vec::new(),
+                }
+            )
+    }
+
+    /// Adds the files given as `KIND:PLACE:FILE`, where `KIND` is `module`,
+    /// `item` or `source` and `PLACE` is `in-header`, `before-content` or
+    /// `after-content`, to be inserted into pages of that kind only. Returns
+    /// `None` (after printing an error) if a file could not be read or a
+    /// specification is malformed.
+    pub fn load_pages(mut self, specs: &[StrBuf]) -> Option<ExternalHtml> {
+        for spec in specs.iter() {
+            let parts = spec.as_slice().splitn(':', 2).collect::<Vec<&str>>();
+            let kind = if parts.len() == 3 {
+                PageKind::from_name(*parts.get(0))
+            } else {
+                None
+            };
+            let kind = match kind {
+                Some(kind) => kind,
+                None => {
+                    let _ = writeln!(&mut io::stderr(),
+                                     "expected `KIND:PLACE:FILE` with a KIND of \
+                                      `module`, `item` or `source`: `{}`", *spec);
+                    return None;
+                }
+            };
+            let contents = load_or_return!(*parts.get(2), None, None);
+            let idx = match self.pages.iter().position(|&(k, _)| k == kind) {
+                Some(i) => i,
+                None => {
+                    self.pages.push((kind, ExternalHtml::empty()));
+                    self.pages.len() - 1
+                }
+            };
+            let html = self.pages.get_mut(idx).mut1();
+            match *parts.get(1) {
+                "in-header" => html.in_header.push_str(contents.as_slice()),
+                "before-content" => html.before_content.push_str(contents.as_slice()),
+                "after-content" => html.after_content.push_str(contents.as_slice()),
+                place => {
+                    let _ = writeln!(&mut io::stderr(),
+                                     "expected a PLACE of `in-header`, \
+                                      `before-content` or `after-content`: `{}`",
+                                     place);
+                    return None;
+                }
+            }
+        }
+        Some(self)
+    }
+
+    /// Adds the stylesheets `files`, concatenated in order, to the `<head>` of
+    /// every page after everything else in it, so their rules take precedence
+    /// over those of the theme. Returns `None` (after printing an error) if
+    /// any of the files could not be read.
+    pub fn load_css(mut self, files: &[StrBuf]) -> Option<ExternalHtml> {
+        if files.len() == 0 { return Some(self) }
+        load_external_files(files).map(|css| {
+            self.in_header.push_str("<style type=\"text/css\">\n");
+            self.in_header.push_str(css.as_slice());
+            self.in_header.push_str("</style>\n");
+            self
+        })
+    }
+
+    /// The HTML to insert into a page whose `layout::Page::ty` is `ty`.
+    pub fn for_page(&self, ty: &str) -> ExternalHtml {
+        let mut html = ExternalHtml {
+            in_header: self.in_header.clone(),
+            before_content: self.before_content.clone(),
+            after_content: self.after_content.clone(),
+            pages: Vec::new(),
+        };
+        let kind = PageKind::of(ty);
+        for &(k, ref extra) in self.pages.iter() {
+            if k == kind {
+                html.in_header.push_str(extra.in_header.as_slice());
+                html.before_content.push_str(extra.before_content.as_slice());
+                html.after_content.push_str(extra.after_content.as_slice());
+            }
+        }
+        html
+    }
+}
+
+/// Reads the entire contents of `input`, returning `Ok(None)` if the file is
+/// not valid UTF-8.
+pub fn load_string(input: &Path) -> io::IoResult<Option<StrBuf>> {
+    let mut f = try!(io::File::open(input));
+    let d = try!(f.read_to_end());
+    Ok(str::from_utf8(d.as_slice()).map(|s| s.to_strbuf()))
+}
+
+/// Concatenates the contents of all `names`, separating each file with a
+/// newline.
+pub fn load_external_files(names: &[StrBuf]) -> Option<StrBuf> {
+    let mut out = StrBuf::new();
+    for name in names.iter() {
+        out.push_str(load_or_return!(name.as_slice(), None, None).as_slice());
+        out.push_char('\n');
+    }
+    Some(out)
+}
+
+/// A stylesheet supplied by the user to be offered as a theme of the HTML
+/// output.
+#[deriving(Clone)]
+pub struct Theme {
+    /// The name the theme is listed under, taken from the name of its file
+    pub name: StrBuf,
+    pub css: StrBuf,
+}
+
+/// The names themes can't have, as the theme is written out next to the
+/// static files as `<name>.css` and would replace one of them.
+static RESERVED_THEME_NAMES: &'static [&'static str] = &[
+    "main", "normalize",
+    "FiraSans-Regular", "FiraSans-Medium",
+    "Heuristica-Regular", "Heuristica-Italic", "Heuristica-Bold",
+];
+
+impl Theme {
+    /// Loads the theme in the CSS file `path`. Returns `None` (after printing
+    /// an error) if it could not be read or its name is reserved.
+    pub fn load(path: &str) -> Option<Theme> {
+        let name = match Path::new(path).filestem_str() {
+            Some(s) => s.to_strbuf(),
+            None => return None,
+        };
+        if RESERVED_THEME_NAMES.contains(&name.as_slice()) {
+            let _ = writeln!(&mut io::stderr(),
+                             "error loading `{}`: `{}` is reserved for rustdoc's own \
+                              files, rename the theme", path, name);
+            return None;
+        }
+        let css = load_or_return!(path, None, None);
+        Some(Theme { name: name, css: css })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{File, TempDir};
+
+    use super::{ExternalHtml, ModulePage, SourcePage, Theme};
+
+    #[test]
+    fn for_page() {
+        let mut html = ExternalHtml::empty();
+        html.in_header.push_str("all ");
+        let mut module = ExternalHtml::empty();
+        module.in_header.push_str("module");
+        let mut source = ExternalHtml::empty();
+        source.after_content.push_str("source");
+        html.pages.push((ModulePage, module));
+        html.pages.push((SourcePage, source));
+
+        let page = html.for_page("mod");
+        assert_eq!(page.in_header.as_slice(), "all module");
+        assert_eq!(page.after_content.as_slice(), "");
+        let page = html.for_page("fn");
+        assert_eq!(page.in_header.as_slice(), "all ");
+        let page = html.for_page("source");
+        assert_eq!(page.in_header.as_slice(), "all ");
+        assert_eq!(page.after_content.as_slice(), "source");
+    }
+
+    #[test]
+    fn reserved_theme_names() {
+        let dir = TempDir::new("rustdoc-theme").unwrap();
+        for name in ["main", "FiraSans-Regular", "ocean"].iter() {
+            let path = dir.path().join(format!("{}.css", name));
+            File::create(&path).write_str("body {}").unwrap();
+            let theme = Theme::load(path.as_str().unwrap());
+            assert_eq!(theme.map(|t| t.name), if *name == "ocean" {
+                Some("ocean".to_strbuf())
+            } else {
+                None
+            });
+        }
+    }
+}
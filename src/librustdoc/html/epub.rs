@@ -0,0 +1,201 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! EPUB rendering backend
+//!
+//! Packages the documentation of a crate as an EPUB 2 book, with one chapter
+//! per module laid out like the single-page HTML output and a table of
+//! contents mirroring the module hierarchy.
+
+use std::io;
+use std::io::MemWriter;
+use std::strbuf::StrBuf;
+use sync::Arc;
+
+use clean;
+use fold::DocFolder;
+use html::escape::Escape;
+use html::markdown;
+use html::render::{Cache, HoardStripper, cache_key};
+use html::single::{chapter, chapter_file};
+use output;
+use output::{OutputSink, ZipWriter};
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+
+/// Renders a crate as an EPUB book.
+pub struct EpubRenderer;
+
+impl Renderer for EpubRenderer {
+    fn name(&self) -> &'static str { "epub" }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("doc.epub") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// A chapter of the book, which documents one module.
+struct Chapter {
+    path: Vec<StrBuf>,
+    file: StrBuf,
+    /// Number of chapters for the submodules of this module, which follow it
+    descendants: uint,
+}
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink) -> io::IoResult<()> {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut item = match krate.module {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    item.name = Some(krate.name.clone());
+    let title = match opts.crate_version {
+        Some(ref v) => format!("{} {}", krate.name, *v),
+        None => krate.name.to_str(),
+    };
+
+    let mut zip = ZipWriter::new(MemWriter::new());
+    // The mimetype must come first and be stored uncompressed, so readers can
+    // identify the archive
+    try!(zip.add("mimetype", "application/epub+zip".as_bytes(), false));
+    try!(zip.add("META-INF/container.xml", CONTAINER.as_bytes(), true));
+    try!(zip.add("OEBPS/main.css", include_bin!("static/main.css"), true));
+
+    let mut chapters = Vec::new();
+    let mut progress = (0, renderer::count_items(&item));
+    try!(chapters_for(&mut zip, &mut chapters, &mut Vec::new(), &item, opts,
+                      &mut progress));
+
+    try!(zip.add("OEBPS/content.opf",
+                 package(title.as_slice(), krate.name.as_slice(),
+                         chapters.as_slice()).as_bytes(), true));
+    try!(zip.add("OEBPS/toc.ncx",
+                 ncx(title.as_slice(), krate.name.as_slice(),
+                     chapters.as_slice()).as_bytes(), true));
+    let book = try!(zip.finish());
+    output::write(sink, &opts.dest, book.get_ref())
+}
+
+static CONTAINER: &'static str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// Renders the chapter for a module and then the chapters of its submodules,
+/// recording each in `chapters`. `progress` is the number of items done so
+/// far and the total.
+fn chapters_for(zip: &mut ZipWriter<MemWriter>, chapters: &mut Vec<Chapter>,
+                current: &mut Vec<StrBuf>, item: &clean::Item, opts: &RenderOptions,
+                progress: &mut (uint, uint)) -> io::IoResult<()> {
+    let m = match item.inner {
+        clean::ModuleItem(ref m) => m,
+        _ => return Ok(()),
+    };
+    let mut path = current.clone();
+    path.push(item.name.get_ref().clone());
+    let file = chapter_file(path.as_slice());
+
+    markdown::reset_headers();
+    let mut w = MemWriter::new();
+    try!(write!(&mut w,
+r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.1//EN" "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd">
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="main.css"/>
+</head>
+<body><div class="content">
+"#, title = Escape(path.connect("::").as_slice())));
+    try!(chapter(&mut w, current, item, opts, progress));
+    try!(write!(&mut w, "</div></body>\n</html>\n"));
+    try!(zip.add(format!("OEBPS/{}", file).as_slice(), w.get_ref(), true));
+
+    let idx = chapters.len();
+    chapters.push(Chapter { path: path, file: file, descendants: 0 });
+    current.push(item.name.get_ref().clone());
+    let mut submodules = m.items.iter().filter(|i| i.is_mod())
+                                .collect::<Vec<&clean::Item>>();
+    submodules.sort_by(|a, b| a.name.cmp(&b.name));
+    for sub in submodules.iter() {
+        try!(chapters_for(zip, chapters, current, *sub, opts, progress));
+    }
+    current.pop();
+    chapters.get_mut(idx).descendants = chapters.len() - idx - 1;
+    Ok(())
+}
+
+/// Generates the package document, listing every file in the book and the
+/// order the chapters are read in.
+fn package(title: &str, krate: &str, chapters: &[Chapter]) -> StrBuf {
+    let mut manifest = StrBuf::new();
+    let mut spine = StrBuf::new();
+    for (i, c) in chapters.iter().enumerate() {
+        manifest.push_str(format!("    <item id=\"c{}\" href=\"{}\" \
+                                   media-type=\"application/xhtml+xml\"/>\n",
+                                  i, Escape(c.file.as_slice())));
+        spine.push_str(format!("    <itemref idref=\"c{}\"/>\n", i));
+    }
+    format_strbuf!(
+r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>{title}</dc:title>
+    <dc:language>en</dc:language>
+    <dc:identifier id="uid">urn:rustdoc:{krate}</dc:identifier>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="css" href="main.css" media-type="text/css"/>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#, title = Escape(title), krate = Escape(krate), manifest = manifest, spine = spine)
+}
+
+/// Generates the table of contents, nesting the chapter of each module under
+/// that of its parent.
+fn ncx(title: &str, krate: &str, chapters: &[Chapter]) -> StrBuf {
+    fn points(out: &mut StrBuf, chapters: &[Chapter], start: uint) {
+        let mut i = 0;
+        while i < chapters.len() {
+            let c = &chapters[i];
+            out.push_str(format!("<navPoint id=\"n{id}\" playOrder=\"{order}\">\
+                                  <navLabel><text>{label}</text></navLabel>\
+                                  <content src=\"{src}\"/>\n",
+                                 id = start + i, order = start + i + 1,
+                                 label = Escape(c.path.connect("::").as_slice()),
+                                 src = Escape(c.file.as_slice())));
+            points(out, chapters.slice(i + 1, i + 1 + c.descendants), start + i + 1);
+            out.push_str("</navPoint>\n");
+            i += c.descendants + 1;
+        }
+    }
+    let mut nav = StrBuf::new();
+    points(&mut nav, chapters, 0);
+    format_strbuf!(
+r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+<head><meta name="dtb:uid" content="urn:rustdoc:{krate}"/></head>
+<docTitle><text>{title}</text></docTitle>
+<navMap>
+{nav}</navMap>
+</ncx>
+"#, title = Escape(title), krate = Escape(krate), nav = nav)
+}
@@ -10,10 +10,13 @@
 
 //! HTML Escaping
 //!
-//! This module contains one unit-struct which can be used to HTML-escape a
-//! string of text (for use in a format string).
+//! This module contains unit-structs which can be used to HTML-escape a string
+//! of text (for use in a format string), and to turn HTML back into the plain
+//! text it represents. Text can also be escaped as it's written, through
+//! `write_escaped` or an `EscapedWriter`.
 
 use std::fmt;
+use std::io;
 
 /// Wrapper struct which will emit the HTML-escaped version of the contained
 /// string when passed to a format string.
@@ -21,33 +24,144 @@ pub struct Escape<'a>(pub &'a str);
 
 impl<'a> fmt::Show for Escape<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        // Because the internet is always right, turns out there's not that many
-        // characters to escape: http://stackoverflow.com/questions/7381974
         let Escape(s) = *self;
-        let pile_o_bits = s.as_slice();
-        let mut last = 0;
-        for (i, ch) in s.bytes().enumerate() {
-            match ch as char {
-                '<' | '>' | '&' | '\'' | '"' => {
-                    try!(fmt.buf.write(pile_o_bits.slice(last, i).as_bytes()));
-                    let s = match ch as char {
-                        '>' => "&gt;",
-                        '<' => "&lt;",
-                        '&' => "&amp;",
-                        '\'' => "&#39;",
-                        '"' => "&quot;",
-                        _ => unreachable!()
-                    };
-                    try!(fmt.buf.write(s.as_bytes()));
-                    last = i + 1;
+        write_escaped(fmt.buf, s)
+    }
+}
+
+/// Writes the HTML-escaped version of `s` into `dst`.
+pub fn write_escaped(dst: &mut Writer, s: &str) -> io::IoResult<()> {
+    escape_bytes(dst, s.as_bytes())
+}
+
+/// Writes the HTML-escaped version of some UTF-8 into `dst`. The characters
+/// which are escaped are all ASCII, so the bytes can be split anywhere.
+fn escape_bytes(dst: &mut Writer, bytes: &[u8]) -> io::IoResult<()> {
+    // Because the internet is always right, turns out there's not that many
+    // characters to escape: http://stackoverflow.com/questions/7381974
+    let mut last = 0;
+    for (i, &ch) in bytes.iter().enumerate() {
+        let s = match ch as char {
+            '>' => "&gt;",
+            '<' => "&lt;",
+            '&' => "&amp;",
+            '\'' => "&#39;",
+            '"' => "&quot;",
+            _ => continue,
+        };
+        try!(dst.write(bytes.slice(last, i)));
+        try!(dst.write(s.as_bytes()));
+        last = i + 1;
+    }
+    dst.write(bytes.slice_from(last))
+}
+
+/// Writer which HTML-escapes everything written through it into another
+/// writer, so large pieces of text can be escaped as they're streamed out.
+pub struct EscapedWriter<W> {
+    inner: W,
+}
+
+impl<W: Writer> EscapedWriter<W> {
+    pub fn new(inner: W) -> EscapedWriter<W> {
+        EscapedWriter { inner: inner }
+    }
+
+    /// Returns the writer the escaped text was written into.
+    pub fn unwrap(self) -> W { self.inner }
+}
+
+impl<W: Writer> Writer for EscapedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        escape_bytes(&mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> io::IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wrapper struct which will emit the plain text represented by the contained
+/// HTML: all tags are dropped and the entities emitted by `Escape` are
+/// decoded. This lets backends which don't produce HTML reuse the
+/// pretty-printers in `html::format`.
+pub struct StripTags<'a>(pub &'a str);
+
+impl<'a> fmt::Show for StripTags<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let StripTags(s) = *self;
+        let mut rest = s;
+        loop {
+            let next = match rest.find(|c: char| c == '<' || c == '&') {
+                Some(i) => i,
+                None => break,
+            };
+            try!(fmt.buf.write(rest.slice_to(next).as_bytes()));
+            rest = rest.slice_from(next);
+            if rest.starts_with("<") {
+                rest = match rest.find('>') {
+                    Some(end) => rest.slice_from(end + 1),
+                    None => "",
+                };
+                continue
+            }
+            let entities = [("&lt;", "<"), ("&gt;", ">"), ("&amp;", "&"),
+                            ("&#39;", "'"), ("&quot;", "\""), ("&nbsp;", " ")];
+            match entities.iter().find(|&&(e, _)| rest.starts_with(e)) {
+                Some(&(e, c)) => {
+                    try!(fmt.buf.write(c.as_bytes()));
+                    rest = rest.slice_from(e.len());
+                }
+                None => {
+                    try!(fmt.buf.write("&".as_bytes()));
+                    rest = rest.slice_from(1);
                 }
-                _ => {}
             }
         }
+        fmt.buf.write(rest.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::MemWriter;
+    use std::str;
+    use super::{Escape, EscapedWriter, StripTags, write_escaped};
+
+    #[test]
+    fn escape() {
+        let s = "<a href='x'>&\"</a> é";
+        let expected = "&lt;a href=&#39;x&#39;&gt;&amp;&quot;&lt;/a&gt; é".to_owned();
+        assert_eq!(format!("{}", Escape(s)), expected);
+
+        let mut w = MemWriter::new();
+        write_escaped(&mut w, s).unwrap();
+        assert_eq!(str::from_utf8(w.unwrap().as_slice()).unwrap().to_owned(), expected);
 
-        if last < s.len() {
-            try!(fmt.buf.write(pile_o_bits.slice_from(last).as_bytes()));
+        // Splitting the text up doesn't change how it's escaped
+        let mut w = EscapedWriter::new(MemWriter::new());
+        for piece in s.as_bytes().chunks(3) {
+            w.write(piece).unwrap();
         }
-        Ok(())
+        assert_eq!(str::from_utf8(w.unwrap().unwrap().as_slice()).unwrap().to_owned(),
+                   expected);
+    }
+
+    #[test]
+    fn strip_tags() {
+        let t = |s: &str| format!("{}", StripTags(s));
+        assert_eq!(t("plain"), "plain".to_owned());
+        assert_eq!(t("<a class='struct' href='x.html'>Vec</a>&lt;T&gt;"),
+                   "Vec<T>".to_owned());
+        assert_eq!(t("&amp;'a mut self"), "&'a mut self".to_owned());
+        assert_eq!(t("a & b"), "a & b".to_owned());
+        assert_eq!(t("unterminated <a"), "unterminated ".to_owned());
+    }
+
+    #[test]
+    fn strip_tags_inverts_escape() {
+        let s = "<T: 'static> & \"quoted\"";
+        let escaped = format!("{}", Escape(s));
+        assert_eq!(format!("{}", StripTags(escaped.as_slice())), s.to_owned());
     }
 }
@@ -15,6 +15,7 @@
 //! assume that HTML output is desired, although it may be possible to redesign
 //! them in the future to instead emit any format desired.
 
+use std::cmp;
 use std::fmt;
 use std::io;
 use std::strbuf::StrBuf;
@@ -23,10 +24,12 @@ use syntax::ast;
 use syntax::ast_util;
 
 use clean;
+use html::escape::{Escape, StripTags};
 use html::item_type;
 use html::item_type::ItemType;
 use html::render;
-use html::render::{cache_key, current_location_key};
+use html::render::{cache_key, current_location_key, page_layout_key};
+use renderer::{PageLayout, Nested, Flat};
 
 /// Helper to render an optional visibility with a space after it (if the
 /// visibility is preset)
@@ -36,6 +39,27 @@ pub struct VisSpace(pub Option<ast::Visibility>);
 pub struct FnStyleSpace(pub ast::FnStyle);
 /// Wrapper struct for properly emitting a method declaration.
 pub struct Method<'a>(pub &'a clean::SelfTy, pub &'a clean::FnDecl);
+/// Wrapper struct for emitting the declaration of a function, or of a method
+/// along with its `self` argument, with each argument on a line of its own
+/// lined up after the opening parenthesis if the signature would otherwise
+/// be wider than `width` characters. `offset` is how many characters of the
+/// line come before the declaration.
+pub struct WrappedDecl<'a> {
+    pub selfty: Option<&'a clean::SelfTy>,
+    pub decl: &'a clean::FnDecl,
+    pub offset: uint,
+    pub width: uint,
+}
+/// Renders the stability of an item as a badge naming its level and the
+/// version it attained it in, with the reason given for it as the title.
+/// Nothing is rendered for items without a stability attribute.
+pub struct StabilityBadge<'a>(pub &'a clean::Item);
+/// Flags an item which can't be seen from outside its crate, for when private
+/// items are documented.
+pub struct PrivateBadge<'a>(pub &'a clean::Item);
+/// Flags an item marked `#[doc(hidden)]`, for when hidden items are
+/// documented.
+pub struct HiddenBadge<'a>(pub &'a clean::Item);
 
 impl VisSpace {
     pub fn get(&self) -> Option<ast::Visibility> {
@@ -151,13 +175,11 @@ fn resolved_path(w: &mut io::Writer, did: ast::DefId, p: &clean::Path,
     path(w, p, print_all,
         |cache, loc| {
             if ast_util::is_local(did) {
-                Some(("../".repeat(loc.len())).to_strbuf())
+                Some(root_path(loc))
             } else {
                 match *cache.extern_locations.get(&did.krate) {
                     render::Remote(ref s) => Some(s.to_strbuf()),
-                    render::Local => {
-                        Some(("../".repeat(loc.len())).to_strbuf())
-                    }
+                    render::Local => Some(root_path(loc)),
                     render::Unknown => None,
                 }
             }
@@ -170,6 +192,22 @@ fn resolved_path(w: &mut io::Writer, did: ast::DefId, p: &clean::Path,
         })
 }
 
+/// The layout of the pages being rendered, nested unless a renderer has said
+/// otherwise.
+fn page_layout() -> PageLayout {
+    page_layout_key.get().map_or(Nested, |layout| *layout)
+}
+
+/// The relative path to the root of the output from the page of the current
+/// location `loc`.
+fn root_path(loc: &[StrBuf]) -> StrBuf {
+    let depth = match page_layout() {
+        Nested => loc.len(),
+        Flat => cmp::min(loc.len(), 1),
+    };
+    "../".repeat(depth).to_strbuf()
+}
+
 fn path(w: &mut io::Writer, path: &clean::Path, print_all: bool,
         root: |&render::Cache, &[StrBuf]| -> Option<StrBuf>,
         info: |&render::Cache| -> Option<(Vec<StrBuf> , ItemType)>)
@@ -196,27 +234,28 @@ fn path(w: &mut io::Writer, path: &clean::Path, print_all: bool,
 
     let loc = current_location_key.get().unwrap();
     let cache = cache_key.get().unwrap();
+    let layout = page_layout();
     let abs_root = root(&**cache, loc.as_slice());
     let rel_root = match path.segments.get(0).name.as_slice() {
-        "self" => Some("./".to_owned()),
+        "self" => Some(Vec::from_slice(loc.as_slice())),
         _ => None,
     };
 
     if print_all {
         let amt = path.segments.len() - 1;
         match rel_root {
-            Some(root) => {
-                let mut root = StrBuf::from_str(root);
+            Some(mut module) => {
+                let root = root_path(loc.as_slice());
                 for seg in path.segments.slice_to(amt).iter() {
                     if "super" == seg.name.as_slice() ||
                             "self" == seg.name.as_slice() {
                         try!(write!(w, "{}::", seg.name));
                     } else {
-                        root.push_str(seg.name.as_slice());
-                        root.push_str("/");
+                        module.push(seg.name.clone());
                         try!(write!(w, "<a class='mod'
-                                            href='{}index.html'>{}</a>::",
-                                      root.as_slice(),
+                                            href='{}{}'>{}</a>::",
+                                      root,
+                                      render::module_page(layout, module.as_slice()),
                                       seg.name));
                     }
                 }
@@ -233,21 +272,15 @@ fn path(w: &mut io::Writer, path: &clean::Path, print_all: bool,
         // This is a documented path, link to it!
         Some((ref fqp, shortty)) if abs_root.is_some() => {
             let mut url = StrBuf::from_str(abs_root.unwrap().as_slice());
-            let to_link = fqp.slice_to(fqp.len() - 1);
-            for component in to_link.iter() {
-                url.push_str(component.as_slice());
-                url.push_str("/");
-            }
             match shortty {
                 item_type::Module => {
-                    url.push_str(fqp.last().unwrap().as_slice());
-                    url.push_str("/index.html");
+                    url.push_str(render::module_page(layout, fqp.as_slice()).as_slice());
                 }
                 _ => {
-                    url.push_str(shortty.to_static_str());
-                    url.push_str(".");
-                    url.push_str(fqp.last().unwrap().as_slice());
-                    url.push_str(".html");
+                    let page = render::item_page(layout, fqp.slice_to(fqp.len() - 1),
+                                                 shortty.to_static_str(),
+                                                 fqp.last().unwrap().as_slice());
+                    url.push_str(page.as_slice());
                 }
             }
 
@@ -281,6 +314,28 @@ fn tybounds(w: &mut io::Writer,
     }
 }
 
+/// The name a primitive type is written with.
+pub fn primitive_name(prim: ast::PrimTy) -> &'static str {
+    match prim {
+        ast::TyInt(ast::TyI) => "int",
+        ast::TyInt(ast::TyI8) => "i8",
+        ast::TyInt(ast::TyI16) => "i16",
+        ast::TyInt(ast::TyI32) => "i32",
+        ast::TyInt(ast::TyI64) => "i64",
+        ast::TyUint(ast::TyU) => "uint",
+        ast::TyUint(ast::TyU8) => "u8",
+        ast::TyUint(ast::TyU16) => "u16",
+        ast::TyUint(ast::TyU32) => "u32",
+        ast::TyUint(ast::TyU64) => "u64",
+        ast::TyFloat(ast::TyF32) => "f32",
+        ast::TyFloat(ast::TyF64) => "f64",
+        ast::TyFloat(ast::TyF128) => "f128",
+        ast::TyStr => "str",
+        ast::TyBool => "bool",
+        ast::TyChar => "char",
+    }
+}
+
 impl fmt::Show for clean::Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -293,27 +348,7 @@ impl fmt::Show for clean::Type {
                 tybounds(f.buf, typarams)
             }
             clean::Self(..) => f.buf.write("Self".as_bytes()),
-            clean::Primitive(prim) => {
-                let s = match prim {
-                    ast::TyInt(ast::TyI) => "int",
-                    ast::TyInt(ast::TyI8) => "i8",
-                    ast::TyInt(ast::TyI16) => "i16",
-                    ast::TyInt(ast::TyI32) => "i32",
-                    ast::TyInt(ast::TyI64) => "i64",
-                    ast::TyUint(ast::TyU) => "uint",
-                    ast::TyUint(ast::TyU8) => "u8",
-                    ast::TyUint(ast::TyU16) => "u16",
-                    ast::TyUint(ast::TyU32) => "u32",
-                    ast::TyUint(ast::TyU64) => "u64",
-                    ast::TyFloat(ast::TyF32) => "f32",
-                    ast::TyFloat(ast::TyF64) => "f64",
-                    ast::TyFloat(ast::TyF128) => "f128",
-                    ast::TyStr => "str",
-                    ast::TyBool => "bool",
-                    ast::TyChar => "char",
-                };
-                f.buf.write(s.as_bytes())
-            }
+            clean::Primitive(prim) => f.buf.write(primitive_name(prim).as_bytes()),
             clean::Closure(ref decl, ref region) => {
                 write!(f.buf, "{style}{lifetimes}|{args}|{bounds}\
                                {arrow, select, yes{ -&gt; {ret}} other{}}",
@@ -447,42 +482,70 @@ impl fmt::Show for clean::FnDecl {
     }
 }
 
+/// The arguments of a function declaration, each rendered on its own and
+/// starting with the `self` argument of a method.
+fn arguments(selfty: Option<&clean::SelfTy>, d: &clean::FnDecl) -> Vec<StrBuf> {
+    let mut args = Vec::new();
+    match selfty {
+        Some(&clean::SelfStatic) | None => {},
+        Some(&clean::SelfValue) => args.push("self".to_strbuf()),
+        Some(&clean::SelfOwned) => args.push("~self".to_strbuf()),
+        Some(&clean::SelfBorrowed(Some(ref lt), clean::Immutable)) => {
+            args.push(format_strbuf!("&amp;{} self", *lt));
+        }
+        Some(&clean::SelfBorrowed(Some(ref lt), clean::Mutable)) => {
+            args.push(format_strbuf!("&amp;{} mut self", *lt));
+        }
+        Some(&clean::SelfBorrowed(None, clean::Mutable)) => {
+            args.push("&amp;mut self".to_strbuf());
+        }
+        Some(&clean::SelfBorrowed(None, clean::Immutable)) => {
+            args.push("&amp;self".to_strbuf());
+        }
+    }
+    for input in d.inputs.values.iter() {
+        if input.name.len() > 0 {
+            args.push(format_strbuf!("{}: {}", input.name, input.type_));
+        } else {
+            args.push(format_strbuf!("{}", input.type_));
+        }
+    }
+    args
+}
+
+/// The number of characters the HTML `s` shows as.
+pub fn text_len(s: &str) -> uint {
+    format!("{}", StripTags(s)).as_slice().char_len()
+}
+
 impl<'a> fmt::Show for Method<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let Method(selfty, d) = *self;
-        let mut args = StrBuf::new();
-        match *selfty {
-            clean::SelfStatic => {},
-            clean::SelfValue => args.push_str("self"),
-            clean::SelfOwned => args.push_str("~self"),
-            clean::SelfBorrowed(Some(ref lt), clean::Immutable) => {
-                args.push_str(format!("&amp;{} self", *lt));
-            }
-            clean::SelfBorrowed(Some(ref lt), clean::Mutable) => {
-                args.push_str(format!("&amp;{} mut self", *lt));
-            }
-            clean::SelfBorrowed(None, clean::Mutable) => {
-                args.push_str("&amp;mut self");
-            }
-            clean::SelfBorrowed(None, clean::Immutable) => {
-                args.push_str("&amp;self");
-            }
-        }
-        for (i, input) in d.inputs.values.iter().enumerate() {
-            if i > 0 || args.len() > 0 { args.push_str(", "); }
-            if input.name.len() > 0 {
-                args.push_str(format!("{}: ", input.name));
-            }
-            args.push_str(format!("{}", input.type_));
-        }
         write!(f.buf,
                "({args}){arrow, select, yes{ -&gt; {ret}} other{}}",
-               args = args,
+               args = arguments(Some(selfty), d).connect(", "),
                arrow = match d.output { clean::Unit => "no", _ => "yes" },
                ret = d.output)
     }
 }
 
+impl<'a> fmt::Show for WrappedDecl<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = arguments(self.selfty, self.decl);
+        let ret = match self.decl.output {
+            clean::Unit => StrBuf::new(),
+            ref t => format_strbuf!(" -&gt; {}", *t),
+        };
+        let line = args.connect(", ");
+        let len = self.offset + text_len(line.as_slice()) + text_len(ret.as_slice()) + 2;
+        if len <= self.width || args.len() < 2 {
+            return write!(f.buf, "({}){}", line, ret)
+        }
+        let sep = format!(",\n{}", " ".repeat(self.offset + 1));
+        write!(f.buf, "({}){}", args.connect(sep.as_slice()), ret)
+    }
+}
+
 impl fmt::Show for VisSpace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.get() {
@@ -501,6 +564,46 @@ impl fmt::Show for FnStyleSpace {
     }
 }
 
+impl<'a> fmt::Show for StabilityBadge<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let StabilityBadge(item) = *self;
+        let stab = match item.stability() {
+            Some(stab) => stab,
+            None => return Ok(()),
+        };
+        try!(write!(f.buf, "<span class='stability {lvl}' title='{reason}'>{lvl}",
+                    lvl = stab.level,
+                    reason = Escape(stab.reason.as_ref().map(|s| s.as_slice())
+                                                        .unwrap_or(""))));
+        match stab.since {
+            Some(ref since) => {
+                try!(write!(f.buf, " since {}", Escape(since.as_slice())));
+            }
+            None => {}
+        }
+        write!(f.buf, "</span>")
+    }
+}
+
+impl<'a> fmt::Show for PrivateBadge<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let PrivateBadge(item) = *self;
+        let cache = cache_key.get().unwrap();
+        if !cache.private_items.contains(&item.id) { return Ok(()) }
+        write!(f.buf, "<span class='private' title='Not visible outside the crate'>\
+                       private</span>")
+    }
+}
+
+impl<'a> fmt::Show for HiddenBadge<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let HiddenBadge(item) = *self;
+        if !item.is_hidden_from_doc() { return Ok(()) }
+        write!(f.buf, "<span class='hidden-item' title='Hidden from the documentation by \
+                       default'>hidden</span>")
+    }
+}
+
 impl fmt::Show for clean::ViewPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
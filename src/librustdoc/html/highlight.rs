@@ -20,12 +20,20 @@ use syntax::parse;
 use syntax::parse::lexer;
 use syntax::codemap::{BytePos, Span};
 
-use html::escape::Escape;
+use html::escape::{Escape, write_escaped};
 
 use t = syntax::parse::token;
 
 /// Highlights some source code, returning the HTML output.
 pub fn highlight(src: &str, class: Option<&str>) -> StrBuf {
+    highlight_linked(src, class, |_, _| None)
+}
+
+/// Highlights some source code like `highlight`, and hyperlinks each
+/// identifier for which `link` returns where it should link to, given the
+/// line the identifier is on and its name.
+pub fn highlight_linked(src: &str, class: Option<&str>,
+                        link: |uint, &str| -> Option<StrBuf>) -> StrBuf {
     debug!("highlighting: ================\n{}\n==============", src);
     let sess = parse::new_parse_sess();
     let fm = parse::string_to_filemap(&sess,
@@ -36,6 +44,7 @@ pub fn highlight(src: &str, class: Option<&str>) -> StrBuf {
     doit(&sess,
          lexer::new_string_reader(&sess.span_diagnostic, fm),
          class,
+         link,
          &mut out).unwrap();
     str::from_utf8_lossy(out.unwrap().as_slice()).to_strbuf()
 }
@@ -48,7 +57,7 @@ pub fn highlight(src: &str, class: Option<&str>) -> StrBuf {
 /// not from the tokens themselves, in order to stay true to the original
 /// source.
 fn doit(sess: &parse::ParseSess, mut lexer: lexer::StringReader, class: Option<&str>,
-        out: &mut Writer) -> io::IoResult<()> {
+        link: |uint, &str| -> Option<StrBuf>, out: &mut Writer) -> io::IoResult<()> {
     use syntax::parse::lexer::Reader;
 
     try!(write!(out, "<pre class='rust {}'>\n", class.unwrap_or("")));
@@ -77,7 +86,7 @@ fn doit(sess: &parse::ParseSess, mut lexer: lexer::StringReader, class: Option<&
                 try!(write!(out, "<span class='comment'>{}</span>",
                               Escape(snip.as_slice())));
             } else {
-                try!(write!(out, "{}", Escape(snip.as_slice())));
+                try!(write_escaped(out, snip.as_slice()));
             }
         }
         last = next.sp.hi;
@@ -173,8 +182,19 @@ fn doit(sess: &parse::ParseSess, mut lexer: lexer::StringReader, class: Option<&
         // as mentioned above, use the original source code instead of
         // stringifying this token
         let snip = sess.span_diagnostic.cm.span_to_snippet(next.sp).unwrap();
+        if klass == "ident" {
+            let line = sess.span_diagnostic.cm.lookup_char_pos(next.sp.lo).line;
+            match link(line, snip.as_slice()) {
+                Some(href) => {
+                    try!(write!(out, "<a class='ident' href='{}'>{}</a>",
+                                Escape(href.as_slice()), Escape(snip.as_slice())));
+                    continue
+                }
+                None => {}
+            }
+        }
         if klass == "" {
-            try!(write!(out, "{}", Escape(snip.as_slice())));
+            try!(write_escaped(out, snip.as_slice()));
         } else {
             try!(write!(out, "<span class='{}'>{}</span>", klass,
                           Escape(snip.as_slice())));
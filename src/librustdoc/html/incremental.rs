@@ -0,0 +1,237 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Incremental rendering of the HTML output
+//!
+//! When rendering incrementally, a hash of everything which goes into each
+//! page is recorded in a file next to the crate's pages. On the next run,
+//! pages whose hash is unchanged and which are still on disk are left alone
+//! instead of being rendered again.
+//!
+//! The hash of a page covers the item it documents (its docs, signature,
+//! contents and source location), the impls and implementors listed on it,
+//! the traits those impls are of (for their default methods) and the
+//! sidebar. It's seeded with everything the pages of the whole crate depend
+//! on: the version of rustdoc, the page layout and the paths which links to
+//! other items are resolved with. Editing the docs of an item thus re-renders
+//! only its own page and those of the modules it's in, while adding,
+//! removing or moving an item re-renders everything.
+
+use collections::{HashMap, TreeMap};
+use std::hash::sip::SipState;
+use std::io;
+use std::io::{File, BufferedReader};
+use std::num;
+use std::strbuf::StrBuf;
+use serialize::Encodable;
+use serialize::json;
+use syntax::ast_util;
+
+use clean;
+use html::render::{Cache, Context, PathType, OtherType, BlanketType, cache_key};
+use output::OutputSink;
+
+/// The name of the file the hashes are recorded in, within the directory of
+/// the crate's pages.
+pub static HASHES_FILE: &'static str = ".render-hashes";
+
+/// The hashes of the pages rendered on this run and on the last one.
+pub struct Hashes {
+    /// The directory on disk the output is in, or `None` if every page is to
+    /// be rendered
+    root: Option<Path>,
+    seed: u64,
+    old: HashMap<StrBuf, u64>,
+    new: TreeMap<StrBuf, u64>,
+}
+
+impl Hashes {
+    /// Hashes which never consider a page unchanged, and aren't recorded.
+    pub fn disabled() -> Hashes {
+        Hashes { root: None, seed: 0, old: HashMap::new(), new: TreeMap::new() }
+    }
+
+    /// Loads the hashes recorded at `file` of the output placed into the
+    /// directory `root`, if there are any. `cx` and `cache` are those the
+    /// crate is about to be rendered with.
+    pub fn load(root: Path, file: &Path, cx: &Context,
+                cache: &Cache) -> io::IoResult<Hashes> {
+        let mut old = HashMap::new();
+        let path = root.join(file);
+        if path.exists() {
+            let mut reader = BufferedReader::new(try!(File::open(&path)));
+            for line in reader.lines() {
+                let line = try!(line);
+                let line = line.trim_right();
+                let (hash, page) = match line.find(' ') {
+                    Some(i) => (line.slice_to(i), line.slice_from(i + 1)),
+                    None => continue,
+                };
+                match num::from_str_radix::<u64>(hash, 16) {
+                    Some(hash) => { old.insert(page.to_strbuf(), hash); }
+                    None => {}
+                }
+            }
+        }
+        Ok(Hashes {
+            root: Some(root),
+            seed: seed(cx, cache),
+            old: old,
+            new: TreeMap::new(),
+        })
+    }
+
+    /// Records the hash of the page at `dst` of `sink` documenting `item`,
+    /// returning whether it's the same as on the last run, so the page
+    /// doesn't need to be rendered again.
+    pub fn unchanged(&mut self, sink: &OutputSink, dst: &Path, cx: &Context,
+                     item: &clean::Item) -> bool {
+        if self.root.is_none() { return false }
+        let hash = page_hash(self.seed, cx, item);
+        // Paths which aren't UTF-8 have the invalid parts replaced, as the
+        // file of hashes lists them as text
+        let key = format_strbuf!("{}", dst.display());
+        let unchanged = self.old.find(&key) == Some(&hash) && sink.exists(dst);
+        self.new.insert(key, hash);
+        unchanged
+    }
+
+    /// Records the hashes of this run at `file` of `sink`.
+    pub fn save(&self, sink: &mut OutputSink, file: &Path) -> io::IoResult<()> {
+        if self.root.is_none() { return Ok(()) }
+        let mut w = try!(sink.create(file));
+        for (page, hash) in self.new.iter() {
+            try!(write!(&mut w, "{:016x} {}\n", *hash, *page));
+        }
+        Ok(())
+    }
+}
+
+/// Hashes everything the pages of a crate depend on besides their own items.
+fn seed(cx: &Context, cache: &Cache) -> u64 {
+    let mut state = SipState::new();
+    let version = option_env!("CFG_VERSION").unwrap_or("unknown");
+    let layout = &cx.layout;
+    for s in [version, layout.logo.as_slice(), layout.favicon.as_slice(),
+              layout.krate.as_slice(),
+              layout.external_html.in_header.as_slice(),
+              layout.external_html.before_content.as_slice(),
+              layout.external_html.after_content.as_slice()].iter() {
+        write(&mut state, *s);
+    }
+    for &(kind, ref html) in layout.external_html.pages.iter() {
+        for s in [html.in_header.as_slice(), html.before_content.as_slice(),
+                  html.after_content.as_slice()].iter() {
+            write(&mut state, format!("{} {}", kind, *s).as_slice());
+        }
+    }
+    write(&mut state, layout.template.as_ref().map(|s| s.as_slice()).unwrap_or(""));
+    write(&mut state, cx.include_sources.to_str().as_slice());
+    write(&mut state, layout.theme.as_slice());
+    write(&mut state, layout.version.as_ref().map(|s| s.as_slice()).unwrap_or(""));
+    write(&mut state, layout.canonical_url.as_ref().map(|s| s.as_slice()).unwrap_or(""));
+    write(&mut state, layout.offline.to_str().as_slice());
+    write(&mut state, layout.minify.to_str().as_slice());
+    write(&mut state, layout.math.as_ref().map(|s| s.as_slice()).unwrap_or(""));
+    write(&mut state, layout.page_layout.to_str().as_slice());
+    write(&mut state, cx.signature_width.to_str().as_slice());
+    write(&mut state, cx.item_order.to_str().as_slice());
+    for &(n, ref name) in cx.externs.iter() {
+        write(&mut state, format!("{} {}", n, *name).as_slice());
+    }
+    write(&mut state, layout.themes.connect(" ").as_slice());
+
+    let mut paths = cache.paths.iter().map(|(did, &(ref fqp, ty))| {
+        (did.krate, did.node, fqp, ty as uint)
+    }).collect::<Vec<_>>();
+    paths.sort();
+    for &(krate, node, fqp, ty) in paths.iter() {
+        write(&mut state, format!("{} {} {} {}", krate, node, fqp.connect("::"),
+                                  ty).as_slice());
+    }
+    let mut locations = cache.extern_locations.iter().map(|(&n, l)| {
+        (n, format!("{:?}", *l))
+    }).collect::<Vec<_>>();
+    locations.sort();
+    for &(n, ref l) in locations.iter() {
+        write(&mut state, format!("{} {}", n, *l).as_slice());
+    }
+    state.result()
+}
+
+/// Hashes everything which goes into the page documenting `item`.
+fn page_hash(seed: u64, cx: &Context, item: &clean::Item) -> u64 {
+    let mut state = SipState::new();
+    write(&mut state, format!("{:016x}", seed).as_slice());
+    write(&mut state, cx.current.connect("::").as_slice());
+    write(&mut state, cx.root_path.as_slice());
+    let mut sidebar = cx.sidebar.iter().collect::<Vec<_>>();
+    sidebar.sort();
+    for &(section, items) in sidebar.iter() {
+        write(&mut state, format!("{}: {}", *section, items.connect(" ")).as_slice());
+    }
+
+    encode(&mut state, item);
+    match cx.inline_source(item) {
+        Some(src) => write(&mut state, src.as_slice()),
+        None => {}
+    }
+    let cache = cache_key.get().unwrap();
+    match cache.impls.find(&item.id) {
+        Some(impls) => {
+            for &(ref i, ref dox) in impls.iter() {
+                encode(&mut state, i);
+                encode(&mut state, dox);
+                // The default methods of the trait are documented with it
+                match i.trait_ {
+                    Some(clean::ResolvedPath { did, .. }) if ast_util::is_local(did) => {
+                        match cache.traits.find(&did.node) {
+                            Some(t) => encode(&mut state, t),
+                            None => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None => {}
+    }
+    match cache.implementors.find(&item.id) {
+        Some(implementors) => {
+            for i in implementors.iter() {
+                match *i {
+                    PathType(ref ty) => encode(&mut state, ty),
+                    OtherType(ref generics, ref trait_, ref for_) |
+                    BlanketType(ref generics, ref trait_, ref for_) => {
+                        encode(&mut state, generics);
+                        encode(&mut state, trait_);
+                        encode(&mut state, for_);
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+    state.result()
+}
+
+/// Feeds the JSON encoding of a value into a hash.
+fn encode<'a, T: Encodable<json::Encoder<'a>, io::IoError>>(state: &mut SipState, t: &T) {
+    // Writing to a `SipState` can't fail
+    let _ = state.write(json::Encoder::buffer_encode(t).as_slice());
+}
+
+/// Feeds a string into a hash, terminated so that consecutive strings can't
+/// run into each other.
+fn write(state: &mut SipState, s: &str) {
+    // Writing to a `SipState` can't fail
+    let _ = state.write(s.as_bytes());
+    let _ = state.write([0xff]);
+}
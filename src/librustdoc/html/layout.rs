@@ -10,24 +10,88 @@
 
 use std::fmt;
 use std::io;
+use std::io::MemWriter;
+use std::str;
+
+use externalfiles::ExternalHtml;
+use html::escape::Escape;
+use html::minify;
+use renderer::{PageLayout, Flat};
 
 #[deriving(Clone)]
 pub struct Layout {
+    /// The URL of the logo, or its path relative to the root of the output if
+    /// it was copied there, or empty for none
     pub logo: StrBuf,
+    /// The URL or path of the favicon, the same way as `logo`
     pub favicon: StrBuf,
+    pub external_html: ExternalHtml,
     pub krate: StrBuf,
+    /// The names of the themes which may be picked, each with a stylesheet
+    /// named after it in the root of the output
+    pub themes: Vec<StrBuf>,
+    /// The theme pages are shown in unless the reader picks another one
+    pub theme: StrBuf,
+    /// A template supplied by the user to lay pages out with instead of the
+    /// built-in one, see `render_template`
+    pub template: Option<StrBuf>,
+    /// The version of the crate, shown at the top of the sidebar
+    pub version: Option<StrBuf>,
+    /// The URL the output is published at, ending in a slash, which pages
+    /// link to as their canonical location
+    pub canonical_url: Option<StrBuf>,
+    /// Whether pages may only load files from the output itself, so they can
+    /// be read without a network connection
+    pub offline: bool,
+    /// Whether pages are minified, see `html::minify`
+    pub minify: bool,
+    /// The URL or path of the script typesetting the math in the docs, if
+    /// there's any to typeset, see `html::markdown::enable_math`
+    pub math: Option<StrBuf>,
+    /// Where the pages of the items are, which the search JS needs to know
+    /// to link to them
+    pub page_layout: PageLayout,
 }
 
 pub struct Page<'a> {
     pub title: &'a str,
     pub ty: &'a str,
     pub root_path: &'a str,
+    /// The location of the page relative to the root of the output, with
+    /// components separated by `/`
+    pub path: &'a str,
 }
 
 pub fn render<T: fmt::Show, S: fmt::Show>(
     dst: &mut io::Writer, layout: &Layout, page: &Page, sidebar: &S, t: &T)
     -> fmt::Result
 {
+    if layout.minify {
+        let mut w = MemWriter::new();
+        try!(render_page(&mut w, layout, page, sidebar, t));
+        let html = str::from_utf8(w.get_ref()).unwrap_or("");
+        return dst.write(minify::html(html).as_bytes())
+    }
+    render_page(dst, layout, page, sidebar, t)
+}
+
+fn render_page<T: fmt::Show, S: fmt::Show>(
+    dst: &mut io::Writer, layout: &Layout, page: &Page, sidebar: &S, t: &T)
+    -> fmt::Result
+{
+    let external_html = layout.external_html.for_page(page.ty);
+    match layout.template {
+        Some(ref template) => {
+            return render_template(dst, template.as_slice(), layout, &external_html,
+                                   page, sidebar, t)
+        }
+        None => {}
+    }
+    let logo = asset_url(page.root_path, layout.logo.as_slice());
+    let favicon = asset_url(page.root_path, layout.favicon.as_slice());
+    let math = layout.math.as_ref().map_or(StrBuf::new(), |m| {
+        asset_url(page.root_path, m.as_slice())
+    });
     write!(dst,
 r##"<!DOCTYPE html>
 <html lang="en">
@@ -37,12 +101,17 @@ r##"<!DOCTYPE html>
     <meta name="description" content="The {krate} library documentation.">
 
     <title>{title}</title>
+    <meta name="color-scheme" content="{color_scheme}">
 
+    {offline, select, yes{} other{
     <link href='http://fonts.googleapis.com/css?family=Source+Code+Pro:400,600'
-          rel='stylesheet' type='text/css'>
+          rel='stylesheet' type='text/css'>}}
     <link rel="stylesheet" type="text/css" href="{root_path}main.css">
+    <link rel="stylesheet" type="text/css" href="{root_path}{theme}.css" id="themeStyle">
+    {canonical}
 
-    {favicon, select, none{} other{<link rel="shortcut icon" href="#">}}
+    {favicon, select, none{} other{<link rel="shortcut icon" href="{favicon_url}">}}
+    {in_header}
 </head>
 <body>
     <!--[if lte IE 8]>
@@ -52,10 +121,13 @@ r##"<!DOCTYPE html>
     </div>
     <![endif]-->
 
+    {before_content}
+
     <section class="sidebar">
         {logo, select, none{} other{
-            <a href='{root_path}{krate}/index.html'><img src='#' alt='' width='100'></a>
+            <a href='{root_path}{krate}/index.html'><img src='{logo_url}' alt='' width='100'></a>
         }}
+        {version}
 
         {sidebar}
     </section>
@@ -69,6 +141,8 @@ r##"<!DOCTYPE html>
                        type="search">
             </div>
         </form>
+        {themes}
+        <select class='version-selector hidden'></select>
     </nav>
 
     <section id='main' class="content {ty}">{content}</section>
@@ -110,23 +184,254 @@ r##"<!DOCTYPE html>
     <script>
         var rootPath = "{root_path}";
         var currentCrate = "{krate}";
+        var currentVersion = "{current_version}";
+        var flatLayout = {flat};
     </script>
     <script src="{root_path}jquery.js"></script>
     <script src="{root_path}main.js"></script>
     <script async src="{root_path}search-index.js"></script>
+    {math, select, none{} other{<script async src="{math_url}"></script>}}
+
+    {after_content}
 </body>
 </html>"##,
     content   = *t,
     root_path = page.root_path,
     ty        = page.ty,
     logo      = nonestr(layout.logo.as_slice()),
+    logo_url  = logo,
     title     = page.title,
     favicon   = nonestr(layout.favicon.as_slice()),
+    favicon_url = favicon,
+    math      = nonestr(math.as_slice()),
+    math_url  = math,
+    offline   = if layout.offline { "yes" } else { "no" },
     sidebar   = *sidebar,
     krate     = layout.krate,
+    theme     = layout.theme,
+    color_scheme = color_scheme(layout.theme.as_slice()),
+    version   = Version(&layout.version),
+    canonical = Canonical(layout, page),
+    current_version = layout.version.as_ref().map(|v| v.as_slice()).unwrap_or(""),
+    flat      = layout.page_layout == Flat,
+    themes    = ThemePicker(layout.themes.as_slice(), layout.theme.as_slice()),
+    in_header = external_html.in_header,
+    before_content = external_html.before_content,
+    after_content = external_html.after_content,
     )
 }
 
+/// Renders a page into the user's template, in which each `{{name}}` is
+/// replaced by the value of the variable `name`:
+///
+/// * `title`, `krate`, `root_path` and `ty`: the title of the page, the name
+///   of the crate, the relative path to the root of the output and the type
+///   of the page
+/// * `head`: the stylesheets, color scheme, canonical link and favicon, to be
+///   placed in `<head>`
+/// * `in_header`, `before_content` and `after_content`: the external HTML
+///   supplied for the page
+/// * `logo`, `version`, `sidebar`, `search` and `content`: the logo, the
+///   version of the crate, the contents of the sidebar, the search box along
+///   with the theme and version pickers, and the documentation itself
+/// * `scripts`: the scripts for searching, typesetting math and such, to be
+///   placed at the end of `<body>`
+///
+/// For searching to work, `content` must be placed within an element with
+/// the id `main`, followed by an empty element with the id `search`.
+fn render_template<T: fmt::Show, S: fmt::Show>(
+    dst: &mut io::Writer, template: &str, layout: &Layout,
+    external_html: &ExternalHtml, page: &Page, sidebar: &S, t: &T) -> fmt::Result
+{
+    let root_path = page.root_path;
+    let out = substitute(template, |name| {
+        Some(match name {
+            "title" => page.title.to_strbuf(),
+            "krate" => layout.krate.clone(),
+            "root_path" => root_path.to_strbuf(),
+            "ty" => page.ty.to_strbuf(),
+            "head" => {
+                let mut head = format_strbuf!(
+                    "<meta name=\"color-scheme\" content=\"{scheme}\">\n\
+                     <link rel=\"stylesheet\" type=\"text/css\" href=\"{root}main.css\">\n\
+                     <link rel=\"stylesheet\" type=\"text/css\" href=\"{root}{theme}.css\" \
+                           id=\"themeStyle\">\n",
+                    root = root_path, theme = layout.theme,
+                    scheme = color_scheme(layout.theme.as_slice()));
+                head.push_str(format!("{}", Canonical(layout, page)).as_slice());
+                if layout.favicon.len() > 0 {
+                    head.push_str(format!("<link rel=\"shortcut icon\" href=\"{}\">\n",
+                                          asset_url(root_path,
+                                                    layout.favicon.as_slice())).as_slice());
+                }
+                head
+            }
+            "in_header" => external_html.in_header.clone(),
+            "before_content" => external_html.before_content.clone(),
+            "after_content" => external_html.after_content.clone(),
+            "logo" if layout.logo.len() == 0 => StrBuf::new(),
+            "logo" => {
+                format_strbuf!("<a href='{}{}/index.html'><img src='{}' alt='' \
+                                width='100'></a>",
+                               root_path, layout.krate,
+                               asset_url(root_path, layout.logo.as_slice()))
+            }
+            "version" => format_strbuf!("{}", Version(&layout.version)),
+            "sidebar" => format_strbuf!("{}", *sidebar),
+            "search" => {
+                format_strbuf!("<form class=\"search-form js-only\">\
+                                <div class=\"search-container\">\
+                                <input class=\"search-input\" name=\"search\" \
+                                autocomplete=\"off\" \
+                                placeholder=\"Search documentation...\" type=\"search\">\
+                                </div></form>{}\
+                                <select class='version-selector hidden'></select>",
+                               ThemePicker(layout.themes.as_slice(),
+                                           layout.theme.as_slice()))
+            }
+            "content" => format_strbuf!("{}", *t),
+            "scripts" => {
+                let mut scripts = format_strbuf!("<script>var rootPath = \"{root}\"; \
+                                var currentCrate = \"{krate}\"; \
+                                var currentVersion = \"{version}\"; \
+                                var flatLayout = {flat};</script>\n\
+                                <script src=\"{root}jquery.js\"></script>\n\
+                                <script src=\"{root}main.js\"></script>\n\
+                                <script async src=\"{root}search-index.js\"></script>\n",
+                               root = root_path, krate = layout.krate,
+                               flat = layout.page_layout == Flat,
+                               version = layout.version.as_ref().map(|v| v.as_slice())
+                                                        .unwrap_or(""));
+                match layout.math {
+                    Some(ref math) => {
+                        scripts.push_str(format!("<script async src=\"{}\"></script>\n",
+                                                 asset_url(root_path, math.as_slice()))
+                                         .as_slice());
+                    }
+                    None => {}
+                }
+                scripts
+            }
+            _ => return None,
+        })
+    });
+    dst.write(out.as_bytes())
+}
+
+/// Replaces each `{{name}}` in `template` with `lookup(name)`. Names which
+/// aren't known are left alone.
+fn substitute(template: &str, lookup: |&str| -> Option<StrBuf>) -> StrBuf {
+    let mut out = StrBuf::new();
+    let mut rest = template;
+    loop {
+        let start = match rest.find_str("{{") {
+            Some(i) => i,
+            None => break,
+        };
+        out.push_str(rest.slice_to(start));
+        rest = rest.slice_from(start);
+        let end = match rest.find_str("}}") {
+            Some(i) => i,
+            None => break,
+        };
+        match lookup(rest.slice(2, end).trim()) {
+            Some(value) => out.push_str(value.as_slice()),
+            None => out.push_str(rest.slice_to(end + 2)),
+        }
+        rest = rest.slice_from(end + 2);
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The color schemes a page in `theme` may be shown in, which browsers match
+/// their own controls and scrollbars to.
+fn color_scheme(theme: &str) -> &'static str {
+    match theme {
+        "auto" => "light dark",
+        "dark" => "dark",
+        _ => "light",
+    }
+}
+
+/// The control for switching between themes, if there's more than one.
+struct ThemePicker<'a>(&'a [StrBuf], &'a str);
+
+impl<'a> fmt::Show for ThemePicker<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ThemePicker(themes, current) = *self;
+        if themes.len() < 2 { return Ok(()) }
+        try!(write!(f.buf, "<select class='theme-picker js-only'>"));
+        for theme in themes.iter() {
+            try!(write!(f.buf, "<option value='{name}'{selected}>{name}</option>",
+                        name = Escape(theme.as_slice()),
+                        selected = if theme.as_slice() == current { " selected" } else { "" }));
+        }
+        write!(f.buf, "</select>")
+    }
+}
+
+/// The link to the canonical location of a page, if the output has one.
+struct Canonical<'a>(&'a Layout, &'a Page<'a>);
+
+impl<'a> fmt::Show for Canonical<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Canonical(layout, page) = *self;
+        match layout.canonical_url {
+            Some(ref url) => write!(f.buf, "<link rel=\"canonical\" href=\"{}{}\">\n",
+                                    Escape(url.as_slice()), Escape(page.path)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The version of the crate at the top of the sidebar, if it's known.
+struct Version<'a>(&'a Option<StrBuf>);
+
+impl<'a> fmt::Show for Version<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Version(version) = *self;
+        match *version {
+            Some(ref v) => write!(f.buf, "<p class='version'>Version {}</p>",
+                                  Escape(v.as_slice())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where a page `root_path` away from the root of the output finds the image
+/// `url`, which is either a URL or a path relative to the root.
+fn asset_url(root_path: &str, url: &str) -> StrBuf {
+    if url.len() == 0 || url.contains("://") || url.starts_with("/") ||
+       url.starts_with("data:") {
+        url.to_strbuf()
+    } else {
+        format_strbuf!("{}{}", root_path, url)
+    }
+}
+
 fn nonestr<'a>(s: &'a str) -> &'a str {
     if s == "" { "none" } else { s }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{substitute, asset_url};
+
+    #[test]
+    fn substitution() {
+        let out = substitute("<h1>{{ title }}</h1>{{unknown}}{{title}}{{", |name| {
+            if name == "title" { Some("t".to_strbuf()) } else { None }
+        });
+        assert_eq!(out.as_slice(), "<h1>t</h1>{{unknown}}t{{");
+    }
+
+    #[test]
+    fn asset_urls() {
+        assert_eq!(asset_url("../../", "logo.png").as_slice(), "../../logo.png");
+        assert_eq!(asset_url("../", "http://example.com/logo.png").as_slice(),
+                   "http://example.com/logo.png");
+        assert_eq!(asset_url("../", "/logo.png").as_slice(), "/logo.png");
+        assert_eq!(asset_url("../", "").as_slice(), "");
+    }
+}
@@ -27,20 +27,28 @@
 #![allow(non_camel_case_types)]
 
 use libc;
-use std::cell::RefCell;
+use std::ascii::StrAsciiExt;
+use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::fmt;
 use std::io;
 use std::slice;
 use std::str;
 use collections::HashMap;
 
-use html::toc::TocBuilder;
+use html::escape::Escape;
+use html::toc::{Toc, TocBuilder};
 use html::highlight;
 
 /// A unit struct which has the `fmt::Show` trait implemented. When
 /// formatted, this struct will emit the HTML corresponding to the rendered
 /// version of the contained markdown string.
 pub struct Markdown<'a>(pub &'a str);
+/// A unit struct like `Markdown`, that renders the markdown with its headers
+/// shifted down to fit where it's shown, so that its top level headers are at
+/// the level given rather than competing with the title of the page. Headers
+/// shifted past `<h6>` stay at that level.
+pub struct MarkdownAt<'a>(pub &'a str, pub u32);
 /// A unit struct like `Markdown`, that renders the markdown with a
 /// table of contents.
 pub struct MarkdownWithToc<'a>(pub &'a str);
@@ -72,7 +80,16 @@ struct hoedown_renderer {
                                     *mut libc::c_void)>,
     header: Option<extern "C" fn(*mut hoedown_buffer, *hoedown_buffer,
                                  libc::c_int, *mut libc::c_void)>,
-    other: [libc::size_t, ..28],
+    other_blocks: [libc::size_t, ..2],
+    listitem: Option<extern "C" fn(*mut hoedown_buffer, *hoedown_buffer,
+                                   libc::c_int, *mut libc::c_void)>,
+    more_blocks: [libc::size_t, ..5],
+    footnote_def: Option<extern "C" fn(*mut hoedown_buffer, *hoedown_buffer,
+                                       libc::c_uint, *mut libc::c_void)>,
+    other_spans: [libc::size_t, ..14],
+    footnote_ref: Option<extern "C" fn(*mut hoedown_buffer, libc::c_uint,
+                                       *mut libc::c_void) -> libc::c_int>,
+    other: [libc::size_t, ..4],
 }
 
 struct hoedown_html_renderer_state {
@@ -94,6 +111,13 @@ struct MyOpaque {
     dfltblk: extern "C" fn(*mut hoedown_buffer, *hoedown_buffer,
                            *hoedown_buffer, *mut libc::c_void),
     toc_builder: Option<TocBuilder>,
+    /// The number of the markdown being rendered among those on the page
+    /// with footnotes, once it's known to have any
+    footnote_block: Option<uint>,
+    /// Whether `math` code blocks are rendered as math
+    math: bool,
+    /// How many levels down headers are shifted
+    header_shift: libc::c_int,
 }
 
 struct hoedown_buffer {
@@ -126,6 +150,16 @@ extern {
 
 }
 
+/// The attributes of Rust code blocks which keep them from being tested the
+/// usual way, each with the badge their code is shown with and what it says
+/// when hovered over. Only the first one a block has is shown, since it
+/// overrides the others.
+static CODE_ATTRIBUTES: &'static [(&'static str, &'static str, &'static str)] = &[
+    ("ignore", "not tested", "This example is not tested"),
+    ("no_run", "not run", "This example is compiled but not run"),
+    ("should_fail", "fails", "This example fails when run"),
+];
+
 /// Returns Some(code) if `s` is a line that should be stripped from
 /// documentation but used in example code. `code` is the portion of
 /// `s` that should be used in tests. (None for lines that should be
@@ -140,8 +174,83 @@ fn stripped_filtered_line<'a>(s: &'a str) -> Option<&'a str> {
 }
 
 local_data_key!(used_header_map: RefCell<HashMap<StrBuf, uint>>)
+local_data_key!(used_footnote_blocks: Cell<uint>)
+local_data_key!(math_enabled: bool)
+local_data_key!(code_block_hooks: RefCell<HashMap<StrBuf, CodeBlockHook>>)
+
+/// Renders the code of a block tagged with the language the hook is
+/// registered for into the HTML to show in its place, such as an SVG drawn
+/// from a diagram. Returning `None` has the code shown as it's written.
+pub type CodeBlockHook = fn(code: &str) -> Option<StrBuf>;
+
+/// The languages of diagrams, which are shown as they're written rather than
+/// taken for Rust unless a hook renders them.
+static DIAGRAM_LANGS: &'static [&'static str] = &["graphviz", "mermaid"];
+
+/// Has code blocks tagged with `lang`, such as `graphviz` or `mermaid`,
+/// rendered by `hook` instead of being shown as code. A hook takes precedence
+/// over rustdoc's own handling of the language.
+///
+/// Like the registry of renderers, hooks are task-local, so they must be
+/// registered on the task which goes on to render.
+pub fn register_code_block_hook(lang: &str, hook: CodeBlockHook) {
+    if code_block_hooks.get().is_none() {
+        code_block_hooks.replace(Some(RefCell::new(HashMap::new())));
+    }
+    let map = code_block_hooks.get().unwrap();
+    map.borrow_mut().insert(lang.to_strbuf(), hook);
+}
 
-pub fn render(w: &mut io::Writer, s: &str, print_toc: bool) -> fmt::Result {
+/// Renders `code` with the hook registered for `lang`, if there's one and it
+/// doesn't decline to.
+fn hook_code_block(lang: &str, code: &str) -> Option<StrBuf> {
+    let map = match code_block_hooks.get() {
+        Some(map) => map,
+        None => return None,
+    };
+    let hook = match map.borrow().find_equiv(&lang) {
+        Some(hook) => *hook,
+        None => return None,
+    };
+    hook(code)
+}
+
+/// Sets whether math written in the markdown rendered on this task, as
+/// `$...$` or in `math` code blocks, is marked up for a script in the page to
+/// typeset. It's off until turned on.
+pub fn enable_math(enabled: bool) {
+    math_enabled.replace(Some(enabled));
+}
+
+/// Renders the markdown `s`, with its top level headers at `header_level`.
+pub fn render(w: &mut io::Writer, s: &str, print_toc: bool,
+              header_level: u32) -> fmt::Result {
+    let mut body = io::MemWriter::new();
+    match try!(render_html(&mut body, s, print_toc, header_level)) {
+        Some(toc) => try!(write!(w, "<nav id=\"TOC\">{}</nav>", toc)),
+        None => {}
+    }
+    w.write(body.get_ref())
+}
+
+/// Builds the table of contents of some markdown without rendering it. The
+/// headers in it are given the ids they get when the markdown is rendered
+/// next.
+pub fn toc(s: &str) -> Toc {
+    let map = used_header_map.get().unwrap();
+    let saved = map.borrow().clone();
+    let blocks = used_footnote_blocks.get().unwrap().get();
+    let toc = render_html(&mut io::util::NullWriter, s, true, 1);
+    *map.borrow_mut() = saved;
+    used_footnote_blocks.get().unwrap().set(blocks);
+    // Writing to a `NullWriter` can't fail
+    toc.unwrap().unwrap()
+}
+
+/// Renders markdown to HTML, returning its table of contents if `build_toc`
+/// is set.
+fn render_html(w: &mut io::Writer, s: &str, build_toc: bool,
+               header_level: u32) -> io::IoResult<Option<Toc>> {
     extern fn block(ob: *mut hoedown_buffer, text: *hoedown_buffer,
                     lang: *hoedown_buffer, opaque: *mut libc::c_void) {
         unsafe {
@@ -150,6 +259,7 @@ pub fn render(w: &mut io::Writer, s: &str, print_toc: bool) -> fmt::Result {
             slice::raw::buf_as_slice((*text).data, (*text).size as uint, |text| {
                 let text = str::from_utf8(text).unwrap();
                 debug!("docblock: ==============\n{}\n=======", text);
+                let source = text;
                 let mut lines = text.lines().filter(|l| {
                     stripped_filtered_line(*l).is_none()
                 });
@@ -161,28 +271,50 @@ pub fn render(w: &mut io::Writer, s: &str, print_toc: bool) -> fmt::Result {
                     asize: text.len() as libc::size_t,
                     unit: 0,
                 };
+                let mut attribute = None;
                 let rendered = if lang.is_null() {
                     false
                 } else {
                     slice::raw::buf_as_slice((*lang).data,
                                            (*lang).size as uint, |rlang| {
                         let rlang = str::from_utf8(rlang).unwrap();
-                        if rlang.contains("notrust") {
+                        match hook_code_block(rlang, source) {
+                            Some(html) => {
+                                html.as_slice().with_c_str(|p| hoedown_buffer_puts(ob, p));
+                                return true
+                            }
+                            None => {}
+                        }
+                        if rlang.contains("notrust") ||
+                           DIAGRAM_LANGS.iter().any(|l| *l == rlang) {
                             (my_opaque.dfltblk)(ob, &buf, lang,
                                                 opaque as *mut libc::c_void);
                             true
+                        } else if my_opaque.math && rlang == "math" {
+                            let math = format!("<div class=\"math\">\\\\[{}\\\\]</div>",
+                                               Escape(source.trim()));
+                            math.with_c_str(|p| hoedown_buffer_puts(ob, p));
+                            true
                         } else {
+                            attribute = CODE_ATTRIBUTES.iter().find(|&&(attr, _, _)| {
+                                rlang.contains(attr)
+                            });
                             false
                         }
                     })
                 };
 
                 if !rendered {
-                    let output = highlight::highlight(text, None).as_slice()
-                                                                 .to_c_str();
-                    output.with_ref(|r| {
-                        hoedown_buffer_puts(ob, r)
-                    })
+                    let output = match attribute {
+                        Some(&(attr, badge, title)) => {
+                            format!("<span class='code-badge {attr}' title='{title}'>\
+                                     {badge}</span>{code}",
+                                    attr = attr, badge = badge, title = title,
+                                    code = highlight::highlight(text, Some(attr)))
+                        }
+                        None => highlight::highlight(text, None).into_owned(),
+                    };
+                    output.with_c_str(|r| hoedown_buffer_puts(ob, r))
                 }
             })
         }
@@ -202,28 +334,13 @@ pub fn render(w: &mut io::Writer, s: &str, print_toc: bool) -> fmt::Result {
             }
         };
 
-        // Transform the contents of the header into a hyphenated string
-        let id = (s.words().map(|s| {
-            match s.to_ascii_opt() {
-                Some(s) => s.to_lower().into_str().to_strbuf(),
-                None => s.to_strbuf()
-            }
-        }).collect::<Vec<StrBuf>>().connect("-")).to_strbuf();
-
-        // This is a terrible hack working around how hoedown gives us rendered
-        // html for text rather than the raw text.
-        let id = id.replace("<code>", "").replace("</code>", "").to_strbuf();
-
         let opaque = opaque as *mut hoedown_html_renderer_state;
         let opaque = unsafe { &mut *((*opaque).opaque as *mut MyOpaque) };
+        let level = cmp::min(level + opaque.header_shift, 6);
 
         // Make sure our hyphenated ID is unique for this page
         let map = used_header_map.get().unwrap();
-        let id = match map.borrow_mut().find_mut(&id) {
-            None => id,
-            Some(a) => { *a += 1; format_strbuf!("{}-{}", id, *a - 1) }
-        };
-        map.borrow_mut().insert(id.clone(), 1);
+        let id = unique_id(&mut *map.borrow_mut(), slugify(s.as_slice()));
 
         let sec = match opaque.toc_builder {
             Some(ref mut builder) => {
@@ -241,36 +358,120 @@ pub fn render(w: &mut io::Writer, s: &str, print_toc: bool) -> fmt::Result {
         text.with_c_str(|p| unsafe { hoedown_buffer_puts(ob, p) });
     }
 
+    extern fn listitem(ob: *mut hoedown_buffer, text: *hoedown_buffer,
+                       _flags: libc::c_int, _opaque: *mut libc::c_void) {
+        let s = if text.is_null() {
+            "".to_owned()
+        } else {
+            unsafe {
+                str::raw::from_buf_len((*text).data, (*text).size as uint)
+            }
+        };
+        let s = s.as_slice().trim_right_chars('\n');
+        let text = match task(s) {
+            Some((done, para, rest)) => {
+                format!("<li class=\"task\">{}<input type=\"checkbox\" disabled{}>{}</li>\n",
+                        para, if done { " checked" } else { "" }, rest)
+            }
+            None => format!("<li>{}</li>\n", s),
+        };
+        text.with_c_str(|p| unsafe { hoedown_buffer_puts(ob, p) });
+    }
+
+    // Every doc block numbers its footnotes from 1, so their ids also carry
+    // the number of the block among those with footnotes on the page
+    fn footnote_id(opaque: *mut libc::c_void, num: libc::c_uint) -> StrBuf {
+        let opaque = opaque as *mut hoedown_html_renderer_state;
+        let opaque = unsafe { &mut *((*opaque).opaque as *mut MyOpaque) };
+        let block = match opaque.footnote_block {
+            Some(block) => block,
+            None => {
+                let blocks = used_footnote_blocks.get().unwrap();
+                blocks.set(blocks.get() + 1);
+                opaque.footnote_block = Some(blocks.get());
+                blocks.get()
+            }
+        };
+        format_strbuf!("{}-{}", block, num)
+    }
+
+    extern fn footnote_ref(ob: *mut hoedown_buffer, num: libc::c_uint,
+                           opaque: *mut libc::c_void) -> libc::c_int {
+        let id = footnote_id(opaque, num);
+        let text = format!("<sup id=\"fnref{id}\"><a href=\"\\#fn{id}\" \
+                            rel=\"footnote\">{num}</a></sup>", id = id, num = num);
+        text.with_c_str(|p| unsafe { hoedown_buffer_puts(ob, p) });
+        1
+    }
+
+    extern fn footnote_def(ob: *mut hoedown_buffer, text: *hoedown_buffer,
+                           num: libc::c_uint, opaque: *mut libc::c_void) {
+        let id = footnote_id(opaque, num);
+        let s = if text.is_null() {
+            "".to_owned()
+        } else {
+            unsafe {
+                str::raw::from_buf_len((*text).data, (*text).size as uint)
+            }
+        };
+
+        // The link back to the reference goes at the end of the footnote's
+        // last paragraph, as hoedown does
+        let backlink = format!("&nbsp;<a href=\"\\#fnref{}\" rev=\"footnote\">\
+                                &\\#8617;</a>", id);
+        let mut end = None;
+        let mut rest = s.as_slice();
+        loop {
+            match rest.find_str("</p>") {
+                Some(i) => {
+                    end = Some(s.len() - rest.len() + i);
+                    rest = rest.slice_from(i + 4);
+                }
+                None => break,
+            }
+        }
+        let body = match end {
+            Some(i) => format!("{}{}{}", s.slice_to(i), backlink, s.slice_from(i)),
+            None => format!("{}{}", s, backlink),
+        };
+
+        let text = format!("\n<li id=\"fn{}\">\n{}</li>\n", id, body);
+        text.with_c_str(|p| unsafe { hoedown_buffer_puts(ob, p) });
+    }
+
+    let math = math_enabled.get().map_or(false, |m| *m);
+    let s = if math { math_spans(s) } else { s.to_strbuf() };
+
     unsafe {
         let ob = hoedown_buffer_new(DEF_OUNIT);
         let renderer = hoedown_html_renderer_new(0, 0);
         let mut opaque = MyOpaque {
             dfltblk: (*renderer).blockcode.unwrap(),
-            toc_builder: if print_toc {Some(TocBuilder::new())} else {None}
+            toc_builder: if build_toc {Some(TocBuilder::new())} else {None},
+            footnote_block: None,
+            math: math,
+            header_shift: cmp::max(header_level, 1) as libc::c_int - 1,
         };
         (*(*renderer).opaque).opaque = &mut opaque as *mut _ as *mut libc::c_void;
         (*renderer).blockcode = Some(block);
         (*renderer).header = Some(header);
+        (*renderer).listitem = Some(listitem);
+        (*renderer).footnote_ref = Some(footnote_ref);
+        (*renderer).footnote_def = Some(footnote_def);
 
         let document = hoedown_document_new(renderer, HOEDOWN_EXTENSIONS, 16);
-        hoedown_document_render(document, ob, s.as_ptr(),
+        hoedown_document_render(document, ob, s.as_slice().as_ptr(),
                                 s.len() as libc::size_t);
         hoedown_document_free(document);
 
         hoedown_html_renderer_free(renderer);
 
-        let mut ret = match opaque.toc_builder {
-            Some(b) => write!(w, "<nav id=\"TOC\">{}</nav>", b.into_toc()),
-            None => Ok(())
-        };
-
-        if ret.is_ok() {
-            ret = slice::raw::buf_as_slice((*ob).data, (*ob).size as uint, |buf| {
-                w.write(buf)
-            });
-        }
+        let ret = slice::raw::buf_as_slice((*ob).data, (*ob).size as uint, |buf| {
+            w.write(buf)
+        });
         hoedown_buffer_free(ob);
-        ret
+        try!(ret);
+        Ok(opaque.toc_builder.map(|b| b.into_toc()))
     }
 }
 
@@ -288,7 +489,8 @@ pub fn find_testable_code(doc: &str, tests: &mut ::test::Collector) {
                     (s.contains("should_fail"),
                      s.contains("no_run"),
                      s.contains("ignore"),
-                     s.contains("notrust"))
+                     s.contains("notrust") || s == "math" ||
+                     DIAGRAM_LANGS.iter().any(|l| *l == s))
                 })
             };
             if notrust { return }
@@ -343,11 +545,246 @@ pub fn find_testable_code(doc: &str, tests: &mut ::test::Collector) {
 /// by hyphens, and a task-local map is used to disambiguate among duplicate
 /// headers (numbers are appended).
 ///
-/// This method will reset the local table for these headers. This is typically
-/// used at the beginning of rendering an entire HTML page to reset from the
-/// previous state (if any).
+/// Splits the rendered contents of a list item which starts with `[ ]` or
+/// `[x]`, marking it as a task, into whether the task is done, the paragraph
+/// tag it's in if any, and the rest of the contents.
+fn task<'a>(s: &'a str) -> Option<(bool, &'a str, &'a str)> {
+    let para = if s.starts_with("<p>") { "<p>" } else { "" };
+    let rest = s.slice_from(para.len());
+    let done = if rest.starts_with("[ ] ") {
+        false
+    } else if rest.starts_with("[x] ") || rest.starts_with("[X] ") {
+        true
+    } else {
+        return None
+    };
+    Some((done, para, rest.slice_from(3)))
+}
+
+/// Transforms the contents of a header into a hyphenated string for its id.
+/// Since hoedown gives us rendered html for them rather than the raw text,
+/// tags and entities are dropped, along with any punctuation.
+fn slugify(html: &str) -> StrBuf {
+    let mut text = StrBuf::with_capacity(html.len());
+    let mut rest = html;
+    loop {
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        let end = match c {
+            '<' => rest.find('>'),
+            '&' => rest.find(';'),
+            _ => None,
+        };
+        match end {
+            Some(i) => { rest = rest.slice_from(i + 1); continue }
+            None => {}
+        }
+        if c.is_alphanumeric() || c.is_whitespace() || c == '-' || c == '_' {
+            text.push_char(c.to_lowercase());
+        }
+        rest = rest.slice_from(c.len_utf8_bytes());
+    }
+    let id = text.as_slice().words().collect::<Vec<&str>>().connect("-");
+    if id.len() == 0 { "section".to_strbuf() } else { id.to_strbuf() }
+}
+
+/// Makes `id` unique among the ids in `used`, which maps each id to the
+/// suffix to try next for it, by giving it the lowest numeric suffix not
+/// taken yet.
+fn unique_id(used: &mut HashMap<StrBuf, uint>, id: StrBuf) -> StrBuf {
+    let id = match used.find_copy(&id) {
+        None => id,
+        Some(mut n) => {
+            let mut candidate = format_strbuf!("{}-{}", id, n);
+            while used.contains_key(&candidate) {
+                n += 1;
+                candidate = format_strbuf!("{}-{}", id, n);
+            }
+            used.insert(id, n + 1);
+            candidate
+        }
+    };
+    used.insert(id.clone(), 1);
+    id
+}
+
+/// The ids of the parts of a page which rustdoc renders itself, which
+/// headers in the docs must not take.
+static PAGE_IDS: &'static [&'static str] = &[
+    "main", "search", "help", "themeStyle", "TOC", "fields", "variants", "methods",
+    "implementations", "derived_implementations", "required-methods",
+    "provided-methods", "implementors", "blanket-implementations",
+];
+
+/// This method will reset the local table for these headers, along with the
+/// count of the doc blocks with footnotes, which number them. This is
+/// typically used at the beginning of rendering an entire HTML page to reset
+/// from the previous state (if any).
 pub fn reset_headers() {
-    used_header_map.replace(Some(RefCell::new(HashMap::new())));
+    let mut map = HashMap::new();
+    for id in PAGE_IDS.iter() {
+        map.insert(id.to_strbuf(), 1);
+    }
+    used_header_map.replace(Some(RefCell::new(map)));
+    used_footnote_blocks.replace(Some(Cell::new(0)));
+}
+
+/// The characters hoedown drops the backslash from when escaped with one.
+static ESCAPABLE: &'static str = "\\`*_{}[]()#+-.!:|&<>^~";
+
+/// Marks up each `$...$` in the markdown `md` outside of code as a span of
+/// inline math, with the TeX in it escaped so that hoedown leaves it be. Like
+/// in pandoc, the opening `$` must be followed by a non-space and the closing
+/// one preceded by one and not followed by a digit, so that amounts such as
+/// $5 aren't taken for math. Math can't span lines.
+fn math_spans(md: &str) -> StrBuf {
+    let mut out = StrBuf::with_capacity(md.len());
+    // The fence of the code block we're in
+    let mut fence = None;
+    for line in md.lines() {
+        let trimmed = line.trim_left();
+        let code = match fence {
+            Some(f) => {
+                if trimmed.starts_with(f) { fence = None }
+                true
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                fence = Some(trimmed.slice_to(3));
+                true
+            }
+            None => line.starts_with("    ") || line.starts_with("\t"),
+        };
+        if code {
+            out.push_str(line);
+            out.push_char('\n');
+            continue
+        }
+        let mut rest = line;
+        loop {
+            let i = match rest.find(|c: char| c == '`' || c == '$' || c == '\\') {
+                Some(i) => i,
+                None => break,
+            };
+            out.push_str(rest.slice_to(i));
+            rest = rest.slice_from(i);
+            let len = match rest.char_at(0) {
+                '\\' => 1 + rest.slice_from(1).chars().next().map_or(0, |c| c.len_utf8_bytes()),
+                '`' => {
+                    let run = rest.chars().take_while(|&c| c == '`').count();
+                    match rest.slice_from(run).find_str(rest.slice_to(run)) {
+                        Some(end) => run + end + run,
+                        None => rest.len(),
+                    }
+                }
+                _ => match math_end(rest) {
+                    Some(end) => {
+                        let tex = format!("\\\\({}\\\\)", rest.slice(1, end));
+                        out.push_str("<span class=\"math\">");
+                        for c in tex.chars() {
+                            if ESCAPABLE.contains_char(c) { out.push_char('\\') }
+                            out.push_char(c);
+                        }
+                        out.push_str("</span>");
+                        rest = rest.slice_from(end + 1);
+                        continue
+                    }
+                    None => 1,
+                },
+            };
+            out.push_str(rest.slice_to(len));
+            rest = rest.slice_from(len);
+        }
+        out.push_str(rest);
+        out.push_char('\n');
+    }
+    out
+}
+
+/// The position of the `$` closing the inline math which `s` starts with, if
+/// it does start with some.
+fn math_end(s: &str) -> Option<uint> {
+    match s.slice_from(1).chars().next() {
+        Some(c) if !c.is_whitespace() && c != '$' => {}
+        _ => return None,
+    }
+    let mut prev = '$';
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            '`' => return None,
+            '$' if !prev.is_whitespace() && prev != '\\' => {
+                let digit = s.slice_from(i + 1).chars().next().map_or(false, |c| c.is_digit());
+                if !digit { return Some(i) }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Words which end in a `.` without ending a sentence.
+static ABBREVIATIONS: &'static [&'static str] = &["e.g", "i.e", "etc", "vs", "cf"];
+
+/// The first sentence of the first paragraph of the markdown `md`, to sum it
+/// up in listings and the search index. A sentence ends with a `.`, `!` or `?`
+/// followed by whitespace, unless it's within a code span or a link, or ends
+/// an abbreviation such as "e.g.".
+pub fn summary<'a>(md: &'a str) -> &'a str {
+    let para = match md.find_str("\n\n") {
+        Some(pos) => md.slice_to(pos),
+        None => md,
+    };
+    // The length of the run of backticks which opened the current code span
+    let mut code = None;
+    // How deep within the text and the target of a link we are
+    let mut text = 0;
+    let mut target = 0;
+    let mut i = 0;
+    while i < para.len() {
+        let c = para.char_at(i);
+        let next = i + c.len_utf8_bytes();
+        match c {
+            '`' => {
+                let run = para.slice_from(i).chars().take_while(|&c| c == '`').count();
+                code = match code {
+                    Some(n) if n == run => None,
+                    None => Some(run),
+                    open => open,
+                };
+                i += run;
+                continue
+            }
+            _ if code.is_some() => {}
+            '[' => text += 1,
+            ']' if text > 0 => {
+                text -= 1;
+                if text == 0 && para.slice_from(next).starts_with("(") {
+                    target += 1;
+                    i = next + 1;
+                    continue
+                }
+            }
+            '(' if target > 0 => target += 1,
+            ')' if target > 0 => target -= 1,
+            '.' | '!' | '?' if text == 0 && target == 0 => {
+                let ends = next == para.len() || para.char_at(next).is_whitespace();
+                let word = para.slice_to(i).split(|c: char| {
+                    c.is_whitespace() || c == '('
+                }).last().unwrap_or("");
+                let abbreviation = c == '.' && ABBREVIATIONS.iter().any(|a| {
+                    word.eq_ignore_ascii_case(*a)
+                });
+                if ends && !abbreviation {
+                    return para.slice_to(next)
+                }
+            }
+            _ => {}
+        }
+        i = next;
+    }
+    para
 }
 
 impl<'a> fmt::Show for Markdown<'a> {
@@ -355,13 +792,134 @@ impl<'a> fmt::Show for Markdown<'a> {
         let Markdown(md) = *self;
         // This is actually common enough to special-case
         if md.len() == 0 { return Ok(()) }
-        render(fmt.buf, md.as_slice(), false)
+        render(fmt.buf, md.as_slice(), false, 1)
+    }
+}
+
+impl<'a> fmt::Show for MarkdownAt<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let MarkdownAt(md, level) = *self;
+        if md.len() == 0 { return Ok(()) }
+        render(fmt.buf, md.as_slice(), false, level)
     }
 }
 
 impl<'a> fmt::Show for MarkdownWithToc<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let MarkdownWithToc(md) = *self;
-        render(fmt.buf, md.as_slice(), true)
+        render(fmt.buf, md.as_slice(), true, 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Markdown, MarkdownAt, enable_math, register_code_block_hook};
+    use super::{reset_headers, summary};
+
+    #[test]
+    fn summaries() {
+        assert_eq!(summary("One. Two."), "One.");
+        assert_eq!(summary("One\ncontinued! Two."), "One\ncontinued!");
+        assert_eq!(summary("No end\n\nSecond paragraph."), "No end");
+        assert_eq!(summary("Calls `a.b(). c` first. Then"), "Calls `a.b(). c` first.");
+        assert_eq!(summary("Like ``a` b. c`` here. Then"), "Like ``a` b. c`` here.");
+        assert_eq!(summary("See [the docs. Really](http://a.b/c.d. e) now. Then"),
+                   "See [the docs. Really](http://a.b/c.d. e) now.");
+        assert_eq!(summary("Has e.g. an abbreviation. Then"), "Has e.g. an abbreviation.");
+        assert_eq!(summary("Ünïcödé ends here. Mörë."), "Ünïcödé ends here.");
+        assert_eq!(summary("Version 1.2 is out"), "Version 1.2 is out");
+    }
+
+    #[test]
+    fn tables() {
+        let md = "| Operator | Precedence |\n|----------|-----------:|\n| `*` | 1 |\n";
+        let html = format!("{}", Markdown(md));
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<th>Operator</th>"));
+        assert!(html.contains("<td><code>*</code></td>"));
+        assert!(html.contains("<td style=\"text-align: right\">1</td>"));
+    }
+
+    #[test]
+    fn tasks() {
+        let html = format!("{}", Markdown("- [ ] todo\n- [x] done\n- [y] other\n"));
+        assert!(html.contains("<li class=\"task\"><input type=\"checkbox\" disabled> todo</li>"));
+        assert!(html.contains("<li class=\"task\"><input type=\"checkbox\" disabled checked> \
+                               done</li>"));
+        assert!(html.contains("<li>[y] other</li>"));
+    }
+
+    #[test]
+    fn footnotes() {
+        reset_headers();
+        let md = "A[^1] and B[^2].\n\n[^1]: First.\n[^2]: Second.\n";
+        let first = format!("{}", Markdown(md));
+        let second = format!("{}", Markdown(md));
+        assert!(first.contains("<sup id=\"fnref1-1\"><a href=\"#fn1-1\" rel=\"footnote\">1</a>"));
+        assert!(first.contains("<li id=\"fn1-2\">"));
+        assert!(first.contains("Second.&nbsp;<a href=\"#fnref1-2\" rev=\"footnote\">\
+                                &#8617;</a></p>"));
+        assert!(second.contains("<li id=\"fn2-1\">"));
+    }
+
+    #[test]
+    fn header_ids() {
+        reset_headers();
+        let md = "# Foo `bar` & *baz*?\n\n# Examples\n\n# Examples\n\n# Examples-1\n\n\
+                  # Methods\n\n# ...\n";
+        let html = format!("{}", Markdown(md));
+        for id in ["foo-bar-baz", "examples", "examples-1", "examples-1-1", "methods-1",
+                   "section"].iter() {
+            assert!(html.contains(format!("id=\"{}\"", *id).as_slice()), "no id {}", *id);
+        }
+        assert!(html.contains("href=\"#examples-1\">Examples</a>"));
+    }
+
+    #[test]
+    fn code_attributes() {
+        let html = format!("{}", Markdown("```no_run,should_fail\nloop {}\n```\n"));
+        assert!(html.contains("<span class='code-badge no_run' \
+                               title='This example is compiled but not run'>not run</span>"));
+        assert!(html.contains("<pre class='rust no_run'>"));
+        let html = format!("{}", Markdown("```\nloop {}\n```\n"));
+        assert!(!html.contains("code-badge"));
+    }
+
+    fn boxed(code: &str) -> Option<StrBuf> {
+        if code.contains("plain") { return None }
+        Some(format_strbuf!("<svg>{}</svg>", code.trim()))
+    }
+
+    #[test]
+    fn code_block_hooks() {
+        register_code_block_hook("graphviz", boxed);
+        let html = format!("{}", Markdown("```graphviz\ndigraph { a -> b }\n```\n"));
+        assert!(html.contains("<svg>digraph { a -> b }</svg>"));
+        let html = format!("{}", Markdown("```graphviz\nplain\n```\n"));
+        assert!(!html.contains("<svg>"));
+        assert!(!html.contains("class='rust"));
+        let html = format!("{}", Markdown("```mermaid\ngraph TD\n```\n"));
+        assert!(!html.contains("class='rust"));
+    }
+
+    #[test]
+    fn header_levels() {
+        reset_headers();
+        let html = format!("{}", MarkdownAt("# A\n\n## B\n\n##### C\n", 2));
+        assert!(html.contains("<h2 id=\"a\""));
+        assert!(html.contains("<h3 id=\"b\""));
+        assert!(html.contains("<h6 id=\"c\""));
+        assert!(format!("{}", Markdown("# D\n")).contains("<h1 id=\"d\""));
+    }
+
+    #[test]
+    fn math() {
+        enable_math(true);
+        let md = "Area $\\pi r^2_{x}$, not $5 or `$x$` or \\$y$.\n\n```math\na < b\n```\n";
+        let html = format!("{}", Markdown(md));
+        enable_math(false);
+        assert!(html.contains("<span class=\"math\">\\(\\pi r^2_{x}\\)</span>"));
+        assert!(html.contains("not $5 or <code>$x$</code> or $y$."));
+        assert!(html.contains("<div class=\"math\">\\[a &lt; b\\]</div>"));
     }
 }
@@ -0,0 +1,94 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Shrinking of the HTML output
+//!
+//! Pages are minified by dropping comments and collapsing each run of
+//! whitespace into a single space, leaving alone the contents of the elements
+//! in which whitespace is significant.
+
+use std::strbuf::StrBuf;
+
+/// The elements whose contents are copied as they are. Code is among them
+/// since the stylesheets preserve its whitespace.
+static VERBATIM: &'static [&'static str] = &["pre", "code", "textarea", "script", "style"];
+
+/// Minifies the HTML `s`. Conditional comments, such as `<!--[if IE]>`, are
+/// kept since browsers act on them.
+pub fn html(s: &str) -> StrBuf {
+    let mut out = StrBuf::with_capacity(s.len());
+    let mut rest = s;
+    let mut space = false;
+    loop {
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        if c.is_whitespace() {
+            space = true;
+            rest = rest.slice_from(c.len_utf8_bytes());
+            continue
+        }
+        if rest.starts_with("<!--") && !rest.starts_with("<!--[") {
+            rest = match rest.find_str("-->") {
+                Some(i) => rest.slice_from(i + 3),
+                None => "",
+            };
+            continue
+        }
+        if space && out.len() > 0 {
+            out.push_char(' ');
+        }
+        space = false;
+        let len = match verbatim(rest) {
+            Some(tag) => {
+                let end = format!("</{}>", tag);
+                rest.find_str(end.as_slice()).map_or(rest.len(), |i| i + end.len())
+            }
+            None => c.len_utf8_bytes(),
+        };
+        out.push_str(rest.slice_to(len));
+        rest = rest.slice_from(len);
+    }
+    out
+}
+
+/// The name of the element whose start tag `s` starts with, if its contents
+/// are to be left as they are.
+fn verbatim(s: &str) -> Option<&'static str> {
+    if !s.starts_with("<") { return None }
+    for &tag in VERBATIM.iter() {
+        let rest = s.slice_from(1);
+        if !rest.starts_with(tag) { continue }
+        match rest.slice_from(tag.len()).chars().next() {
+            Some('>') | Some('/') => return Some(tag),
+            Some(c) if c.is_whitespace() => return Some(tag),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::html;
+
+    #[test]
+    fn minify() {
+        assert_eq!(html("  <p>a \n\n  <b>b</b>\t</p>\n").as_slice(), "<p>a <b>b</b> </p>");
+        assert_eq!(html("<p>a<!-- gone -->b</p>").as_slice(), "<p>ab</p>");
+        assert_eq!(html("<!--[if lte IE 8]>x<![endif]-->").as_slice(),
+                   "<!--[if lte IE 8]>x<![endif]-->");
+        assert_eq!(html("<pre class='rust'>a\n    b</pre>\n<p>c  d</p>").as_slice(),
+                   "<pre class='rust'>a\n    b</pre> <p>c d</p>");
+        assert_eq!(html("<code>a  b</code> <codex>c  d</codex>").as_slice(),
+                   "<code>a  b</code> <codex>c d</codex>");
+    }
+}
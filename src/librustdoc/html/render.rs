@@ -33,31 +33,44 @@
 //! These tasks are not parallelized (they haven't been a bottleneck yet), and
 //! both occur before the crate is rendered.
 
-use collections::{HashMap, HashSet};
+use collections::{HashMap, HashSet, TreeMap};
+use std::ascii::StrAsciiExt;
 use std::fmt;
-use std::io::{fs, File, BufferedWriter, MemWriter, BufferedReader};
+use std::io::{fs, File, BufferedWriter, MemWriter};
 use std::io;
 use std::str;
 use std::strbuf::StrBuf;
 
 use sync::Arc;
+use serialize::base64::{ToBase64, STANDARD};
+use serialize::json;
 use serialize::json::ToJson;
 use syntax::ast;
 use syntax::ast_util;
 use syntax::attr;
-use syntax::parse::token::InternedString;
 use rustc::util::nodemap::NodeSet;
 
 use clean;
 use doctree;
 use fold::DocFolder;
-use html::format::{VisSpace, Method, FnStyleSpace};
+use html::escape::Escape;
+use html::format::{VisSpace, Method, FnStyleSpace, StabilityBadge, PrivateBadge,
+                   HiddenBadge, WrappedDecl};
+use html::format::{primitive_name, text_len};
 use html::highlight;
+use html::incremental::{Hashes, HASHES_FILE};
 use html::item_type::{ItemType, shortty};
 use html::item_type;
 use html::layout;
-use html::markdown::Markdown;
+use html::markdown::{Markdown, MarkdownAt};
 use html::markdown;
+use output;
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult, ItemOrder, Alphabetical, SourceOrder};
+use renderer::{PageLayout, Nested, Flat};
+use stability_summary;
+use stability_summary::{Counts, ModuleSummary};
 
 /// Major driving force in all rustdoc rendering. This contains information
 /// about where in the tree-like hierarchy rendering is occurring and controls
@@ -74,8 +87,9 @@ pub struct Context {
     /// String representation of how to get back to the root path of the 'doc/'
     /// folder in terms of a relative URL.
     pub root_path: StrBuf,
-    /// The current destination folder of where HTML artifacts should be placed.
-    /// This changes as the context descends into the module hierarchy.
+    /// The current destination folder of where HTML artifacts should be placed,
+    /// relative to the root of the output sink. This changes as the context
+    /// descends into the module hierarchy.
     pub dst: Path,
     /// This describes the layout of each page, and is not modified after
     /// creation of the context (contains info like the favicon)
@@ -86,12 +100,27 @@ pub struct Context {
     /// header. This map will change depending on the surrounding context of the
     /// page.
     pub sidebar: HashMap<StrBuf, Vec<StrBuf> >,
+    /// The crates the crate being documented links to, sorted by name, which
+    /// are listed on the sidebar of its root page.
+    pub externs: Vec<(ast::CrateNum, StrBuf)>,
     /// This flag indicates whether [src] links should be generated or not. If
     /// the source files are present in the html rendering, then this will be
     /// `true`.
     pub include_sources: bool,
+    /// The contents of the source files of the crate by their name, if the
+    /// source of short items is to be shown on their pages.
+    pub inline_sources: Option<Arc<HashMap<StrBuf, StrBuf>>>,
+    /// The width in characters past which signatures are wrapped onto a line
+    /// per argument
+    pub signature_width: uint,
+    /// The order the items of each kind are listed in within a module, on its
+    /// page and its sidebar
+    pub item_order: ItemOrder,
 }
 
+/// The most lines the source of an item can span to be shown on its page.
+static MAX_INLINE_SOURCE_LINES: uint = 30;
+
 /// Indicates where an external crate can be found.
 pub enum ExternalLocation {
     /// Remote URL root of the external crate
@@ -109,6 +138,9 @@ pub enum Implementor {
     /// This is the generic representation of a trait implementor, used for
     /// primitive types and otherwise non-path types.
     OtherType(clean::Generics, /* trait */ clean::Type, /* for */ clean::Type),
+    /// An implementation for any type meeting some bounds, such as
+    /// `impl<T: Foo> Bar for T`, which is listed apart from the others.
+    BlanketType(clean::Generics, /* trait */ clean::Type, /* for */ clean::Type),
 }
 
 /// This cache is used to store information about the `clean::Crate` being
@@ -155,11 +187,22 @@ pub struct Cache {
     /// Cache of where external crate documentation can be found.
     pub extern_locations: HashMap<ast::CrateNum, ExternalLocation>,
 
+    /// Entries of the search index for every item in the crate, in the order
+    /// they were encountered while crawling it.
+    pub search_index: Vec<IndexItem>,
+
+    /// Whether private items are documented, in which case they're kept
+    /// rather than stripped along with private modules.
+    pub document_private: bool,
+
+    /// The items which can't be seen from outside the crate, when private
+    /// items are documented. These are left out of the search index.
+    pub private_items: NodeSet,
+
     // Private fields only used when initially crawling a crate to build a cache
 
     stack: Vec<StrBuf> ,
     parent_stack: Vec<ast::NodeId> ,
-    search_index: Vec<IndexItem> ,
     privmod: bool,
     public_items: NodeSet,
 
@@ -174,16 +217,37 @@ pub struct Cache {
 /// Helper struct to render all source code to HTML pages
 struct SourceCollector<'a> {
     cx: &'a mut Context,
+    sink: &'a mut OutputSink,
 
     /// Processed source-file paths
     seen: HashSet<StrBuf>,
     /// Root destination to place all HTML output into
     dst: Path,
+    /// Where the identifiers in the source link to
+    links: SourceLinks,
+    /// The contents of the processed source files, if they're to be kept for
+    /// showing the source of items on their pages
+    contents: Option<HashMap<StrBuf, StrBuf>>,
+}
+
+/// Where the identifiers in the rendered source of the crate link to. Each
+/// item with a page links there from the span it's defined at. Paths are
+/// relative to the root of the output.
+struct SourceLinks {
+    /// The pages of the items defined in each file, by the line the item
+    /// starts on and its name
+    defs: HashMap<StrBuf, HashMap<(uint, StrBuf), StrBuf>>,
 }
 
 /// Wrapper struct to render the source code of a file. This will do things like
 /// adding line numbers to the left-hand side.
-struct Source<'a>(&'a str);
+struct Source<'a> {
+    contents: &'a str,
+    /// The name of the file, as recorded in the spans of its items
+    file: &'a str,
+    links: &'a SourceLinks,
+    root_path: &'a str,
+}
 
 // Helper structs for rendering items/sidebars and carrying along contextual
 // information
@@ -193,59 +257,132 @@ struct Sidebar<'a> { cx: &'a Context, item: &'a clean::Item, }
 
 /// Struct representing one entry in the JS search index. These are all emitted
 /// by hand to a large JS file at the end of cache-creation.
-struct IndexItem {
-    ty: ItemType,
-    name: StrBuf,
-    path: StrBuf,
-    desc: StrBuf,
-    parent: Option<ast::NodeId>,
+pub struct IndexItem {
+    pub ty: ItemType,
+    pub name: StrBuf,
+    /// Path of the module the item is in, joined with `::`
+    pub path: StrBuf,
+    /// Summary line of the item's documentation
+    pub desc: StrBuf,
+    /// The type or trait a method belongs to, if any
+    pub parent: Option<ast::NodeId>,
+    /// The signature of the item if it's a function or method
+    pub sig: Option<IndexSig>,
+    /// Whether the item is marked `#[deprecated]`
+    pub deprecated: bool,
+    /// The other names the item is found by, from `#[doc(alias = "...")]`
+    pub aliases: Vec<StrBuf>,
+}
+
+impl IndexItem {
+    /// The entry of `item`, which is in the module at `path` and belongs to
+    /// `parent`.
+    fn new(item: &clean::Item, path: StrBuf, parent: Option<ast::NodeId>,
+           typarams: &HashMap<ast::NodeId, StrBuf>) -> IndexItem {
+        // The first sentence of the docs, joined onto a single line
+        let desc = shorter(item.doc_value()).lines().map(|l| l.trim())
+                                            .collect::<Vec<&str>>().connect(" ");
+        IndexItem {
+            ty: shortty(item),
+            name: item.name.clone().unwrap(),
+            path: path,
+            desc: desc.to_strbuf(),
+            parent: parent,
+            sig: IndexSig::of(item, typarams),
+            deprecated: match item.stability() {
+                Some(stab) => stab.level == attr::Deprecated,
+                None => false,
+            },
+            aliases: item.doc_aliases(),
+        }
+    }
+}
+
+/// The types a function takes and returns, for searching by signature. Types
+/// are reduced to the lowercased name they're known by, dropping pointers,
+/// vectors, type arguments and module paths, so `&[Vec<u8>]` becomes `vec`.
+/// The search script reduces the types in queries the same way.
+pub struct IndexSig {
+    pub inputs: Vec<StrBuf>,
+    /// The returned type, or `None` for `()`
+    pub output: Option<StrBuf>,
+}
+
+impl IndexSig {
+    /// The signature of `item` if it's a function or method, with its type
+    /// parameters named according to `typarams`.
+    pub fn of(item: &clean::Item,
+              typarams: &HashMap<ast::NodeId, StrBuf>) -> Option<IndexSig> {
+        let decl = match item.inner {
+            clean::FunctionItem(ref f) | clean::ForeignFunctionItem(ref f) => &f.decl,
+            clean::MethodItem(ref m) => &m.decl,
+            clean::TyMethodItem(ref m) => &m.decl,
+            _ => return None,
+        };
+        Some(IndexSig {
+            inputs: decl.inputs.values.iter().map(|a| {
+                index_type_name(&a.type_, typarams)
+            }).collect(),
+            output: match decl.output {
+                clean::Unit => None,
+                ref t => Some(index_type_name(t, typarams)),
+            },
+        })
+    }
+}
+
+/// Reduces a type to the name it's searched for by.
+fn index_type_name(t: &clean::Type, typarams: &HashMap<ast::NodeId, StrBuf>) -> StrBuf {
+    let name = match *t {
+        clean::ResolvedPath { ref path, .. } => {
+            path.segments.last().map(|s| s.name.clone()).unwrap_or(StrBuf::new())
+        }
+        clean::TyParamBinder(id) | clean::Generic(id) => {
+            typarams.find(&id).map(|s| s.clone()).unwrap_or(StrBuf::new())
+        }
+        clean::Self(..) => "self".to_strbuf(),
+        clean::Primitive(prim) => primitive_name(prim).to_strbuf(),
+        clean::Closure(..) | clean::Proc(..) | clean::BareFunction(..) => {
+            "fn".to_strbuf()
+        }
+        clean::Tuple(..) => "tuple".to_strbuf(),
+        clean::String => "str".to_strbuf(),
+        clean::Bool => "bool".to_strbuf(),
+        clean::Unit => "()".to_strbuf(),
+        clean::Bottom => "!".to_strbuf(),
+        clean::Vector(ref t) | clean::FixedVector(ref t, _) |
+        clean::Unique(ref t) | clean::Managed(ref t) |
+        clean::RawPointer(_, ref t) |
+        clean::BorrowedRef { type_: ref t, .. } => return index_type_name(&**t, typarams),
+    };
+    name.as_slice().to_ascii_lower().to_strbuf()
 }
 
 // TLS keys used to carry information around during rendering.
 
 local_data_key!(pub cache_key: Arc<Cache>)
 local_data_key!(pub current_location_key: Vec<StrBuf> )
+local_data_key!(pub page_layout_key: PageLayout)
 
-/// Generates the documentation for `crate` into the directory `dst`
-pub fn run(mut krate: clean::Crate, dst: Path) -> io::IoResult<()> {
-    let mut cx = Context {
-        dst: dst,
-        current: Vec::new(),
-        root_path: StrBuf::new(),
-        sidebar: HashMap::new(),
-        layout: layout::Layout {
-            logo: "".to_strbuf(),
-            favicon: "".to_strbuf(),
-            krate: krate.name.clone(),
-        },
-        include_sources: true,
-    };
-    try!(mkdir(&cx.dst));
+/// The default rustdoc backend, rendering a crate to a tree of static HTML
+/// pages.
+pub struct HtmlRenderer;
 
-    match krate.module.as_ref().map(|m| m.doc_list().unwrap_or(&[])) {
-        Some(attrs) => {
-            for attr in attrs.iter() {
-                match *attr {
-                    clean::NameValue(ref x, ref s)
-                            if "html_favicon_url" == x.as_slice() => {
-                        cx.layout.favicon = s.to_strbuf();
-                    }
-                    clean::NameValue(ref x, ref s)
-                            if "html_logo_url" == x.as_slice() => {
-                        cx.layout.logo = s.to_strbuf();
-                    }
-                    clean::Word(ref x)
-                            if "html_no_source" == x.as_slice() => {
-                        cx.include_sources = false;
-                    }
-                    _ => {}
-                }
-            }
-        }
-        None => {}
+impl Renderer for HtmlRenderer {
+    fn name(&self) -> &'static str { "html" }
+
+    fn output_filename(&self) -> Option<&'static str> { None }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
     }
+}
 
-    // Crawl the crate to build various caches used for the output
+/// Crawls `krate` to build the cache shared among all renderers. Documentation
+/// for external crates is looked for relative to the `dst` directory.
+/// `document_private` is whether the private items of `krate` were kept.
+pub fn build_cache(krate: &clean::Crate, dst: &Path, document_private: bool) -> Cache {
     let analysis = ::analysiskey.get();
     let public_items = analysis.as_ref().map(|a| a.public_items.clone());
     let paths = analysis.as_ref().map(|a| {
@@ -272,18 +409,18 @@ pub fn run(mut krate: clean::Crate, dst: Path) -> io::IoResult<()> {
         parent_stack: Vec::new(),
         search_index: Vec::new(),
         extern_locations: HashMap::new(),
+        document_private: document_private,
+        private_items: NodeSet::new(),
         privmod: false,
         public_items: public_items.unwrap_or(NodeSet::new()),
         orphan_methods: Vec::new(),
     };
     cache.stack.push(krate.name.clone());
-    krate = cache.fold_crate(krate);
+    cache.fold_crate(krate.clone());
 
-    let mut nodeid_to_pathid = HashMap::new();
-    let mut pathid_to_nodeid = Vec::new();
     {
-        let Cache { search_index: ref mut index,
-                    orphan_methods: ref meths, paths: ref mut paths, ..} = cache;
+        let Cache { search_index: ref mut index, orphan_methods: ref meths,
+                    paths: ref mut paths, typarams: ref typarams, ..} = cache;
 
         // Attach all orphan methods to the type's definition if the type
         // has since been learned.
@@ -291,172 +428,820 @@ pub fn run(mut krate: clean::Crate, dst: Path) -> io::IoResult<()> {
             let did = ast_util::local_def(pid);
             match paths.find(&did) {
                 Some(&(ref fqp, _)) => {
-                    index.push(IndexItem {
-                        ty: shortty(item),
-                        name: item.name.clone().unwrap(),
-                        path: fqp.slice_to(fqp.len() - 1).connect("::")
-                                                         .to_strbuf(),
-                        desc: shorter(item.doc_value()).to_strbuf(),
-                        parent: Some(pid),
-                    });
+                    let path = fqp.slice_to(fqp.len() - 1).connect("::");
+                    index.push(IndexItem::new(item, path.to_strbuf(), Some(pid),
+                                              typarams));
                 },
                 None => {}
             }
         };
+    }
 
-        // Reduce `NodeId` in paths into smaller sequential numbers,
-        // and prune the paths that do not appear in the index.
-        for item in index.iter() {
-            match item.parent {
-                Some(nodeid) => {
-                    if !nodeid_to_pathid.contains_key(&nodeid) {
-                        let pathid = pathid_to_nodeid.len();
-                        nodeid_to_pathid.insert(nodeid, pathid);
-                        pathid_to_nodeid.push(nodeid);
-                    }
-                }
-                None => {}
-            }
+    for &(n, ref e) in krate.externs.iter() {
+        cache.extern_locations.insert(n, extern_location(e, dst));
+        let did = ast::DefId { krate: n, node: ast::CRATE_NODE_ID };
+        cache.paths.insert(did, (Vec::new(), item_type::Module));
+    }
+
+    cache
+}
+
+/// The shard of the search index the item named `name` goes in: the initial
+/// letter or digit of the name, lowercased, or `_` for anything else.
+pub fn search_shard(name: &str) -> char {
+    match name.chars().next() {
+        Some(c) if (c >= 'a' && c <= 'z') || (c >= '0' && c <= '9') => c,
+        Some(c) if c >= 'A' && c <= 'Z' => c.to_lowercase(),
+        _ => '_',
+    }
+}
+
+/// Splits the search index of a crate into shards by `search_shard`, so the
+/// search JS can fetch the part of the index a query starts with before the
+/// rest. Returns the key of each shard with its chunk, ordered by key.
+///
+/// The items are listed by module, either in the order they were found in or
+/// sorted by name within each module, depending on `order`. Each shard keeps
+/// that order.
+pub fn search_index_shards(krate: &str, cache: &Cache,
+                           order: ItemOrder) -> io::IoResult<Vec<(char, StrBuf)>> {
+    let mut index = cache.search_index.iter().collect::<Vec<&IndexItem>>();
+    if order == Alphabetical {
+        index.sort_by(|a, b| (&a.path, &a.name).cmp(&(&b.path, &b.name)));
+    }
+    let mut shards = HashMap::new();
+    for item in index.move_iter() {
+        shards.find_or_insert_with(search_shard(item.name.as_slice()), |_| Vec::new())
+              .push(item);
+    }
+    let mut shards = shards.move_iter().collect::<Vec<(char, Vec<&IndexItem>)>>();
+    shards.sort_by(|a, b| a.ref0().cmp(b.ref0()));
+
+    let mut chunks = Vec::new();
+    for &(shard, ref items) in shards.iter() {
+        chunks.push((shard, try!(search_index_js(krate, shard, cache, items.as_slice()))));
+    }
+    Ok(chunks)
+}
+
+/// Renders `search-index/<crate>.js`, which lists the shards of the search
+/// index of a crate for the search JS to fetch.
+pub fn search_shards_js(krate: &str, shards: &[(char, StrBuf)]) -> StrBuf {
+    let keys = shards.iter().map(|&(shard, _)| shard.to_str().to_strbuf())
+                     .collect::<Vec<StrBuf>>();
+    format_strbuf!("addSearchShards('{}', {});\n", krate, keys.to_json().to_str())
+}
+
+/// Renders `search-index/<crate>/<shard>.js`, a shard of the search index of
+/// a crate holding `index`, which hands it to the search JS once it's loaded.
+///
+/// The index is laid out by column rather than by item to keep it small for
+/// large crates, with the keys:
+///
+/// * `t` - the types of the items, as the numbers of their `ItemType`s
+/// * `n` - the names of the items
+/// * `q` - the module paths of the items, as `[i, path]` pairs giving the path
+///   of the `i`th item and all those following it up to the next pair
+/// * `d` - the one-line descriptions of the items
+/// * `i` - the parents of the items, `0` for none and otherwise one more than
+///   the parent's index into `p`
+/// * `x` - the deprecated items, each given as the distance from the previous
+///   deprecated item (or from the start)
+/// * `f` - the signatures of functions and methods, as `[distance, inputs,
+///   output]` with the distance counted the same way
+/// * `a` - the other names items are found by, from `#[doc(alias = "...")]`,
+///   as `[distance, aliases]` with the distance counted the same way
+/// * `p` - the parents, as `[type, name, module path]`
+fn search_index_js(krate: &str, shard: char, cache: &Cache,
+                   index: &[&IndexItem]) -> io::IoResult<StrBuf> {
+    let (nodeid_to_pathid, pathid_to_nodeid) = index_paths(index.iter().map(|&item| item));
+    let mut w = MemWriter::new();
+    try!(write!(&mut w, "addSearchIndex('{}', '{}', \\{\"t\":{}", krate, shard,
+                index.iter().map(|item| item.ty as uint).collect::<Vec<uint>>()
+                     .to_json().to_str()));
+
+    try!(write!(&mut w, ",\"n\":{}", index.iter().map(|item| {
+        item.name.clone()
+    }).collect::<Vec<StrBuf>>().to_json().to_str()));
+
+    try!(write!(&mut w, ",\"q\":["));
+    let mut lastpath = None;
+    for (i, item) in index.iter().enumerate() {
+        // Only record where the path changes
+        if lastpath == Some(item.path.as_slice()) { continue }
+        if lastpath.is_some() {
+            try!(write!(&mut w, ","));
         }
-        assert_eq!(nodeid_to_pathid.len(), pathid_to_nodeid.len());
+        try!(write!(&mut w, "[{},{}]", i, item.path.to_json().to_str()));
+        lastpath = Some(item.path.as_slice());
     }
 
-    // Publish the search index
-    let index = {
-        let mut w = MemWriter::new();
-        try!(write!(&mut w, r#"searchIndex['{}'] = \{"items":["#, krate.name));
-
-        let mut lastpath = "".to_strbuf();
-        for (i, item) in cache.search_index.iter().enumerate() {
-            // Omit the path if it is same to that of the prior item.
-            let path;
-            if lastpath.as_slice() == item.path.as_slice() {
-                path = "";
-            } else {
-                lastpath = item.path.to_strbuf();
-                path = item.path.as_slice();
-            };
+    try!(write!(&mut w, "],\"d\":{}", index.iter().map(|item| {
+        item.desc.clone()
+    }).collect::<Vec<StrBuf>>().to_json().to_str()));
+
+    try!(write!(&mut w, ",\"i\":{}", index.iter().map(|item| {
+        item.parent.map_or(0, |nodeid| *nodeid_to_pathid.find(&nodeid).unwrap() + 1)
+    }).collect::<Vec<uint>>().to_json().to_str()));
+
+    let mut last = 0;
+    let deprecated = index.iter().enumerate().filter(|&(_, item)| {
+        item.deprecated
+    }).map(|(i, _)| {
+        let delta = i - last;
+        last = i;
+        delta
+    }).collect::<Vec<uint>>();
+    try!(write!(&mut w, ",\"x\":{}", deprecated.to_json().to_str()));
+
+    try!(write!(&mut w, ",\"f\":["));
+    let mut last = None;
+    for (i, item) in index.iter().enumerate() {
+        let sig = match item.sig {
+            Some(ref sig) => sig,
+            None => continue,
+        };
+        if last.is_some() {
+            try!(write!(&mut w, ","));
+        }
+        try!(write!(&mut w, "[{},{},{}]", i - last.unwrap_or(0),
+                    sig.inputs.to_json().to_str(), sig.output.to_json().to_str()));
+        last = Some(i);
+    }
 
-            if i > 0 {
-                try!(write!(&mut w, ","));
+    try!(write!(&mut w, "],\"a\":["));
+    let mut last = None;
+    for (i, item) in index.iter().enumerate() {
+        if item.aliases.len() == 0 { continue }
+        if last.is_some() {
+            try!(write!(&mut w, ","));
+        }
+        try!(write!(&mut w, "[{},{}]", i - last.unwrap_or(0),
+                    item.aliases.to_json().to_str()));
+        last = Some(i);
+    }
+
+    try!(write!(&mut w, "],\"p\":["));
+    for (i, &nodeid) in pathid_to_nodeid.iter().enumerate() {
+        let def = ast_util::local_def(nodeid);
+        let &(ref fqp, short) = cache.paths.find(&def).unwrap();
+        if i > 0 {
+            try!(write!(&mut w, ","));
+        }
+        try!(write!(&mut w, "[{:u},{},{}]", short,
+                    fqp.last().unwrap().to_json().to_str(),
+                    fqp.slice_to(fqp.len() - 1).connect("::").to_json().to_str()));
+    }
+    try!(write!(&mut w, "]\\});\n"));
+
+    Ok(str::from_utf8(w.unwrap().as_slice()).unwrap().to_strbuf())
+}
+
+/// Turns the image at the local path `path` into a `data:` URI, for pages to
+/// show without loading anything. Images which are only available over the
+/// network, or which can't be read, are dropped with a warning.
+fn embed_image(path: &str) -> StrBuf {
+    if path.len() == 0 || path.starts_with("data:") { return path.to_strbuf() }
+    if path.contains("://") || path.starts_with("//") {
+        println!("warning: not embedding `{}` into offline output, \
+                  it isn't a local file", path);
+        return StrBuf::new()
+    }
+    let p = Path::new(path);
+    let ext = p.extension_str().map(|e| e.to_ascii_lower()).unwrap_or("".to_owned());
+    let mime = match ext.as_slice() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    };
+    match File::open(&p).read_to_end() {
+        Ok(bytes) => {
+            format_strbuf!("data:{};base64,{}", mime, bytes.as_slice().to_base64(STANDARD))
+        }
+        Err(e) => {
+            println!("warning: not embedding `{}` into offline output: {}", path, e);
+            StrBuf::new()
+        }
+    }
+}
+
+/// Copies the image at `path` into the root of the output at `dst` if it's a
+/// local file, returning its path relative to that root. Anything else, such
+/// as a URL, is returned as it is.
+fn copy_image(sink: &mut OutputSink, dst: &Path, path: &str) -> io::IoResult<StrBuf> {
+    if path.len() == 0 || path.starts_with("data:") || path.contains("://") {
+        return Ok(path.to_strbuf())
+    }
+    let p = Path::new(path);
+    let name = match p.filename_str() {
+        Some(name) if p.is_file() => name.to_strbuf(),
+        _ => return Ok(path.to_strbuf()),
+    };
+    let contents = try!(File::open(&p).read_to_end());
+    try!(output::write(sink, &dst.join(name.as_slice()), contents.as_slice()));
+    Ok(name)
+}
+
+/// Makes sure the URL of the root of some documentation, such as where the
+/// output is published at, ends in a slash, so the paths of pages can be
+/// appended to it.
+fn root_url(url: &str) -> StrBuf {
+    if url.ends_with("/") {
+        url.to_strbuf()
+    } else {
+        format_strbuf!("{}/", url)
+    }
+}
+
+/// Adds `version` to the versions of `krate` listed in the manifest at `dst`,
+/// which maps the name of each crate to the list of its versions.
+///
+/// Rustdoc only ever adds the version being documented to the manifest.
+/// Sites hosting several versions of the docs, each in a directory named
+/// after the version, can list all of them in the manifest of each so pages
+/// offer to switch between them.
+fn update_versions(sink: &mut OutputSink, dst: &Path, krate: &str,
+                   version: &str) -> io::IoResult<()> {
+    let mut manifest = match try!(sink.read(dst)) {
+        Some(contents) => {
+            let contents = str::from_utf8(contents.as_slice()).unwrap_or("");
+            match json::from_str(contents) {
+                Ok(json::Object(obj)) => obj,
+                _ => box TreeMap::new(),
             }
-            try!(write!(&mut w, r#"[{:u},"{}","{}",{}"#,
-                        item.ty, item.name, path,
-                        item.desc.to_json().to_str()));
-            match item.parent {
-                Some(nodeid) => {
-                    let pathid = *nodeid_to_pathid.find(&nodeid).unwrap();
-                    try!(write!(&mut w, ",{}", pathid));
+        }
+        None => box TreeMap::new(),
+    };
+    let mut versions = match manifest.pop(&krate.to_owned()) {
+        Some(json::List(list)) => list,
+        _ => Vec::new(),
+    };
+    let version = json::String(version.to_owned());
+    if !versions.contains(&version) {
+        versions.push(version);
+    }
+    manifest.insert(krate.to_owned(), json::List(versions));
+    let mut w = try!(sink.create(dst));
+    try!(write!(&mut w, "{}", json::Object(manifest).to_str()));
+    Ok(())
+}
+
+/// How `search-index.js` starts the line listing the crates with a chunk of
+/// the search index.
+static SEARCH_CRATES_VAR: &'static str = "var searchIndexCrates = ";
+
+/// Reduces the `NodeId`s of the parents in the search index into smaller
+/// sequential numbers, pruning the paths that do not appear in the index.
+/// Returns the mapping each way.
+pub fn search_paths(cache: &Cache) -> (HashMap<ast::NodeId, uint>, Vec<ast::NodeId>) {
+    index_paths(cache.search_index.iter())
+}
+
+/// Numbers the parents of `items` like `search_paths`, for part of the index.
+fn index_paths<'a, I: Iterator<&'a IndexItem>>(items: I)
+                                               -> (HashMap<ast::NodeId, uint>, Vec<ast::NodeId>) {
+    let mut nodeid_to_pathid = HashMap::new();
+    let mut pathid_to_nodeid = Vec::new();
+    for item in items {
+        match item.parent {
+            Some(nodeid) => {
+                if !nodeid_to_pathid.contains_key(&nodeid) {
+                    let pathid = pathid_to_nodeid.len();
+                    nodeid_to_pathid.insert(nodeid, pathid);
+                    pathid_to_nodeid.push(nodeid);
                 }
-                None => {}
             }
-            try!(write!(&mut w, "]"));
+            None => {}
         }
+    }
+    assert_eq!(nodeid_to_pathid.len(), pathid_to_nodeid.len());
+    (nodeid_to_pathid, pathid_to_nodeid)
+}
 
-        try!(write!(&mut w, r#"],"paths":["#));
+/// Generates the documentation for `crate` into the directory `opts.dest` of
+/// `sink`
+pub fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+           sink: &mut OutputSink) -> io::IoResult<()> {
+    // The built-in themes come first, followed by those of the user. The
+    // first, `auto`, follows whether the reader's system prefers dark colors.
+    let mut themes = vec!("auto".to_strbuf(), "light".to_strbuf(), "dark".to_strbuf());
+    for theme in opts.themes.iter() {
+        if !themes.contains(&theme.name) {
+            themes.push(theme.name.clone());
+        }
+    }
+    let theme = match opts.default_theme {
+        Some(ref t) if !themes.contains(t) => {
+            return Err(io::IoError {
+                kind: io::InvalidInput,
+                desc: "unknown theme",
+                detail: Some(t.to_str()),
+            })
+        }
+        Some(ref t) => t.clone(),
+        None => themes.get(0).clone(),
+    };
 
-        for (i, &nodeid) in pathid_to_nodeid.iter().enumerate() {
-            let def = ast_util::local_def(nodeid);
-            let &(ref fqp, short) = cache.paths.find(&def).unwrap();
-            if i > 0 {
-                try!(write!(&mut w, ","));
+    let mut cx = Context {
+        dst: opts.dest.clone(),
+        current: Vec::new(),
+        root_path: StrBuf::new(),
+        sidebar: HashMap::new(),
+        externs: {
+            let mut externs = krate.externs.iter().map(|&(n, ref e)| {
+                (n, e.name.clone())
+            }).collect::<Vec<_>>();
+            externs.sort_by(|a, b| a.ref1().cmp(b.ref1()));
+            externs
+        },
+        layout: layout::Layout {
+            logo: "".to_strbuf(),
+            favicon: "".to_strbuf(),
+            external_html: opts.external_html.clone(),
+            krate: krate.name.clone(),
+            themes: themes,
+            theme: theme,
+            template: opts.template.clone(),
+            version: opts.crate_version.clone(),
+            canonical_url: opts.canonical_url.as_ref().map(|s| root_url(s.as_slice())),
+            offline: opts.offline_assets,
+            minify: opts.minify,
+            math: opts.math.clone(),
+            page_layout: opts.page_layout.clone(),
+        },
+        include_sources: true,
+        inline_sources: None,
+        signature_width: opts.signature_width,
+        item_order: opts.item_order.clone(),
+    };
+
+    match krate.module.as_ref().map(|m| m.doc_list().unwrap_or(&[])) {
+        Some(attrs) => {
+            for attr in attrs.iter() {
+                match *attr {
+                    clean::NameValue(ref x, ref s)
+                            if "html_favicon_url" == x.as_slice() => {
+                        cx.layout.favicon = s.to_strbuf();
+                    }
+                    clean::NameValue(ref x, ref s)
+                            if "html_logo_url" == x.as_slice() => {
+                        cx.layout.logo = s.to_strbuf();
+                    }
+                    // The command line takes precedence
+                    clean::NameValue(ref x, ref s)
+                            if "html_canonical_url" == x.as_slice() &&
+                               opts.canonical_url.is_none() => {
+                        cx.layout.canonical_url = Some(root_url(s.as_slice()));
+                    }
+                    clean::Word(ref x)
+                            if "html_no_source" == x.as_slice() => {
+                        cx.include_sources = false;
+                    }
+                    _ => {}
+                }
             }
-            try!(write!(&mut w, r#"[{:u},"{}"]"#,
-                        short, *fqp.last().unwrap()));
         }
+        None => {}
+    }
 
-        try!(write!(&mut w, r"]\};"));
+    // The command line takes precedence here as well
+    match opts.logo {
+        Some(ref logo) => cx.layout.logo = logo.clone(),
+        None => {}
+    }
+    match opts.favicon {
+        Some(ref favicon) => cx.layout.favicon = favicon.clone(),
+        None => {}
+    }
 
-        str::from_utf8(w.unwrap().as_slice()).unwrap().to_owned()
-    };
+    // Offline output can't load the logo or favicon from elsewhere, so they're
+    // embedded into the pages instead, and math is left as it's written unless
+    // its script is at hand
+    if opts.offline_assets {
+        cx.layout.logo = embed_image(cx.layout.logo.as_slice());
+        cx.layout.favicon = embed_image(cx.layout.favicon.as_slice());
+        let remote = cx.layout.math.as_ref().map_or(false, |m| {
+            m.as_slice().contains("://") || m.as_slice().starts_with("//")
+        });
+        if remote {
+            println!("warning: not typesetting math in offline output, its script \
+                      `{}` isn't a local file", cx.layout.math.get_ref());
+            cx.layout.math = None;
+        }
+    }
+
+    // Impls are rendered on the pages of the types they're for (by way of the
+    // cache), so they and any modules which only contained them are dropped.
+    let mut krate = HoardStripper::new(&*cache).fold_crate(krate);
+
+    // Publish the search index. The index of each crate is split into shards
+    // by the initial of the item names, which the search JS only loads once
+    // they're needed. Nothing else writes to them, so they're published
+    // without waiting for the lock on the shared files.
+    let shards = try!(search_index_shards(krate.name.as_slice(), &*cache,
+                                          opts.item_order.clone()));
+    let index_dir = cx.dst.join("search-index");
+    for &(shard, ref index) in shards.iter() {
+        try!(output::write(sink, &index_dir.join(krate.name.as_slice())
+                                           .join(format!("{}.js", shard)),
+                           index.as_bytes()));
+    }
+    try!(output::write(sink, &index_dir.join(format!("{}.js", krate.name)),
+                       search_shards_js(krate.name.as_slice(), shards.as_slice()).as_bytes()));
 
     // Write out the shared files. Note that these are shared among all rustdoc
     // docs placed in the output directory, so this needs to be a synchronized
-    // operation with respect to all other rustdocs running around.
+    // operation with respect to all other rustdocs running around. Only output
+    // placed on disk can be shared like this.
     {
-        try!(mkdir(&cx.dst));
-        let _lock = ::flock::Lock::new(&cx.dst.join(".lock"));
+        let _lock = match sink.root() {
+            Some(root) => {
+                let dir = root.join(&cx.dst);
+                if !dir.exists() {
+                    try!(fs::mkdir_recursive(&dir, io::UserRWX));
+                }
+                Some(::flock::Lock::new(&dir.join(".lock")))
+            }
+            None => None,
+        };
 
         // Add all the static files. These may already exist, but we just
         // overwrite them anyway to make sure that they're fresh and up-to-date.
-        try!(write(cx.dst.join("jquery.js"),
-                   include_bin!("static/jquery-2.1.0.min.js")));
-        try!(write(cx.dst.join("main.js"), include_bin!("static/main.js")));
-        try!(write(cx.dst.join("main.css"), include_bin!("static/main.css")));
-        try!(write(cx.dst.join("normalize.css"),
-                   include_bin!("static/normalize.css")));
-        try!(write(cx.dst.join("FiraSans-Regular.woff"),
-                   include_bin!("static/FiraSans-Regular.woff")));
-        try!(write(cx.dst.join("FiraSans-Medium.woff"),
-                   include_bin!("static/FiraSans-Medium.woff")));
-        try!(write(cx.dst.join("Heuristica-Regular.woff"),
-                   include_bin!("static/Heuristica-Regular.woff")));
-        try!(write(cx.dst.join("Heuristica-Italic.woff"),
-                   include_bin!("static/Heuristica-Italic.woff")));
-        try!(write(cx.dst.join("Heuristica-Bold.woff"),
-                   include_bin!("static/Heuristica-Bold.woff")));
-
-        // Update the search index
+        try!(output::write(sink, &cx.dst.join("jquery.js"),
+                           include_bin!("static/jquery-2.1.0.min.js")));
+        try!(output::write(sink, &cx.dst.join("main.js"),
+                           include_bin!("static/main.js")));
+        try!(output::write(sink, &cx.dst.join("main.css"),
+                           include_bin!("static/main.css")));
+        try!(output::write(sink, &cx.dst.join("normalize.css"),
+                           include_bin!("static/normalize.css")));
+        try!(output::write(sink, &cx.dst.join("light.css"),
+                           include_bin!("static/light.css")));
+        try!(output::write(sink, &cx.dst.join("dark.css"),
+                           include_bin!("static/dark.css")));
+        try!(output::write(sink, &cx.dst.join("auto.css"),
+                           auto_theme_css().as_bytes()));
+        for theme in opts.themes.iter() {
+            try!(output::write(sink, &cx.dst.join(format!("{}.css", theme.name)),
+                               theme.css.as_bytes()));
+        }
+        try!(output::write(sink, &cx.dst.join("FiraSans-Regular.woff"),
+                           include_bin!("static/FiraSans-Regular.woff")));
+        try!(output::write(sink, &cx.dst.join("FiraSans-Medium.woff"),
+                           include_bin!("static/FiraSans-Medium.woff")));
+        try!(output::write(sink, &cx.dst.join("Heuristica-Regular.woff"),
+                           include_bin!("static/Heuristica-Regular.woff")));
+        try!(output::write(sink, &cx.dst.join("Heuristica-Italic.woff"),
+                           include_bin!("static/Heuristica-Italic.woff")));
+        try!(output::write(sink, &cx.dst.join("Heuristica-Bold.woff"),
+                           include_bin!("static/Heuristica-Bold.woff")));
+
+        // A logo or favicon given as a local file is copied in with the rest,
+        // for the pages to refer to relative to the root
+        cx.layout.logo = try!(copy_image(sink, &cx.dst, cx.layout.logo.as_slice()));
+        cx.layout.favicon = try!(copy_image(sink, &cx.dst, cx.layout.favicon.as_slice()));
+
+        // Update the list of crates in the search index. It only changes when
+        // a crate is documented into the output for the first time, so other
+        // runs leave it alone rather than rewriting it.
         let dst = cx.dst.join("search-index.js");
-        let mut all_indexes = Vec::new();
-        all_indexes.push(index);
-        if dst.exists() {
-            for line in BufferedReader::new(File::open(&dst)).lines() {
-                let line = try!(line);
-                if !line.starts_with("searchIndex") { continue }
-                if line.starts_with(format!("searchIndex['{}']", krate.name)) {
-                    continue
+        let mut crates = vec!(krate.name.clone());
+        let mut listed = false;
+        match try!(sink.read(&dst)) {
+            Some(contents) => {
+                let contents = str::from_utf8(contents.as_slice()).unwrap_or("");
+                for line in contents.lines() {
+                    if !line.starts_with(SEARCH_CRATES_VAR) { continue }
+                    let list = line.slice_from(SEARCH_CRATES_VAR.len()).trim_right_chars(';');
+                    match json::from_str(list) {
+                        Ok(json::List(list)) => {
+                            for name in list.move_iter() {
+                                match name {
+                                    json::String(name) => {
+                                        if name.as_slice() == krate.name.as_slice() {
+                                            listed = true;
+                                        } else {
+                                            crates.push(name.into_strbuf());
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
                 }
-                all_indexes.push(line);
             }
+            None => {}
+        }
+        if !listed {
+            crates.sort();
+            let mut w = try!(sink.create(&dst));
+            try!(writeln!(&mut w, "{}{};", SEARCH_CRATES_VAR, crates.to_json().to_str()));
+            try!(writeln!(&mut w, "initSearch(searchIndexCrates);"));
+        }
+
+        // Record the version of the crate for the version picker
+        match opts.crate_version {
+            Some(ref version) => {
+                try!(update_versions(sink, &cx.dst.join("versions.json"),
+                                     krate.name.as_slice(), version.as_slice()));
+            }
+            None => {}
         }
-        let mut w = try!(File::create(&dst));
-        try!(writeln!(&mut w, r"var searchIndex = \{\};"));
-        for index in all_indexes.iter() {
-            try!(writeln!(&mut w, "{}", *index));
+
+        if opts.sitemap {
+            try!(sitemap(&cx, &krate, sink));
         }
-        try!(writeln!(&mut w, "initSearch(searchIndex);"));
     }
 
+    // Hashes of the pages from the last run, if only changed pages are to be
+    // rendered
+    let hashes_file = cx.dst.join(krate.name.as_slice()).join(HASHES_FILE);
+    let mut hashes = match sink.root() {
+        Some(root) if opts.incremental => {
+            try!(Hashes::load(root, &hashes_file, &cx, &*cache))
+        }
+        Some(..) | None => Hashes::disabled(),
+    };
+
     // Render all source files (this may turn into a giant no-op)
     {
         info!("emitting source files");
-        let dst = cx.dst.join("src");
-        try!(mkdir(&dst));
-        let dst = dst.join(krate.name.as_slice());
-        try!(mkdir(&dst));
+        let dst = cx.dst.join("src").join(krate.name.as_slice());
         let mut folder = SourceCollector {
             dst: dst,
             seen: HashSet::new(),
+            links: SourceLinks::new(&krate, cx.layout.page_layout),
+            contents: if opts.inline_source { Some(HashMap::new()) } else { None },
             cx: &mut cx,
+            sink: sink,
         };
         krate = folder.fold_crate(krate);
+        folder.cx.inline_sources = folder.contents.take().map(|c| Arc::new(c));
     }
 
-    for &(n, ref e) in krate.externs.iter() {
-        cache.extern_locations.insert(n, extern_location(e, &cx.dst));
-        let did = ast::DefId { krate: n, node: ast::CRATE_NODE_ID };
-        cache.paths.insert(did, (Vec::new(), item_type::Module));
-    }
+    try!(all_items(&cx, &*cache, krate.name.as_slice(), sink));
+    try!(stability(&cx, &stability_summary::summarize(&krate), sink));
 
     // And finally render the whole crate's documentation
-    cx.krate(krate, cache)
+    try!(cx.krate(krate, cache, opts, sink, &mut hashes));
+    hashes.save(sink, &hashes_file)
 }
 
-/// Writes the entire contents of a string to a destination, not attempting to
-/// catch any errors.
-fn write(dst: Path, contents: &[u8]) -> io::IoResult<()> {
-    File::create(&dst).write(contents)
+/// The sections of the page listing all the items of a crate, with the type
+/// of the items in each and the id and title of the section.
+static ALL_ITEMS_SECTIONS: &'static [(ItemType, &'static str, &'static str)] = &[
+    (item_type::Module, "modules", "Modules"),
+    (item_type::Struct, "structs", "Structs"),
+    (item_type::Enum, "enums", "Enums"),
+    (item_type::Trait, "traits", "Traits"),
+    (item_type::Function, "functions", "Functions"),
+    (item_type::Typedef, "types", "Type Definitions"),
+    (item_type::Static, "statics", "Statics"),
+    (item_type::ForeignFunction, "ffi-fns", "Foreign Functions"),
+    (item_type::ForeignStatic, "ffi-statics", "Foreign Statics"),
+    (item_type::Macro, "macros", "Macros"),
+];
+
+/// The stylesheet of the `auto` theme, which applies the colors of the dark
+/// theme when the reader's system prefers dark colors, leaving those of
+/// main.css otherwise.
+fn auto_theme_css() -> StrBuf {
+    format_strbuf!("@media (prefers-color-scheme: dark) \\{\n{}\\}\n",
+                   include_str!("static/dark.css"))
 }
 
-/// Makes a directory on the filesystem, failing the task if an error occurs and
-/// skipping if the directory already exists.
-fn mkdir(path: &Path) -> io::IoResult<()> {
-    if !path.exists() {
-        fs::mkdir(path, io::UserRWX)
-    } else {
-        Ok(())
+/// Renders `all.html` into the directory of the crate, listing every item of
+/// the crate in the search index which isn't a member of another, grouped by
+/// type and sorted by path.
+fn all_items(cx: &Context, cache: &Cache, krate: &str,
+             sink: &mut OutputSink) -> io::IoResult<()> {
+    let mut items = cache.search_index.iter().filter(|item| {
+        item.parent.is_none()
+    }).map(|item| {
+        let path = if item.path.len() == 0 {
+            item.name.clone()
+        } else {
+            format_strbuf!("{}::{}", item.path, item.name)
+        };
+        (path, item)
+    }).collect::<Vec<(StrBuf, &IndexItem)>>();
+    items.sort_by(|a, b| a.ref0().cmp(b.ref0()));
+
+    let layout = cx.layout.page_layout;
+    let mut w = MemWriter::new();
+    try!(breadcrumbs(&mut w, layout, "../", [krate.to_strbuf()], 1, "All items"));
+    try!(write!(&mut w, "<h1 class='fqn'>List of all items in {}</h1>", krate));
+    for &(ty, id, name) in ALL_ITEMS_SECTIONS.iter() {
+        let mut section = items.iter().filter(|&&(_, item)| item.ty == ty).peekable();
+        if section.peek().is_none() { continue }
+        try!(write!(&mut w, "<h2 id='{id}' class='section-header'>\
+                             <a href='\\#{id}'>{name}</a></h2>\n<ul class='all-items'>",
+                    id = id, name = name));
+        for &(ref path, item) in section {
+            let module = item.path.as_slice().split_str("::").map(|s| s.to_strbuf())
+                                  .collect::<Vec<StrBuf>>();
+            // Statics don't have pages of their own
+            let href = match ty {
+                item_type::Module => {
+                    let mut fqp = module.clone();
+                    fqp.push(item.name.clone());
+                    module_page(layout, fqp.as_slice())
+                }
+                item_type::Static | item_type::ForeignStatic => {
+                    module_page(layout, module.as_slice())
+                }
+                _ => item_page(layout, module.as_slice(), ty.to_static_str(),
+                               item.name.as_slice()),
+            };
+            try!(write!(&mut w, "<li><a class='{}' href='../{}'>{}</a></li>",
+                        ty.to_static_str(), href, *path));
+        }
+        try!(write!(&mut w, "</ul>"));
     }
+    let content = str::from_utf8(w.unwrap().as_slice()).unwrap().to_strbuf();
+
+    let dst = cx.dst.join(krate).join("all.html");
+    let path = format_strbuf!("{}/all.html", krate);
+    let title = format_strbuf!("List of all items in {} - Rust", krate);
+    let page = layout::Page {
+        title: title.as_slice(),
+        ty: "mod",
+        root_path: "../",
+        path: path.as_slice(),
+    };
+    let mut w = BufferedWriter::new(try!(sink.create(&dst)));
+    try!(layout::render(&mut w as &mut Writer, &cx.layout, &page, &(""), &content));
+    w.flush()
+}
+
+/// The start of each entry of the sitemap index, `sitemap.xml`, which lists
+/// the sitemaps of the crates in the output one per line.
+static SITEMAP_ENTRY: &'static str = "<sitemap><loc>";
+
+/// Writes `sitemap.xml` into the directory of the crate, listing the pages of
+/// the crate at the URL the output is published at, and adds it to the
+/// sitemap index of the same name at the root of the output. A `robots.txt`
+/// pointing crawlers to the index is written unless there's one already.
+fn sitemap(cx: &Context, krate: &clean::Crate, sink: &mut OutputSink) -> io::IoResult<()> {
+    let root = match cx.layout.canonical_url {
+        Some(ref url) => url.as_slice(),
+        None => {
+            println!("warning: not writing a sitemap, the URL the documentation is \
+                      published at isn't known (see --canonical-url)");
+            return Ok(())
+        }
+    };
+
+    let mut pages = vec!(format_strbuf!("{}/all.html", krate.name),
+                         format_strbuf!("{}/stability.html", krate.name));
+    match krate.module {
+        Some(clean::Item { inner: clean::ModuleItem(ref m), .. }) => {
+            item_pages(cx.layout.page_layout, m, &mut vec!(krate.name.clone()), &mut pages);
+        }
+        _ => {}
+    }
+    pages.sort();
+
+    let mut w = MemWriter::new();
+    try!(write!(&mut w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"));
+    for page in pages.iter() {
+        try!(write!(&mut w, "<url><loc>{}{}</loc></url>\n",
+                    Escape(root), Escape(page.as_slice())));
+    }
+    try!(write!(&mut w, "</urlset>\n"));
+    try!(output::write(sink, &cx.dst.join(krate.name.as_slice()).join("sitemap.xml"),
+                       w.unwrap().as_slice()));
+
+    // The index is shared with the other crates in the output, so the entries
+    // of the others are kept, and it's left alone if it lists this crate
+    let dst = cx.dst.join("sitemap.xml");
+    let ours = format_strbuf!("{}{}{}/sitemap.xml</loc></sitemap>", SITEMAP_ENTRY,
+                              Escape(root), Escape(krate.name.as_slice()));
+    let mut entries = vec!(ours.clone());
+    let mut listed = false;
+    match try!(sink.read(&dst)) {
+        Some(contents) => {
+            let contents = str::from_utf8(contents.as_slice()).unwrap_or("");
+            for line in contents.lines() {
+                if line == ours.as_slice() {
+                    listed = true;
+                } else if line.starts_with(SITEMAP_ENTRY) {
+                    entries.push(line.to_strbuf());
+                }
+            }
+        }
+        None => {}
+    }
+    if !listed {
+        entries.sort();
+        entries.dedup();
+        let mut w = MemWriter::new();
+        try!(write!(&mut w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                             <sitemapindex \
+                              xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"));
+        for entry in entries.iter() {
+            try!(write!(&mut w, "{}\n", *entry));
+        }
+        try!(write!(&mut w, "</sitemapindex>\n"));
+        try!(output::write(sink, &dst, w.unwrap().as_slice()));
+    }
+
+    let robots = cx.dst.join("robots.txt");
+    if try!(sink.read(&robots)).is_none() {
+        let contents = format!("User-agent: *\nAllow: /\nSitemap: {}sitemap.xml\n", root);
+        try!(output::write(sink, &robots, contents.as_bytes()));
+    }
+    Ok(())
+}
+
+/// Adds the paths of the pages of module `m`, at `path`, and of everything
+/// within it which is linked to a page of its own to `pages`, relative to the
+/// root of the output.
+fn item_pages(layout: PageLayout, m: &clean::Module, path: &mut Vec<StrBuf>,
+              pages: &mut Vec<StrBuf>) {
+    pages.push(module_page(layout, path.as_slice()));
+    for item in m.items.iter() {
+        let name = match item.name {
+            Some(ref name) => name,
+            None => continue,
+        };
+        match item.inner {
+            clean::ModuleItem(ref m) => {
+                path.push(name.clone());
+                item_pages(layout, m, path, pages);
+                path.pop();
+            }
+            // Statics are documented on the page of their module, like in
+            // `all.html`
+            clean::StaticItem(..) | clean::ForeignStaticItem(..) => {}
+            _ => pages.push(item_path(layout, path.as_slice(), item)),
+        }
+    }
+}
+
+/// Renders `stability.html` into the directory of the crate, tabulating how
+/// many items of each module and its submodules are at each stability level.
+fn stability(cx: &Context, summary: &ModuleSummary,
+             sink: &mut OutputSink) -> io::IoResult<()> {
+    let krate = summary.path.get(0).as_slice();
+    let layout = cx.layout.page_layout;
+    let mut w = MemWriter::new();
+    try!(breadcrumbs(&mut w, layout, "../", [krate.to_strbuf()], 1, "Stability"));
+    try!(write!(&mut w, "<h1 class='fqn'>Stability of {}</h1>", krate));
+    try!(write!(&mut w, "<table class='stability-summary'><tr><th>Module</th><th></th>"));
+    for &(name, _) in Counts::zero().levels().iter() {
+        try!(write!(&mut w, "<th>{}</th>", name));
+    }
+    try!(write!(&mut w, "</tr>"));
+    try!(stability_rows(&mut w, layout, summary));
+    try!(write!(&mut w, "</table>"));
+    let content = str::from_utf8(w.unwrap().as_slice()).unwrap().to_strbuf();
+
+    let dst = cx.dst.join(krate).join("stability.html");
+    let path = format_strbuf!("{}/stability.html", krate);
+    let title = format_strbuf!("Stability of {} - Rust", krate);
+    let page = layout::Page {
+        title: title.as_slice(),
+        ty: "mod",
+        root_path: "../",
+        path: path.as_slice(),
+    };
+    let mut w = BufferedWriter::new(try!(sink.create(&dst)));
+    try!(layout::render(&mut w as &mut Writer, &cx.layout, &page, &(""), &content));
+    w.flush()
+}
+
+/// Writes the row of the stability table for a module, followed by those of
+/// its submodules.
+fn stability_rows(w: &mut Writer, layout: PageLayout,
+                  m: &ModuleSummary) -> io::IoResult<()> {
+    let counts = m.total();
+    let total = counts.total();
+    let depth = m.path.len() - 1;
+    try!(write!(w, "<tr><td style='padding-left: {}em'>\
+                    <a class='mod' href='../{}'>{}</a></td>\
+                    <td><div class='stability-bar'>",
+                depth, module_page(layout, m.path.as_slice()), *m.path.get(depth)));
+    for &(name, n) in counts.levels().iter() {
+        if n == 0 { continue }
+        try!(write!(w, "<span class='{}' style='width: {:.1f}%' title='{} {}'></span>",
+                    name, 100.0 * n as f64 / total as f64, n, name));
+    }
+    try!(write!(w, "</div></td>"));
+    for &(_, n) in counts.levels().iter() {
+        try!(write!(w, "<td>{}</td>", n));
+    }
+    try!(write!(w, "</tr>"));
+    for sub in m.submodules.iter() {
+        try!(stability_rows(w, layout, sub));
+    }
+    Ok(())
 }
 
 /// Takes a path to a source file and cleans the path to it. This canonicalizes
@@ -476,6 +1261,23 @@ fn clean_srcpath(src: &[u8], f: |&str|) {
     }
 }
 
+/// Points links to the items of each external crate named in `urls` at the
+/// documentation at the URL paired with it, wherever else it may be found.
+/// Returns the names which aren't of external crates of `krate`.
+pub fn set_extern_urls(cache: &mut Cache, krate: &clean::Crate,
+                       urls: &[(StrBuf, StrBuf)]) -> Vec<StrBuf> {
+    let mut unknown = Vec::new();
+    for &(ref name, ref url) in urls.iter() {
+        match krate.externs.iter().find(|&&(_, ref e)| e.name == *name) {
+            Some(&(n, _)) => {
+                cache.extern_locations.insert(n, Remote(root_url(url.as_slice())));
+            }
+            None => unknown.push(name.clone()),
+        }
+    }
+    unknown
+}
+
 /// Attempts to find where an external crate is located, given that we're
 /// rendering in to the specified source destination.
 fn extern_location(e: &clean::ExternalCrate, dst: &Path) -> ExternalLocation {
@@ -494,10 +1296,7 @@ fn extern_location(e: &clean::ExternalCrate, dst: &Path) -> ExternalLocation {
                     match *attr {
                         clean::NameValue(ref x, ref s)
                                 if "html_root_url" == x.as_slice() => {
-                            if s.as_slice().ends_with("/") {
-                                return Remote(s.to_strbuf());
-                            }
-                            return Remote(format_strbuf!("{}/", s));
+                            return Remote(root_url(s.as_slice()));
                         }
                         _ => {}
                     }
@@ -515,7 +1314,8 @@ impl<'a> DocFolder for SourceCollector<'a> {
     fn fold_item(&mut self, item: clean::Item) -> Option<clean::Item> {
         // If we're including source files, and we haven't seen this file yet,
         // then we need to render it out to the filesystem
-        if self.cx.include_sources && !self.seen.contains(&item.source.filename) {
+        if self.cx.include_sources && !item.is_source_hidden() &&
+           !self.seen.contains(&item.source.filename) {
 
             // If it turns out that we couldn't read this file, then we probably
             // can't read any of the files (generating html output from json or
@@ -536,59 +1336,137 @@ impl<'a> DocFolder for SourceCollector<'a> {
                 }
             };
             self.seen.insert(item.source.filename.clone());
+        } else if self.contents.is_some() && !item.is_source_hidden() &&
+                  !self.seen.contains(&item.source.filename) {
+            // Without source pages (e.g. under `#![doc(html_no_source)]`), the
+            // files are still read for the source shown on item pages
+            match read_source(item.source.filename.as_slice()) {
+                Ok(Some(contents)) => {
+                    self.contents.get_mut_ref().insert(item.source.filename.clone(), contents);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    println!("warning: source code was requested to be inlined, \
+                              but processing `{}` had an error: {}",
+                             item.source.filename, e);
+                    println!("         skipping inlining of source code");
+                    self.contents = None;
+                }
+            }
+            self.seen.insert(item.source.filename.clone());
         }
 
         self.fold_item_recur(item)
     }
 }
 
+impl SourceLinks {
+    fn new(krate: &clean::Crate, layout: PageLayout) -> SourceLinks {
+        let mut links = SourceLinks { defs: HashMap::new() };
+        match krate.module {
+            Some(clean::Item { inner: clean::ModuleItem(ref m), .. }) => {
+                let mut path = vec!(krate.name.clone());
+                for item in m.items.iter() {
+                    links.collect_defs(layout, &mut path, item);
+                }
+            }
+            _ => {}
+        }
+        links
+    }
+
+    /// Records where `item`, which is in the module at `path`, and the items
+    /// within it are defined.
+    fn collect_defs(&mut self, layout: PageLayout, path: &mut Vec<StrBuf>,
+                    item: &clean::Item) {
+        let name = match item.name {
+            Some(ref name) if name.len() > 0 => name.clone(),
+            _ => return,
+        };
+        let page = item_path(layout, path.as_slice(), item);
+        self.defs.find_or_insert_with(item.source.filename.clone(), |_| HashMap::new())
+                 .insert((item.source.loline, name.clone()), page);
+        match item.inner {
+            clean::ModuleItem(ref m) => {
+                path.push(name);
+                for item in m.items.iter() {
+                    self.collect_defs(layout, path, item);
+                }
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the source file `filename`, without its utf-8 BOM if any. Returns
+/// `None` if it's some standard library macro thing we can't have the source
+/// to anyway.
+fn read_source(filename: &str) -> io::IoResult<Option<StrBuf>> {
+    let contents = match File::open(&Path::new(filename)).read_to_end() {
+        Ok(r) => r,
+        // macros from other libraries get special filenames which we can
+        // safely ignore
+        Err(..) if filename.starts_with("<") &&
+                   filename.ends_with("macros>") => return Ok(None),
+        Err(e) => return Err(e)
+    };
+    let contents = str::from_utf8(contents.as_slice()).unwrap();
+
+    // Remove the utf-8 BOM if any
+    let contents = if contents.starts_with("\ufeff") {
+        contents.as_slice().slice_from(3)
+    } else {
+        contents.as_slice()
+    };
+    Ok(Some(contents.to_strbuf()))
+}
+
 impl<'a> SourceCollector<'a> {
     /// Renders the given filename into its corresponding HTML source file.
     fn emit_source(&mut self, filename: &str) -> io::IoResult<()> {
         let p = Path::new(filename);
-
-        // If we couldn't open this file, then just returns because it
-        // probably means that it's some standard library macro thing and we
-        // can't have the source to it anyway.
-        let contents = match File::open(&p).read_to_end() {
-            Ok(r) => r,
-            // macros from other libraries get special filenames which we can
-            // safely ignore
-            Err(..) if filename.starts_with("<") &&
-                       filename.ends_with("macros>") => return Ok(()),
-            Err(e) => return Err(e)
+        let contents = match try!(read_source(filename)) {
+            Some(contents) => contents,
+            None => return Ok(()),
         };
-        let contents = str::from_utf8(contents.as_slice()).unwrap();
+        let contents = contents.as_slice();
 
-        // Remove the utf-8 BOM if any
-        let contents = if contents.starts_with("\ufeff") {
-            contents.as_slice().slice_from(3)
-        } else {
-            contents.as_slice()
-        };
-
-        // Create the intermediate directories
+        // Mirror the intermediate directories
         let mut cur = self.dst.clone();
         let mut root_path = StrBuf::from_str("../../");
         clean_srcpath(p.dirname(), |component| {
             cur.push(component);
-            mkdir(&cur).unwrap();
             root_path.push_str("../");
         });
 
         cur.push(Vec::from_slice(p.filename().expect("source has no filename"))
                  .append(bytes!(".html")));
-        let mut w = BufferedWriter::new(try!(File::create(&cur)));
+        let mut w = BufferedWriter::new(try!(self.sink.create(&cur)));
 
         let title = format!("{} -- source", cur.filename_display());
+        // Paths which aren't UTF-8 have the invalid parts replaced, like the
+        // names of files in archives
+        let path = format_strbuf!("{}", cur.path_relative_from(&self.cx.dst).unwrap().display());
         let page = layout::Page {
             title: title,
             ty: "source",
             root_path: root_path.as_slice(),
+            path: path.as_slice(),
+        };
+        let source = Source {
+            contents: contents,
+            file: filename,
+            links: &self.links,
+            root_path: root_path.as_slice(),
         };
         try!(layout::render(&mut w as &mut Writer, &self.cx.layout,
-                              &page, &(""), &Source(contents)));
+                              &page, &(""), &source));
         try!(w.flush());
+        match self.contents {
+            Some(ref mut c) => { c.insert(filename.to_strbuf(), contents.to_strbuf()); }
+            None => {}
+        }
         return Ok(());
     }
 }
@@ -604,6 +1482,10 @@ impl DocFolder for Cache {
             }
             _ => self.privmod,
         };
+        let private = self.document_private && self.is_private(&item);
+        if private {
+            self.private_items.insert(item.id);
+        }
 
         // Register any generics to their corresponding string. This is used
         // when pretty-printing types
@@ -646,6 +1528,11 @@ impl DocFolder for Cache {
                             clean::ResolvedPath{..} => {
                                 v.unshift(PathType(i.for_.clone()));
                             }
+                            ref for_ if is_blanket(for_) => {
+                                v.push(BlanketType(i.generics.clone(),
+                                                   i.trait_.get_ref().clone(),
+                                                   i.for_.clone()));
+                            }
                             _ => {
                                 v.push(OtherType(i.generics.clone(),
                                                  i.trait_.get_ref().clone(),
@@ -661,7 +1548,7 @@ impl DocFolder for Cache {
 
         // Index this method for searching later on
         match item.name {
-            Some(ref s) => {
+            Some(..) => {
                 let parent = match item.inner {
                     clean::TyMethodItem(..) |
                     clean::StructFieldItem(..) |
@@ -694,16 +1581,12 @@ impl DocFolder for Cache {
                     _ => (None, Some(self.stack.as_slice()))
                 };
                 match parent {
-                    (parent, Some(path)) if !self.privmod => {
-                        self.search_index.push(IndexItem {
-                            ty: shortty(&item),
-                            name: s.to_strbuf(),
-                            path: path.connect("::").to_strbuf(),
-                            desc: shorter(item.doc_value()).to_strbuf(),
-                            parent: parent,
-                        });
+                    (parent, Some(path)) if !self.privmod && !private => {
+                        let path = path.connect("::").to_strbuf();
+                        let entry = IndexItem::new(&item, path, parent, &self.typarams);
+                        self.search_index.push(entry);
                     }
-                    (Some(parent), None) if !self.privmod => {
+                    (Some(parent), None) if !self.privmod && !private => {
                         // We have a parent, but we don't know where they're
                         // defined yet. Wait for later to index this item.
                         self.orphan_methods.push((parent, item.clone()))
@@ -773,6 +1656,7 @@ impl DocFolder for Cache {
 
         // Once we've recursively found all the generics, then hoard off all the
         // implementations elsewhere
+        let keep_private = self.document_private;
         let ret = match self.fold_item_recur(item) {
             Some(item) => {
                 match item {
@@ -808,12 +1692,13 @@ impl DocFolder for Cache {
                     }
                     // Private modules may survive the strip-private pass if
                     // they contain impls for public types, but those will get
-                    // stripped here
+                    // stripped here, unless private items are documented
                     clean::Item { inner: clean::ModuleItem(ref m),
                                   visibility, .. }
                             if (m.items.len() == 0 &&
                                 item.doc_value().is_none()) ||
-                               visibility != Some(ast::Public) => None,
+                               (visibility != Some(ast::Public) &&
+                                !keep_private) => None,
 
                     i => Some(i),
                 }
@@ -828,7 +1713,64 @@ impl DocFolder for Cache {
     }
 }
 
+/// Removes the items which the cache hoards away while it's being built: all
+/// impls, along with private modules (unless private items are documented)
+/// and those left empty without them.
+pub struct HoardStripper {
+    keep_private: bool,
+}
+
+impl HoardStripper {
+    pub fn new(cache: &Cache) -> HoardStripper {
+        HoardStripper { keep_private: cache.document_private }
+    }
+}
+
+impl DocFolder for HoardStripper {
+    fn fold_item(&mut self, item: clean::Item) -> Option<clean::Item> {
+        let keep_private = self.keep_private;
+        match self.fold_item_recur(item) {
+            Some(clean::Item { inner: clean::ImplItem(..), .. }) => None,
+            Some(item) => {
+                match item {
+                    clean::Item { inner: clean::ModuleItem(ref m),
+                                  visibility, .. }
+                            if (m.items.len() == 0 &&
+                                item.doc_value().is_none()) ||
+                               (visibility != Some(ast::Public) &&
+                                !keep_private) => None,
+                    i => Some(i),
+                }
+            }
+            None => None,
+        }
+    }
+}
+
 impl<'a> Cache {
+    /// Whether `item` can't be seen from outside the crate, either because
+    /// it's private or because what it's in is. Only the modules enclosing
+    /// `item` and its parents must have been crawled already.
+    fn is_private(&self, item: &clean::Item) -> bool {
+        match item.inner {
+            clean::StructItem(..) | clean::EnumItem(..) |
+            clean::TypedefItem(..) | clean::TraitItem(..) |
+            clean::FunctionItem(..) | clean::ModuleItem(..) |
+            clean::StaticItem(..) | clean::ForeignFunctionItem(..) |
+            clean::ForeignStaticItem(..) => {
+                self.privmod || item.visibility != Some(ast::Public)
+            }
+            // Methods and variants are as visible as what they belong to
+            clean::MethodItem(..) | clean::TyMethodItem(..) |
+            clean::VariantItem(..) => {
+                self.privmod || self.parent_stack.last().map_or(false, |id| {
+                    self.private_items.contains(id)
+                })
+            }
+            _ => false,
+        }
+    }
+
     fn generics(&mut self, generics: &clean::Generics) {
         for typ in generics.type_params.iter() {
             self.typarams.insert(typ.id, typ.name.clone());
@@ -837,28 +1779,72 @@ impl<'a> Cache {
 }
 
 impl Context {
+    /// The link to the lines of `span` in the rendered source of the crate,
+    /// relative to the current page.
+    fn src_href(&self, span: &clean::Span) -> StrBuf {
+        let mut path = Vec::new();
+        clean_srcpath(span.filename.as_bytes(), |component| {
+            path.push(component.to_owned());
+        });
+        let href = if span.loline == span.hiline {
+            format_strbuf!("{}", span.loline)
+        } else {
+            format_strbuf!("{}-{}", span.loline, span.hiline)
+        };
+        format_strbuf!("{root}src/{krate}/{path}.html\\#{href}",
+                       root = self.root_path,
+                       krate = self.layout.krate,
+                       path = path.connect("/"),
+                       href = href)
+    }
+
+    /// The source of `item`, if it's to be shown on its page. Only the source
+    /// of items other than modules which span a few lines is shown.
+    pub fn inline_source(&self, item: &clean::Item) -> Option<StrBuf> {
+        let sources = match self.inline_sources {
+            Some(ref sources) => sources,
+            None => return None,
+        };
+        let span = &item.source;
+        if item.is_mod() || item.is_source_hidden() || span.loline == 0 ||
+           span.hiline - span.loline >= MAX_INLINE_SOURCE_LINES {
+            return None
+        }
+        sources.find(&span.filename).map(|contents| {
+            let lines = contents.as_slice().lines().skip(span.loline - 1)
+                                .take(span.hiline - span.loline + 1)
+                                .collect::<Vec<&str>>();
+            lines.connect("\n").to_strbuf()
+        })
+    }
+
     /// Recurse in the directory structure and change the "root path" to make
-    /// sure it always points to the top (relatively)
+    /// sure it always points to the top (relatively). With the flat layout,
+    /// only the crate gets a directory of its own.
     fn recurse<T>(&mut self, s: StrBuf, f: |&mut Context| -> T) -> T {
         if s.len() == 0 {
             fail!("what {:?}", self);
         }
         let prev = self.dst.clone();
-        self.dst.push(s.as_slice());
-        self.root_path.push_str("../");
+        let descend = self.layout.page_layout == Nested || self.current.len() == 0;
+        if descend {
+            self.dst.push(s.as_slice());
+            self.root_path.push_str("../");
+        }
         self.current.push(s);
 
         info!("Recursing into {}", self.dst.display());
 
-        mkdir(&self.dst).unwrap();
         let ret = f(self);
 
         info!("Recursed; leaving {}", self.dst.display());
 
         // Go back to where we were at
         self.dst = prev;
-        let len = self.root_path.len();
-        self.root_path.truncate(len - 3);
+        if descend {
+            let len = self.root_path.len();
+            self.root_path.truncate(len - 3);
+        }
         self.current.pop().unwrap();
 
         return ret;
@@ -868,7 +1854,9 @@ impl Context {
     ///
     /// This currently isn't parallelized, but it'd be pretty easy to add
     /// parallelization to this function.
-    fn krate(self, mut krate: clean::Crate, cache: Cache) -> io::IoResult<()> {
+    fn krate(self, mut krate: clean::Crate, cache: Arc<Cache>,
+             opts: &RenderOptions, sink: &mut OutputSink,
+             hashes: &mut Hashes) -> io::IoResult<()> {
         let mut item = match krate.module.take() {
             Some(i) => i,
             None => return Ok(())
@@ -876,14 +1864,22 @@ impl Context {
         item.name = Some(krate.name);
 
         // using a rwarc makes this parallelizable in the future
-        cache_key.replace(Some(Arc::new(cache)));
+        cache_key.replace(Some(cache));
+        page_layout_key.replace(Some(self.layout.page_layout));
+        markdown::enable_math(self.layout.math.is_some());
 
+        let total = renderer::count_items(&item);
+        let mut done = 0;
         let mut work = vec!((self, item));
         loop {
             match work.pop() {
-                Some((mut cx, item)) => try!(cx.item(item, |cx, item| {
-                    work.push((cx.clone(), item));
-                })),
+                Some((mut cx, item)) => {
+                    try!(cx.item(item, sink, hashes, |cx, item| {
+                        work.push((cx.clone(), item));
+                    }));
+                    done += 1;
+                    try!(opts.tick(done, total));
+                }
                 None => break,
             }
         }
@@ -895,11 +1891,18 @@ impl Context {
     /// all sub-items which need to be rendered.
     ///
     /// The rendering driver uses this closure to queue up more work.
-    fn item(&mut self, item: clean::Item,
+    fn item(&mut self, item: clean::Item, sink: &mut OutputSink, hashes: &mut Hashes,
             f: |&mut Context, clean::Item|) -> io::IoResult<()> {
-        fn render(w: io::File, cx: &mut Context, it: &clean::Item,
+        fn render(sink: &mut OutputSink, hashes: &mut Hashes, path: &str,
+                  cx: &mut Context, it: &clean::Item,
                   pushname: bool) -> io::IoResult<()> {
-            info!("Rendering an item to {}", w.path().display());
+            // Every page is in the current directory, whatever the layout
+            let dst = cx.dst.join(Path::new(path).filename().unwrap());
+            if hashes.unchanged(&*sink, &dst, cx, it) {
+                info!("Skipping unchanged {}", dst.display());
+                return Ok(())
+            }
+            info!("Rendering an item to {}", dst.display());
             // A little unfortunate that this is done like this, but it sure
             // does make formatting *a lot* nicer.
             current_location_key.replace(Some(cx.current.clone()));
@@ -916,6 +1919,7 @@ impl Context {
                 ty: shortty(it).to_static_str(),
                 root_path: cx.root_path.as_slice(),
                 title: title.as_slice(),
+                path: path,
             };
 
             markdown::reset_headers();
@@ -923,7 +1927,7 @@ impl Context {
             // We have a huge number of calls to write, so try to alleviate some
             // of the pain by using a buffered writer instead of invoking the
             // write sycall all the time.
-            let mut writer = BufferedWriter::new(w);
+            let mut writer = BufferedWriter::new(try!(sink.create(&dst)));
             try!(layout::render(&mut writer as &mut Writer, &cx.layout, &page,
                                   &Sidebar{ cx: cx, item: it },
                                   &Item{ cx: cx, item: it }));
@@ -938,15 +1942,14 @@ impl Context {
                 let mut item = Some(item);
                 self.recurse(name, |this| {
                     let item = item.take_unwrap();
-                    let dst = this.dst.join("index.html");
-                    let dst = try!(File::create(&dst));
-                    try!(render(dst, this, &item, false));
+                    let path = module_page(this.layout.page_layout, this.current.as_slice());
+                    try!(render(sink, hashes, path.as_slice(), this, &item, false));
 
                     let m = match item.inner {
                         clean::ModuleItem(m) => m,
                         _ => unreachable!()
                     };
-                    this.sidebar = build_sidebar(&m);
+                    this.sidebar = build_sidebar(&m, &this.item_order);
                     for item in m.items.move_iter() {
                         f(this,item);
                     }
@@ -957,9 +1960,8 @@ impl Context {
             // Things which don't have names (like impls) don't get special
             // pages dedicated to them.
             _ if item.name.is_some() => {
-                let dst = self.dst.join(item_path(&item));
-                let dst = try!(File::create(&dst));
-                render(dst, self, &item, true)
+                let path = item_path(self.layout.page_layout, self.current.as_slice(), &item);
+                render(sink, hashes, path.as_slice(), self, &item, true)
             }
 
             _ => Ok(())
@@ -967,6 +1969,20 @@ impl Context {
     }
 }
 
+/// Writes the trail of links to the first `amt` modules of `path` followed by
+/// the name of the current page, which is within the last module of `path`
+/// and `root_path` away from the root of the output.
+fn breadcrumbs(w: &mut Writer, layout: PageLayout, root_path: &str, path: &[StrBuf],
+               amt: uint, name: &str) -> io::IoResult<()> {
+    try!(write!(w, "<nav class='breadcrumbs'>"));
+    for (i, component) in path.iter().enumerate().take(amt) {
+        try!(write!(w, "<a href='{}{}'>{}</a> &rsaquo; ",
+                    root_path, module_page(layout, path.slice_to(i + 1)),
+                    component.as_slice()));
+    }
+    write!(w, "<a href=''>{}</a></nav>", name)
+}
+
 impl<'a> Item<'a> {
     fn ismodule(&self) -> bool {
         match self.item.inner {
@@ -975,28 +1991,18 @@ impl<'a> Item<'a> {
     }
 
     fn link(&self) -> StrBuf {
-        let mut path = Vec::new();
-        clean_srcpath(self.item.source.filename.as_bytes(), |component| {
-            path.push(component.to_owned());
-        });
-        let href = if self.item.source.loline == self.item.source.hiline {
-            format_strbuf!("{}", self.item.source.loline)
-        } else {
-            format_strbuf!("{}-{}",
-                           self.item.source.loline,
-                           self.item.source.hiline)
-        };
-        format_strbuf!("{root}src/{krate}/{path}.html\\#{href}",
-                       root = self.cx.root_path,
-                       krate = self.cx.layout.krate,
-                       path = path.connect("/"),
-                       href = href)
+        self.cx.src_href(&self.item.source)
     }
 }
 
 impl<'a> fmt::Show for Item<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        // Write the breadcrumb trail header for the top
+        // Write the breadcrumb trail for the top, from the crate down
+        let cur = self.cx.current.as_slice();
+        let amt = if self.ismodule() { cur.len() - 1 } else { cur.len() };
+        try!(breadcrumbs(fmt.buf, self.cx.layout.page_layout, self.cx.root_path.as_slice(),
+                         cur, amt, self.item.name.get_ref().as_slice()));
+
         try!(write!(fmt.buf, "\n<h1 class='fqn'>"));
         match self.item.inner {
             clean::ModuleItem(ref m) => if m.is_crate {
@@ -1010,49 +2016,47 @@ impl<'a> fmt::Show for Item<'a> {
             clean::EnumItem(..) => try!(write!(fmt.buf, "Enum ")),
             _ => {}
         }
-        let cur = self.cx.current.as_slice();
-        let amt = if self.ismodule() { cur.len() - 1 } else { cur.len() };
-        for (i, component) in cur.iter().enumerate().take(amt) {
-            let mut trail = StrBuf::new();
-            for _ in range(0, cur.len() - i - 1) {
-                trail.push_str("../");
-            }
-            try!(write!(fmt.buf, "<a href='{}index.html'>{}</a>::",
-                          trail, component.as_slice()));
-        }
         try!(write!(fmt.buf, "<a class='{}' href=''>{}</a>",
                       shortty(self.item), self.item.name.get_ref().as_slice()));
 
         // Write stability attributes
-        match attr::find_stability(self.item.attrs.iter()) {
-            Some(ref stability) => {
-                try!(write!(fmt.buf,
-                       "<a class='stability {lvl}' title='{reason}'>{lvl}</a>",
-                       lvl = stability.level.to_str(),
-                       reason = match stability.text {
-                           Some(ref s) => (*s).clone(),
-                           None => InternedString::new(""),
-                       }));
-            }
-            None => {}
-        }
+        try!(write!(fmt.buf, "{}{}{}", StabilityBadge(self.item), PrivateBadge(self.item),
+                    HiddenBadge(self.item)));
 
         // Write `src` tag
-        if self.cx.include_sources {
+        if self.cx.include_sources && !self.item.is_source_hidden() {
             try!(write!(fmt.buf, "<a class='source' href='{}'>[src]</a>",
                         self.link()));
         }
         try!(write!(fmt.buf, "</h1>\n"));
+        match self.item.stability() {
+            Some(clean::Stability { level, reason: Some(ref reason), .. }) => {
+                try!(write!(fmt.buf, "<div class='stability-reason {}'>{}</div>",
+                            level, Escape(reason.as_slice())));
+            }
+            _ => {}
+        }
+
+        // Short items can show their source, which starts out collapsed
+        match self.cx.inline_source(self.item) {
+            Some(src) => {
+                try!(write!(fmt.buf, "<h3 class='collapsible inline-source'>Source \
+                                      <a href='javascript:void(0)' \
+                                         class='collapse-toggle js-only'>[&minus;]</a>\
+                                      </h3>{}", highlight::highlight(src.as_slice(), None)));
+            }
+            None => {}
+        }
 
         match self.item.inner {
             clean::ModuleItem(ref m) => {
                 item_module(fmt.buf, self.cx, self.item, m.items.as_slice())
             }
             clean::FunctionItem(ref f) | clean::ForeignFunctionItem(ref f) =>
-                item_function(fmt.buf, self.item, f),
-            clean::TraitItem(ref t) => item_trait(fmt.buf, self.item, t),
-            clean::StructItem(ref s) => item_struct(fmt.buf, self.item, s),
-            clean::EnumItem(ref e) => item_enum(fmt.buf, self.item, e),
+                item_function(fmt.buf, self.cx, self.item, f),
+            clean::TraitItem(ref t) => item_trait(fmt.buf, self.cx, self.item, t),
+            clean::StructItem(ref s) => item_struct(fmt.buf, self.cx, self.item, s),
+            clean::EnumItem(ref e) => item_enum(fmt.buf, self.cx, self.item, e),
             clean::TypedefItem(ref t) => item_typedef(fmt.buf, self.item, t),
             clean::MacroItem(ref m) => item_macro(fmt.buf, self.item, m),
             _ => Ok(())
@@ -1060,16 +2064,40 @@ impl<'a> fmt::Show for Item<'a> {
     }
 }
 
-fn item_path(item: &clean::Item) -> StrBuf {
+/// The path of the page of the module at `fqp`, relative to the root of the
+/// output. The root module of a crate is at `krate/index.html` either way.
+pub fn module_page(layout: PageLayout, fqp: &[StrBuf]) -> StrBuf {
+    match layout {
+        Nested => format_strbuf!("{}/index.html", fqp.connect("/")),
+        Flat if fqp.len() == 1 => format_strbuf!("{}/index.html", fqp[0]),
+        Flat => format_strbuf!("{}/{}.index.html", fqp[0], fqp.slice_from(1).connect(".")),
+    }
+}
+
+/// The path of the page of the item `name` of type `ty`, within the module at
+/// `module`, relative to the root of the output.
+pub fn item_page(layout: PageLayout, module: &[StrBuf], ty: &str, name: &str) -> StrBuf {
+    match layout {
+        Nested => format_strbuf!("{}/{}.{}.html", module.connect("/"), ty, name),
+        Flat if module.len() == 1 => format_strbuf!("{}/{}.{}.html", module[0], ty, name),
+        Flat => {
+            format_strbuf!("{}/{}.{}.{}.html", module[0], module.slice_from(1).connect("."),
+                           ty, name)
+        }
+    }
+}
+
+/// The path of the page of `item`, within the module at `module`, relative to
+/// the root of the output.
+fn item_path(layout: PageLayout, module: &[StrBuf], item: &clean::Item) -> StrBuf {
+    let name = item.name.get_ref();
     match item.inner {
         clean::ModuleItem(..) => {
-            format_strbuf!("{}/index.html", item.name.get_ref())
-        }
-        _ => {
-            format_strbuf!("{}.{}.html",
-                           shortty(item).to_static_str(),
-                           *item.name.get_ref())
+            let mut fqp = Vec::from_slice(module);
+            fqp.push(name.clone());
+            module_page(layout, fqp.as_slice())
         }
+        _ => item_page(layout, module, shortty(item).to_static_str(), name.as_slice()),
     }
 }
 
@@ -1087,20 +2115,22 @@ fn blank<'a>(s: Option<&'a str>) -> &'a str {
     }
 }
 
-fn shorter<'a>(s: Option<&'a str>) -> &'a str {
+/// The summary of the documentation `s` shown in item listings and the search
+/// index: its first sentence, see `markdown::summary`.
+pub fn shorter<'a>(s: Option<&'a str>) -> &'a str {
     match s {
-        Some(s) => match s.find_str("\n\n") {
-            Some(pos) => s.slice_to(pos),
-            None => s,
-        },
+        Some(s) => markdown::summary(s),
         None => ""
     }
 }
 
-fn document(w: &mut Writer, item: &clean::Item) -> fmt::Result {
+/// Renders the docs of `item`, with their top level headers at `level` to fit
+/// under the heading they're shown beneath: 2 under the title of the page, 3
+/// under a section of it, and so on.
+fn document(w: &mut Writer, item: &clean::Item, level: u32) -> fmt::Result {
     match item.doc_value() {
         Some(s) => {
-            try!(write!(w, "<div class='docblock'>{}</div>", Markdown(s)));
+            try!(write!(w, "<div class='docblock'>{}</div>", MarkdownAt(s, level)));
         }
         None => {}
     }
@@ -1109,13 +2139,17 @@ fn document(w: &mut Writer, item: &clean::Item) -> fmt::Result {
 
 fn item_module(w: &mut Writer, cx: &Context,
                item: &clean::Item, items: &[clean::Item]) -> fmt::Result {
-    try!(document(w, item));
+    try!(document(w, item, 2));
     debug!("{:?}", items);
     let mut indices = Vec::from_fn(items.len(), |i| i);
 
-    fn cmp(i1: &clean::Item, i2: &clean::Item, idx1: uint, idx2: uint) -> Ordering {
+    fn cmp(i1: &clean::Item, i2: &clean::Item, idx1: uint, idx2: uint,
+           order: &ItemOrder) -> Ordering {
         if shortty(i1) == shortty(i2) {
-            return i1.name.cmp(&i2.name);
+            return match *order {
+                Alphabetical => i1.name.cmp(&i2.name),
+                SourceOrder => idx1.cmp(&idx2),
+            };
         }
         match (&i1.inner, &i2.inner) {
             (&clean::ViewItemItem(ref a), &clean::ViewItemItem(ref b)) => {
@@ -1152,7 +2186,7 @@ fn item_module(w: &mut Writer, cx: &Context,
     }
 
     debug!("{:?}", indices);
-    indices.sort_by(|&i1, &i2| cmp(&items[i1], &items[i2], i1, i2));
+    indices.sort_by(|&i1, &i2| cmp(&items[i1], &items[i2], i1, i2, &cx.item_order));
 
     debug!("{:?}", indices);
     let mut curty = None;
@@ -1247,14 +2281,19 @@ fn item_module(w: &mut Writer, cx: &Context,
                 try!(write!(w, "
                     <tr>
                         <td><a class='{class}' href='{href}'
-                               title='{title}'>{}</a></td>
+                               title='{title}'>{}</a>{}{}{}</td>
                         <td class='docblock short'>{}</td>
                     </tr>
                 ",
                 *myitem.name.get_ref(),
+                StabilityBadge(myitem),
+                PrivateBadge(myitem),
+                HiddenBadge(myitem),
                 Markdown(shorter(myitem.doc_value())),
                 class = shortty(myitem),
-                href = item_path(myitem),
+                href = format!("{}{}", cx.root_path,
+                               item_path(cx.layout.page_layout, cx.current.as_slice(),
+                                         myitem)),
                 title = full_path(cx, myitem)));
             }
         }
@@ -1262,19 +2301,23 @@ fn item_module(w: &mut Writer, cx: &Context,
     write!(w, "</table>")
 }
 
-fn item_function(w: &mut Writer, it: &clean::Item,
+fn item_function(w: &mut Writer, cx: &Context, it: &clean::Item,
                  f: &clean::Function) -> fmt::Result {
-    try!(write!(w, "<pre class='rust fn'>{vis}{fn_style}fn \
-                    {name}{generics}{decl}</pre>",
-           vis = VisSpace(it.visibility),
-           fn_style = FnStyleSpace(f.fn_style),
-           name = it.name.get_ref().as_slice(),
-           generics = f.generics,
-           decl = f.decl));
-    document(w, it)
+    let prefix = format!("{vis}{fn_style}fn {name}{generics}",
+                         vis = VisSpace(it.visibility),
+                         fn_style = FnStyleSpace(f.fn_style),
+                         name = it.name.get_ref().as_slice(),
+                         generics = f.generics);
+    try!(write!(w, "<pre class='rust fn'>{}{}</pre>", prefix, WrappedDecl {
+        selfty: None,
+        decl: &f.decl,
+        offset: text_len(prefix.as_slice()),
+        width: cx.signature_width,
+    }));
+    document(w, it, 2)
 }
 
-fn item_trait(w: &mut Writer, it: &clean::Item,
+fn item_trait(w: &mut Writer, cx: &Context, it: &clean::Item,
               t: &clean::Trait) -> fmt::Result {
     let mut parents = StrBuf::new();
     if t.parents.len() > 0 {
@@ -1300,7 +2343,7 @@ fn item_trait(w: &mut Writer, it: &clean::Item,
         try!(write!(w, "\\{\n"));
         for m in required.iter() {
             try!(write!(w, "    "));
-            try!(render_method(w, m.item()));
+            try!(render_method(w, m.item(), Some((4, cx.signature_width))));
             try!(write!(w, ";\n"));
         }
         if required.len() > 0 && provided.len() > 0 {
@@ -1308,7 +2351,7 @@ fn item_trait(w: &mut Writer, it: &clean::Item,
         }
         for m in provided.iter() {
             try!(write!(w, "    "));
-            try!(render_method(w, m.item()));
+            try!(render_method(w, m.item(), Some((4, cx.signature_width))));
             try!(write!(w, " \\{ ... \\}\n"));
         }
         try!(write!(w, "\\}"));
@@ -1316,22 +2359,23 @@ fn item_trait(w: &mut Writer, it: &clean::Item,
     try!(write!(w, "</pre>"));
 
     // Trait documentation
-    try!(document(w, it));
+    try!(document(w, it, 2));
 
     fn meth(w: &mut Writer, m: &clean::TraitMethod) -> fmt::Result {
         try!(write!(w, "<h3 id='{}.{}' class='method'><code>",
                       shortty(m.item()),
                       *m.item().name.get_ref()));
-        try!(render_method(w, m.item()));
-        try!(write!(w, "</code></h3>"));
-        try!(document(w, m.item()));
+        try!(render_method(w, m.item(), None));
+        try!(write!(w, "</code>{}</h3>", HiddenBadge(m.item())));
+        try!(document(w, m.item(), 4));
         Ok(())
     }
 
     // Output the documentation for each function individually
     if required.len() > 0 {
         try!(write!(w, "
-            <h2 id='required-methods'>Required Methods</h2>
+            <h2 id='required-methods' class='section-header'>\
+                <a href='\#required-methods'>Required Methods</a></h2>
             <div class='methods'>
         "));
         for m in required.iter() {
@@ -1341,7 +2385,8 @@ fn item_trait(w: &mut Writer, it: &clean::Item,
     }
     if provided.len() > 0 {
         try!(write!(w, "
-            <h2 id='provided-methods'>Provided Methods</h2>
+            <h2 id='provided-methods' class='section-header'>\
+                <a href='\#provided-methods'>Provided Methods</a></h2>
             <div class='methods'>
         "));
         for m in provided.iter() {
@@ -1352,55 +2397,92 @@ fn item_trait(w: &mut Writer, it: &clean::Item,
 
     match cache_key.get().unwrap().implementors.find(&it.id) {
         Some(implementors) => {
-            try!(write!(w, "
-                <h2 id='implementors'>Implementors</h2>
-                <ul class='item-list'>
-            "));
-            for i in implementors.iter() {
-                match *i {
-                    PathType(ref ty) => {
-                        try!(write!(w, "<li><code>{}</code></li>", *ty));
-                    }
-                    OtherType(ref generics, ref trait_, ref for_) => {
-                        try!(write!(w, "<li><code>impl{} {} for {}</code></li>",
-                                      *generics, *trait_, *for_));
-                    }
-                }
-            }
-            try!(write!(w, "</ul>"));
+            let implementors = implementors.iter().collect::<Vec<&Implementor>>();
+            let (blanket, direct) = implementors.partition(|i| {
+                match **i { BlanketType(..) => true, _ => false }
+            });
+            try!(implementor_list(w, "implementors", "Implementors", direct));
+            try!(implementor_list(w, "blanket-implementations",
+                                  "Blanket Implementations", blanket));
         }
         None => {}
     }
     Ok(())
 }
 
-fn render_method(w: &mut Writer, meth: &clean::Item) -> fmt::Result {
-    fn fun(w: &mut Writer, it: &clean::Item, fn_style: ast::FnStyle,
-           g: &clean::Generics, selfty: &clean::SelfTy,
-           d: &clean::FnDecl) -> fmt::Result {
-        write!(w, "{}fn <a href='\\#{ty}.{name}' class='fnname'>{name}</a>\
-                   {generics}{decl}",
-               match fn_style {
-                   ast::UnsafeFn => "unsafe ",
-                   _ => "",
-               },
-               ty = shortty(it),
-               name = it.name.get_ref().as_slice(),
-               generics = *g,
-               decl = Method(selfty, d))
-    }
-    match meth.inner {
-        clean::TyMethodItem(ref m) => {
-            fun(w, meth, m.fn_style, &m.generics, &m.self_, &m.decl)
+/// Renders a collapsible section of the implementors of a trait, if there are
+/// any.
+fn implementor_list(w: &mut Writer, id: &str, title: &str,
+                    implementors: Vec<&Implementor>) -> fmt::Result {
+    if implementors.len() == 0 { return Ok(()) }
+    try!(write!(w, "
+        <h2 id='{id}' class='collapsible section-header'><a href='\#{id}'>{title}</a> \
+            <a href='javascript:void(0)' class='collapse-toggle js-only'>[&minus;]</a></h2>
+        <ul class='item-list'>
+    ", id = id, title = title));
+    for i in implementors.iter() {
+        match **i {
+            PathType(ref ty) => {
+                try!(write!(w, "<li><code>{}</code></li>", *ty));
+            }
+            OtherType(ref generics, ref trait_, ref for_) |
+            BlanketType(ref generics, ref trait_, ref for_) => {
+                try!(write!(w, "<li><code>impl{} {} for {}</code></li>",
+                              *generics, *trait_, *for_));
+            }
         }
-        clean::MethodItem(ref m) => {
-            fun(w, meth, m.fn_style, &m.generics, &m.self_, &m.decl)
+    }
+    write!(w, "</ul>")
+}
+
+/// Whether an impl for `ty` is for any type meeting the bounds of a type
+/// parameter, such as the `T` of `impl<T: Foo> Bar for T`, or a pointer to
+/// one.
+fn is_blanket(ty: &clean::Type) -> bool {
+    match *ty {
+        clean::Generic(..) | clean::TyParamBinder(..) => true,
+        clean::BorrowedRef { type_: ref t, .. } | clean::Unique(ref t) |
+        clean::Managed(ref t) | clean::RawPointer(_, ref t) => is_blanket(&**t),
+        _ => false,
+    }
+}
+
+/// Renders the signature of a method. Signatures within a `<pre>` can be
+/// wrapped like those of functions, given the indentation of the line the
+/// signature starts on and the width to wrap at by `wrap`.
+fn render_method(w: &mut Writer, meth: &clean::Item,
+                 wrap: Option<(uint, uint)>) -> fmt::Result {
+    let fun = |w: &mut Writer, fn_style: ast::FnStyle, g: &clean::Generics,
+               selfty: &clean::SelfTy, d: &clean::FnDecl| -> fmt::Result {
+        let prefix = format!("{}fn <a href='\\#{ty}.{name}' class='fnname'>{name}</a>\
+                              {generics}",
+                             match fn_style {
+                                 ast::UnsafeFn => "unsafe ",
+                                 _ => "",
+                             },
+                             ty = shortty(meth),
+                             name = meth.name.get_ref().as_slice(),
+                             generics = *g);
+        match wrap {
+            Some((indent, width)) => {
+                write!(w, "{}{}", prefix, WrappedDecl {
+                    selfty: Some(selfty),
+                    decl: d,
+                    offset: indent + text_len(prefix.as_slice()),
+                    width: width,
+                })
+            }
+            None => write!(w, "{}{}", prefix, Method(selfty, d)),
         }
+    };
+    match meth.inner {
+        clean::TyMethodItem(ref m) => fun(w, m.fn_style, &m.generics, &m.self_, &m.decl),
+        clean::MethodItem(ref m) => fun(w, m.fn_style, &m.generics, &m.self_, &m.decl),
         _ => unreachable!()
     }
 }
 
-fn item_struct(w: &mut Writer, it: &clean::Item,
+fn item_struct(w: &mut Writer, cx: &Context, it: &clean::Item,
                s: &clean::Struct) -> fmt::Result {
     try!(write!(w, "<pre class='rust struct'>"));
     try!(render_struct(w,
@@ -1412,7 +2494,7 @@ fn item_struct(w: &mut Writer, it: &clean::Item,
                        true));
     try!(write!(w, "</pre>"));
 
-    try!(document(w, it));
+    try!(document(w, it, 2));
     let mut fields = s.fields.iter().filter(|f| {
         match f.inner {
             clean::StructFieldItem(clean::HiddenStructField) => false,
@@ -1422,22 +2504,24 @@ fn item_struct(w: &mut Writer, it: &clean::Item,
     }).peekable();
     match s.struct_type {
         doctree::Plain if fields.peek().is_some() => {
-            try!(write!(w, "<h2 class='fields'>Fields</h2>\n<table>"));
+            try!(write!(w, "<h2 id='fields' class='fields section-header'>\
+                            <a href='\#fields'>Fields</a></h2>\n<table>"));
             for field in fields {
                 try!(write!(w, "<tr><td id='structfield.{name}'>\
                                   <code>{name}</code></td><td>",
                               name = field.name.get_ref().as_slice()));
-                try!(document(w, field));
+                try!(document(w, field, 3));
                 try!(write!(w, "</td></tr>"));
             }
             try!(write!(w, "</table>"));
         }
         _ => {}
     }
-    render_methods(w, it)
+    render_methods(w, cx, it)
 }
 
-fn item_enum(w: &mut Writer, it: &clean::Item, e: &clean::Enum) -> fmt::Result {
+fn item_enum(w: &mut Writer, cx: &Context, it: &clean::Item,
+             e: &clean::Enum) -> fmt::Result {
     try!(write!(w, "<pre class='rust enum'>{}enum {}{}",
                   VisSpace(it.visibility),
                   it.name.get_ref().as_slice(),
@@ -1486,13 +2570,14 @@ fn item_enum(w: &mut Writer, it: &clean::Item, e: &clean::Enum) -> fmt::Result {
     }
     try!(write!(w, "</pre>"));
 
-    try!(document(w, it));
+    try!(document(w, it, 2));
     if e.variants.len() > 0 {
-        try!(write!(w, "<h2 class='variants'>Variants</h2>\n<table>"));
+        try!(write!(w, "<h2 id='variants' class='variants section-header'>\
+                        <a href='\#variants'>Variants</a></h2>\n<table>"));
         for variant in e.variants.iter() {
             try!(write!(w, "<tr><td id='variant.{name}'><code>{name}</code></td><td>",
                           name = variant.name.get_ref().as_slice()));
-            try!(document(w, variant));
+            try!(document(w, variant, 3));
             match variant.inner {
                 clean::VariantItem(ref var) => {
                     match var.kind {
@@ -1514,7 +2599,7 @@ fn item_enum(w: &mut Writer, it: &clean::Item, e: &clean::Enum) -> fmt::Result {
                                                   <code>{f}</code></td><td>",
                                               v = variant.name.get_ref().as_slice(),
                                               f = field.name.get_ref().as_slice()));
-                                try!(document(w, field));
+                                try!(document(w, field, 3));
                                 try!(write!(w, "</td></tr>"));
                             }
                             try!(write!(w, "</table>"));
@@ -1529,7 +2614,7 @@ fn item_enum(w: &mut Writer, it: &clean::Item, e: &clean::Enum) -> fmt::Result {
         try!(write!(w, "</table>"));
 
     }
-    try!(render_methods(w, it));
+    try!(render_methods(w, cx, it));
     Ok(())
 }
 
@@ -1597,7 +2682,7 @@ fn render_struct(w: &mut Writer, it: &clean::Item,
     Ok(())
 }
 
-fn render_methods(w: &mut Writer, it: &clean::Item) -> fmt::Result {
+fn render_methods(w: &mut Writer, cx: &Context, it: &clean::Item) -> fmt::Result {
     match cache_key.get().unwrap().impls.find(&it.id) {
         Some(v) => {
             let mut non_trait = v.iter().filter(|p| {
@@ -1609,29 +2694,33 @@ fn render_methods(w: &mut Writer, it: &clean::Item) -> fmt::Result {
             });
             let traits = traits.collect::<Vec<&(clean::Impl, Option<StrBuf>)>>();
 
+            // The anchors of the methods taken so far
+            let mut ids = HashSet::new();
             if non_trait.len() > 0 {
-                try!(write!(w, "<h2 id='methods'>Methods</h2>"));
+                try!(write!(w, "<h2 id='methods' class='section-header'>\
+                                <a href='\#methods'>Methods</a></h2>"));
                 for &(ref i, ref dox) in non_trait.move_iter() {
-                    try!(render_impl(w, i, dox));
+                    try!(render_impl(w, cx, i, dox, &mut ids));
                 }
             }
             if traits.len() > 0 {
-                try!(write!(w, "<h2 id='implementations'>Trait \
-                                  Implementations</h2>"));
+                try!(write!(w, "<h2 id='implementations' class='section-header'>\
+                                <a href='\#implementations'>Trait Implementations</a></h2>"));
                 let mut any_derived = false;
                 for & &(ref i, ref dox) in traits.iter() {
                     if !i.derived {
-                        try!(render_impl(w, i, dox));
+                        try!(render_impl(w, cx, i, dox, &mut ids));
                     } else {
                         any_derived = true;
                     }
                 }
                 if any_derived {
-                    try!(write!(w, "<h3 id='derived_implementations'>Derived Implementations \
-                                </h3>"));
+                    try!(write!(w, "<h3 id='derived_implementations' class='section-header'>\
+                                    <a href='\#derived_implementations'>Derived \
+                                    Implementations</a></h3>"));
                     for &(ref i, ref dox) in traits.move_iter() {
                         if i.derived {
-                            try!(render_impl(w, i, dox));
+                            try!(render_impl(w, cx, i, dox, &mut ids));
                         }
                     }
                 }
@@ -1642,37 +2731,91 @@ fn render_methods(w: &mut Writer, it: &clean::Item) -> fmt::Result {
     Ok(())
 }
 
-fn render_impl(w: &mut Writer, i: &clean::Impl,
-               dox: &Option<StrBuf>) -> fmt::Result {
-    try!(write!(w, "<h3 class='impl'><code>impl{} ", i.generics));
-    let trait_id = match i.trait_ {
-        Some(ref ty) => {
-            try!(write!(w, "{} for ", *ty));
-            match *ty {
-                clean::ResolvedPath { did, .. } => Some(did),
-                _ => None,
+/// Picks the anchor of a method of the impl `i` which stays the same when the
+/// page is rendered again: `method.name`, unless an impl earlier on the page
+/// took it, in which case the name of the trait implemented tells them apart,
+/// as in `Show.method.fmt`. Inherent impls come first, so their methods
+/// always get the plain anchor.
+fn method_id(ids: &mut HashSet<StrBuf>, i: &clean::Impl, name: &str) -> StrBuf {
+    let mut id = format_strbuf!("method.{}", name);
+    if ids.contains(&id) {
+        match i.trait_ {
+            Some(clean::ResolvedPath { ref path, .. }) => {
+                id = format_strbuf!("{}.{}", path.segments.last().unwrap().name, id);
             }
+            _ => {}
         }
-        None => None
-    };
-    try!(write!(w, "{}</code></h3>", i.for_));
+        // Traits implemented more than once, with different type parameters
+        let base = id.clone();
+        let mut n = 1;
+        while ids.contains(&id) {
+            id = format_strbuf!("{}-{}", base, n);
+            n += 1;
+        }
+    }
+    ids.insert(id.clone());
+    id
+}
+
+fn render_impl(w: &mut Writer, cx: &Context, i: &clean::Impl,
+               dox: &Option<StrBuf>, ids: &mut HashSet<StrBuf>) -> fmt::Result {
+    // If we've implemented a trait, then also emit documentation for all
+    // default methods which weren't overridden in the implementation block.
+    let cache = cache_key.get().unwrap();
+    let mut defaults = Vec::new();
+    match i.trait_ {
+        // FIXME: this should work for non-local traits
+        Some(clean::ResolvedPath { did, .. }) if ast_util::is_local(did) => {
+            match cache.traits.find(&did.node) {
+                Some(t) => {
+                    for method in t.methods.iter() {
+                        let n = method.item().name.clone();
+                        if !i.methods.iter().any(|m| m.name == n) {
+                            defaults.push(method.item());
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        _ => {}
+    }
+
+    // Impls of traits can be collapsed down to a summary of their methods,
+    // and start out collapsed
+    let count = i.methods.len() + defaults.len();
+    try!(write!(w, "<h3 class='impl collapsible{}'><code>impl{} ",
+                if i.trait_.is_some() { " trait-impl" } else { "" }, i.generics));
+    match i.trait_ {
+        Some(ref ty) => try!(write!(w, "{} for ", *ty)),
+        None => {}
+    }
+    try!(write!(w, "{}</code> <span class='impl-summary'>{} method{}</span>\
+                    <a href='javascript:void(0)' class='collapse-toggle js-only'>\
+                    [&minus;]</a></h3>",
+                i.for_, count, if count == 1 { "" } else { "s" }));
+    try!(write!(w, "<div class='impl-items'>"));
     match *dox {
         Some(ref dox) => {
             try!(write!(w, "<div class='docblock'>{}</div>",
-                          Markdown(dox.as_slice())));
+                          MarkdownAt(dox.as_slice(), 4)));
         }
         None => {}
     }
 
-    fn docmeth(w: &mut Writer, item: &clean::Item,
+    fn docmeth(w: &mut Writer, cx: &Context, item: &clean::Item, id: &str,
                dox: bool) -> io::IoResult<()> {
-        try!(write!(w, "<h4 id='method.{}' class='method'><code>",
-                      *item.name.get_ref()));
-        try!(render_method(w, item));
-        try!(write!(w, "</code></h4>\n"));
+        try!(write!(w, "<h4 id='{}' class='method'><code>", id));
+        try!(render_method(w, item, None));
+        try!(write!(w, "</code>{}", HiddenBadge(item)));
+        if cx.include_sources && !item.is_source_hidden() {
+            try!(write!(w, "<a class='source' href='{}'>[src]</a>",
+                        cx.src_href(&item.source)));
+        }
+        try!(write!(w, "</h4>\n"));
         match item.doc_value() {
             Some(s) if dox => {
-                try!(write!(w, "<div class='docblock'>{}</div>", Markdown(s)));
+                try!(write!(w, "<div class='docblock'>{}</div>", MarkdownAt(s, 5)));
                 Ok(())
             }
             Some(..) | None => Ok(())
@@ -1681,36 +2824,14 @@ fn render_impl(w: &mut Writer, i: &clean::Impl,
 
     try!(write!(w, "<div class='methods'>"));
     for meth in i.methods.iter() {
-        try!(docmeth(w, meth, true));
+        let id = method_id(ids, i, meth.name.get_ref().as_slice());
+        try!(docmeth(w, cx, meth, id.as_slice(), true));
     }
-
-    // If we've implemented a trait, then also emit documentation for all
-    // default methods which weren't overridden in the implementation block.
-    match trait_id {
-        None => {}
-        // FIXME: this should work for non-local traits
-        Some(did) if ast_util::is_local(did) => {
-            try!({
-                match cache_key.get().unwrap().traits.find(&did.node) {
-                    Some(t) => {
-                        for method in t.methods.iter() {
-                            let n = method.item().name.clone();
-                            match i.methods.iter().find(|m| m.name == n) {
-                                Some(..) => continue,
-                                None => {}
-                            }
-
-                            try!(docmeth(w, method.item(), false));
-                        }
-                    }
-                    None => {}
-                }
-                Ok(())
-            })
-        }
-        Some(..) => {}
+    for meth in defaults.iter() {
+        let id = method_id(ids, i, meth.name.get_ref().as_slice());
+        try!(docmeth(w, cx, *meth, id.as_slice(), false));
     }
-    try!(write!(w, "</div>"));
+    try!(write!(w, "</div></div>"));
     Ok(())
 }
 
@@ -1721,7 +2842,7 @@ fn item_typedef(w: &mut Writer, it: &clean::Item,
                   t.generics,
                   t.type_));
 
-    document(w, it)
+    document(w, it, 2)
 }
 
 impl<'a> fmt::Show for Sidebar<'a> {
@@ -1729,58 +2850,117 @@ impl<'a> fmt::Show for Sidebar<'a> {
         let cx = self.cx;
         let it = self.item;
         try!(write!(fmt.buf, "<p class='location'>"));
+        // The sidebar of a module lists the items of the module it's in
         let len = cx.current.len() - if it.is_mod() {1} else {0};
-        for (i, name) in cx.current.iter().take(len).enumerate() {
+        let module = cx.current.slice_to(len);
+        for (i, name) in module.iter().enumerate() {
             if i > 0 {
                 try!(write!(fmt.buf, "&\\#8203;::"));
             }
-            try!(write!(fmt.buf, "<a href='{}index.html'>{}</a>",
-                          cx.root_path
-                            .as_slice()
-                            .slice_to((cx.current.len() - i - 1) * 3),
+            try!(write!(fmt.buf, "<a href='{}{}'>{}</a>",
+                          cx.root_path,
+                          module_page(cx.layout.page_layout, module.slice_to(i + 1)),
                           *name));
         }
         try!(write!(fmt.buf, "</p>"));
+        try!(write!(fmt.buf, "<p class='all-items'><a href='{root}{krate}/all.html'>\
+                              All items</a> &middot; \
+                              <a href='{root}{krate}/stability.html'>Stability</a></p>",
+                    root = cx.root_path, krate = cx.layout.krate));
+
+        // Long docs get a table of contents of their sections
+        match it.doc_value() {
+            Some(doc) => {
+                let toc = markdown::toc(doc);
+                if toc.num_entries() > 1 {
+                    try!(write!(fmt.buf, "<div class='block toc'><h2>Contents</h2>{}</div>",
+                                toc));
+                }
+            }
+            None => {}
+        }
 
-        fn block(w: &mut Writer, short: &str, longty: &str,
-                 cur: &clean::Item, cx: &Context) -> fmt::Result {
+        fn block(w: &mut Writer, short: &str, longty: &str, cur: &clean::Item,
+                 module: &[StrBuf], cx: &Context) -> fmt::Result {
             let items = match cx.sidebar.find_equiv(&short) {
                 Some(items) => items.as_slice(),
                 None => return Ok(())
             };
+            let layout = cx.layout.page_layout;
             try!(write!(w, "<div class='block {}'><h2>{}</h2>", short, longty));
             for item in items.iter() {
                 let curty = shortty(cur).to_static_str();
                 let class = if cur.name.get_ref() == item &&
                                short == curty { "current" } else { "" };
-                try!(write!(w, "<a class='{ty} {class}' href='{curty, select,
-                                mod{../}
-                                other{}
-                           }{tysel, select,
-                                mod{{name}/index.html}
-                                other{#.{name}.html}
-                           }'>{name}</a><br/>",
+                let page = if short == "mod" {
+                    let mut fqp = Vec::from_slice(module);
+                    fqp.push(item.clone());
+                    module_page(layout, fqp.as_slice())
+                } else {
+                    item_page(layout, module, short, item.as_slice())
+                };
+                try!(write!(w, "<a class='{ty} {class}' href='{root}{page}'>{name}</a><br/>",
                        ty = short,
-                       tysel = short,
                        class = class,
-                       curty = curty,
+                       root = cx.root_path,
+                       page = page,
                        name = item.as_slice()));
             }
             try!(write!(w, "</div>"));
             Ok(())
         }
 
-        try!(block(fmt.buf, "mod", "Modules", it, cx));
-        try!(block(fmt.buf, "struct", "Structs", it, cx));
-        try!(block(fmt.buf, "enum", "Enums", it, cx));
-        try!(block(fmt.buf, "trait", "Traits", it, cx));
-        try!(block(fmt.buf, "fn", "Functions", it, cx));
-        try!(block(fmt.buf, "macro", "Macros", it, cx));
+        for &(short, longty) in SIDEBAR_SECTIONS.iter() {
+            try!(block(fmt.buf, short, longty, it, module, cx));
+        }
+
+        // The root page of a crate also lists the crates it links to, marking
+        // which are documented alongside it and which elsewhere
+        if it.is_mod() && cx.current.len() == 1 && cx.externs.len() > 0 {
+            let cache = cache_key.get().unwrap();
+            try!(write!(fmt.buf, "<div class='block crates'><h2>Dependencies</h2>"));
+            for &(n, ref name) in cx.externs.iter() {
+                match cache.extern_locations.find(&n) {
+                    Some(&Remote(ref s)) => {
+                        try!(write!(fmt.buf, "<a class='dep remote' href='{}{}/index.html' \
+                                              title='Documented at {}'>{}</a> \
+                                              <span class='dep-location'>remote</span><br/>",
+                                    *s, *name, *s, *name));
+                    }
+                    Some(&Local) => {
+                        try!(write!(fmt.buf, "<a class='dep local' href='{}{}/index.html' \
+                                              title='Documented locally'>{}</a><br/>",
+                                    cx.root_path, *name, *name));
+                    }
+                    Some(&Unknown) | None => {
+                        try!(write!(fmt.buf, "<span class='dep unknown' \
+                                              title='No documentation found'>{}</span><br/>",
+                                    *name));
+                    }
+                }
+            }
+            try!(write!(fmt.buf, "</div>"));
+        }
         Ok(())
     }
 }
 
-fn build_sidebar(m: &clean::Module) -> HashMap<StrBuf, Vec<StrBuf> > {
+/// The sections of the sidebar, in order, keyed by the short name of the
+/// type of the items in each. Enums share their short name with type
+/// definitions.
+static SIDEBAR_SECTIONS: &'static [(&'static str, &'static str)] = &[
+    ("mod", "Modules"),
+    ("struct", "Structs"),
+    ("type", "Types"),
+    ("trait", "Traits"),
+    ("fn", "Functions"),
+    ("static", "Statics"),
+    ("ffi", "Foreign Functions"),
+    ("ffs", "Foreign Statics"),
+    ("macro", "Macros"),
+];
+
+fn build_sidebar(m: &clean::Module, order: &ItemOrder) -> HashMap<StrBuf, Vec<StrBuf> > {
     let mut map = HashMap::new();
     for item in m.items.iter() {
         let short = shortty(item).to_static_str();
@@ -1792,15 +2972,17 @@ fn build_sidebar(m: &clean::Module) -> HashMap<StrBuf, Vec<StrBuf> > {
         v.push(myname);
     }
 
-    for (_, items) in map.mut_iter() {
-        items.as_mut_slice().sort();
+    if *order == Alphabetical {
+        for (_, items) in map.mut_iter() {
+            items.as_mut_slice().sort();
+        }
     }
     return map;
 }
 
 impl<'a> fmt::Show for Source<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let Source(s) = *self;
+        let s = self.contents;
         let lines = s.lines().len();
         let mut cols = 0;
         let mut tmp = lines;
@@ -1813,7 +2995,12 @@ impl<'a> fmt::Show for Source<'a> {
             try!(write!(fmt.buf, "<span id='{0:u}'>{0:1$u}</span>\n", i, cols));
         }
         try!(write!(fmt.buf, "</pre>"));
-        try!(write!(fmt.buf, "{}", highlight::highlight(s.as_slice(), None)));
+        let defs = self.links.defs.find_equiv(&self.file);
+        try!(write!(fmt.buf, "{}", highlight::highlight_linked(s.as_slice(), None, |line, name| {
+            defs.and_then(|defs| defs.find(&(line, name.to_strbuf()))).map(|path| {
+                format_strbuf!("{}{}", self.root_path, *path)
+            })
+        })));
         Ok(())
     }
 }
@@ -1822,5 +3009,105 @@ fn item_macro(w: &mut Writer, it: &clean::Item,
               t: &clean::Macro) -> fmt::Result {
     try!(w.write_str(highlight::highlight(t.source.as_slice(),
                                           Some("macro")).as_slice()));
-    document(w, it)
+    document(w, it, 2)
+}
+
+#[cfg(test)]
+mod test {
+    use syntax::ast;
+    use syntax::ast_util;
+
+    use clean;
+    use fold::DocFolder;
+    use renderer::{SourceOrder, Nested};
+    use super::{build_cache, item_pages, search_shard, search_index_shards};
+
+    fn module(name: &str, id: ast::NodeId, visibility: ast::Visibility,
+              items: Vec<clean::Item>) -> clean::Item {
+        clean::Item {
+            source: clean::Span {
+                filename: "lib.rs".to_strbuf(),
+                loline: 1, locol: 0, hiline: 1, hicol: 0,
+            },
+            name: Some(name.to_strbuf()),
+            attrs: vec!(clean::NameValue("doc".to_strbuf(), "Docs".to_strbuf())),
+            inner: clean::ModuleItem(clean::Module { items: items, is_crate: false }),
+            visibility: Some(visibility),
+            id: id,
+        }
+    }
+
+    /// The number of items left in the crate root once the cache is done
+    /// with `krate`.
+    fn kept(krate: &clean::Crate, document_private: bool) -> uint {
+        let mut cache = build_cache(krate, &Path::new("."), document_private);
+        match cache.fold_crate(krate.clone()).module {
+            Some(clean::Item { inner: clean::ModuleItem(ref m), .. }) => m.items.len(),
+            _ => fail!("expected a module"),
+        }
+    }
+
+    #[test]
+    fn private_modules() {
+        let inner = module("n", 2, ast::Public, Vec::new());
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", 0, ast::Public, vec!(
+                module("m", 1, ast::Inherited, vec!(inner))))),
+            externs: Vec::new(),
+        };
+        let cache = build_cache(&krate, &Path::new("."), true);
+        assert!(cache.private_items.contains(&2));
+        assert_eq!(cache.paths.find(&ast_util::local_def(2)).map(|p| p.ref0().clone()),
+                   Some(vec!("krate".to_strbuf(), "m".to_strbuf(), "n".to_strbuf())));
+        assert_eq!(kept(&krate, true), 1);
+        assert_eq!(kept(&krate, false), 0);
+    }
+    #[test]
+    fn search_shards() {
+        assert_eq!(search_shard("Vec"), 'v');
+        assert_eq!(search_shard("u8"), 'u');
+        assert_eq!(search_shard("2d"), '2');
+        assert_eq!(search_shard("_private"), '_');
+        assert_eq!(search_shard("\u00e9t\u00e9"), '_');
+
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", 0, ast::Public, vec!(
+                module("beta", 1, ast::Public, Vec::new()),
+                module("Alpha", 2, ast::Public, Vec::new()),
+                module("alpine", 3, ast::Public, Vec::new())))),
+            externs: Vec::new(),
+        };
+        let cache = build_cache(&krate, &Path::new("."), false);
+        let shards = search_index_shards("krate", &cache, SourceOrder).unwrap();
+        assert_eq!(shards.iter().map(|&(shard, _)| shard).collect::<Vec<char>>(),
+                   vec!('a', 'b', 'k'));
+        let a = shards.get(0).ref1().as_slice();
+        assert!(a.starts_with("addSearchIndex('krate', 'a', {\"t\":[0,0],"));
+        assert!(a.contains("\"n\":[\"Alpha\",\"alpine\"]"));
+        assert!(!a.contains("beta"));
+    }
+
+    #[test]
+    fn sitemap_pages() {
+        let statik = clean::Item {
+            name: Some("S".to_strbuf()),
+            inner: clean::StaticItem(clean::Static {
+                type_: clean::Primitive(ast::TyBool),
+                mutability: clean::Immutable,
+                expr: "true".to_strbuf(),
+            }),
+            id: 2,
+            .. module("S", 2, ast::Public, Vec::new())
+        };
+        let m = clean::Module {
+            items: vec!(module("m", 1, ast::Public, Vec::new()), statik),
+            is_crate: false,
+        };
+        let mut pages = Vec::new();
+        item_pages(Nested, &m, &mut vec!("krate".to_strbuf()), &mut pages);
+        assert_eq!(pages, vec!("krate/index.html".to_strbuf(),
+                               "krate/m/index.html".to_strbuf()));
+    }
 }
@@ -0,0 +1,355 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Single-page HTML rendering backend
+//!
+//! Renders the documentation of a whole crate into one self-contained HTML
+//! file. The stylesheets are inlined into the page, fonts and all, and every
+//! item becomes a section of the page instead of a page of its own, with
+//! links between items pointing at the anchors of those sections. Nothing
+//! else is required to view the page, which makes it easy to email, archive,
+//! or convert to PDF.
+//!
+//! Signatures are rendered as plain text, as the pretty-printers in
+//! `html::format` link to the pages of the multi-page output.
+
+use std::io;
+use std::io::BufferedWriter;
+use std::strbuf::StrBuf;
+use serialize::base64::{ToBase64, STANDARD};
+use sync::Arc;
+
+use clean;
+use fold::DocFolder;
+use html::escape::Escape;
+use html::item_type;
+use html::item_type::{ItemType, shortty};
+use html::markdown;
+use html::markdown::{Markdown, MarkdownAt};
+use html::render::{Cache, HoardStripper, cache_key, current_location_key};
+use html::render;
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header, kind_name};
+
+/// Renders a crate as a single self-contained HTML page.
+pub struct SinglePageRenderer;
+
+impl Renderer for SinglePageRenderer {
+    fn name(&self) -> &'static str { "single-html" }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("doc.html") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// The order in which groups of items are listed in a module, along with the
+/// heading of each group.
+static SECTIONS: &'static [(ItemType, &'static str)] = &[
+    (item_type::Module, "Modules"),
+    (item_type::Macro, "Macros"),
+    (item_type::Struct, "Structs"),
+    (item_type::Enum, "Enums"),
+    (item_type::Static, "Statics"),
+    (item_type::ForeignFunction, "Foreign Functions"),
+    (item_type::ForeignStatic, "Foreign Statics"),
+    (item_type::Trait, "Traits"),
+    (item_type::Function, "Functions"),
+    (item_type::Typedef, "Type Definitions"),
+];
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink) -> io::IoResult<()> {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut item = match krate.module {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    item.name = Some(krate.name.clone());
+
+    // Headers in docs all share one page, so they're only reset once
+    markdown::reset_headers();
+
+    let mut w = BufferedWriter::new(try!(sink.create(&opts.dest)));
+    try!(write!(&mut w,
+r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <meta name="description" content="The {krate} library documentation.">
+
+    <title>{krate} - Rust</title>
+
+    <style type="text/css">{normalize}</style>
+    <style type="text/css">{main}</style>
+    {in_header}
+</head>
+<body>
+    {before_content}
+
+    <section id='main' class="content single-page">
+"##,
+        krate = krate.name,
+        normalize = include_str!("static/normalize.css"),
+        main = main_css(),
+        in_header = opts.external_html.in_header,
+        before_content = opts.external_html.before_content));
+
+    let mut current = Vec::new();
+    try!(write!(&mut w, "<nav class='toc'><h1>Contents</h1>"));
+    try!(contents(&mut w, &mut current, &item));
+    try!(write!(&mut w, "</nav>"));
+    let mut progress = (0, renderer::count_items(&item));
+    try!(module(&mut w, &mut current, &item, false, opts, &mut progress));
+
+    try!(write!(&mut w,
+r##"    </section>
+
+    {after_content}
+</body>
+</html>"##,
+        after_content = opts.external_html.after_content));
+    w.flush()
+}
+
+/// The fonts `main.css` loads, by the name of their file.
+static FONTS: &'static [(&'static str, &'static [u8])] = &[
+    ("FiraSans-Regular.woff", include_bin!("static/FiraSans-Regular.woff")),
+    ("FiraSans-Medium.woff", include_bin!("static/FiraSans-Medium.woff")),
+    ("Heuristica-Regular.woff", include_bin!("static/Heuristica-Regular.woff")),
+    ("Heuristica-Italic.woff", include_bin!("static/Heuristica-Italic.woff")),
+    ("Heuristica-Bold.woff", include_bin!("static/Heuristica-Bold.woff")),
+];
+
+/// `main.css` with the fonts it loads embedded as `data:` URIs, as there are
+/// no files next to the page to load them from.
+fn main_css() -> StrBuf {
+    let mut css = include_str!("static/main.css").to_owned();
+    for &(file, font) in FONTS.iter() {
+        let url = format!("url(\"{}\")", file);
+        let data = format!("url(\"data:application/font-woff;base64,{}\")",
+                           font.to_base64(STANDARD));
+        css = css.replace(url.as_slice(), data.as_slice());
+    }
+    css.into_strbuf()
+}
+
+/// The id of the section documenting the item at `path`.
+fn anchor(ty: ItemType, path: &[StrBuf]) -> StrBuf {
+    format_strbuf!("{}.{}", ty, path.connect("::"))
+}
+
+/// Returns the items of a module in the order they're documented in, along
+/// with the heading of the group each is in.
+fn grouped<'a>(m: &'a clean::Module) -> Vec<(&'static str, Vec<&'a clean::Item>)> {
+    SECTIONS.iter().filter_map(|&(ty, header)| {
+        let mut items = m.items.iter().filter(|i| shortty(*i) == ty)
+                               .collect::<Vec<&clean::Item>>();
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        if items.len() == 0 { None } else { Some((header, items)) }
+    }).collect()
+}
+
+/// Writes the table of contents for a module and all modules within it.
+fn contents(w: &mut Writer, current: &mut Vec<StrBuf>,
+            item: &clean::Item) -> io::IoResult<()> {
+    let m = match item.inner {
+        clean::ModuleItem(ref m) => m,
+        _ => return Ok(()),
+    };
+    current.push(item.name.get_ref().clone());
+    try!(write!(w, "<ul><li><a href='\\#{}'>{}</a>",
+                anchor(item_type::Module, current.as_slice()),
+                Escape(item.name.get_ref().as_slice())));
+    for (_, items) in grouped(m).move_iter() {
+        for it in items.iter() {
+            if it.is_mod() {
+                try!(contents(w, current, *it));
+            }
+        }
+    }
+    try!(write!(w, "</li></ul>"));
+    current.pop();
+    Ok(())
+}
+
+/// Writes the body of the page documenting the module `item` and everything
+/// in it except for its submodules, for backends which split a crate into
+/// one page per module. Submodules are linked to as `chapter_file` names.
+/// `progress` is the number of items done so far and the total, which is
+/// reported to `opts`.
+pub fn chapter(w: &mut Writer, current: &mut Vec<StrBuf>, item: &clean::Item,
+               opts: &RenderOptions, progress: &mut (uint, uint)) -> io::IoResult<()> {
+    module(w, current, item, true, opts, progress)
+}
+
+/// The name of the page a module at `path` is rendered to by `chapter`.
+pub fn chapter_file(path: &[StrBuf]) -> StrBuf {
+    format_strbuf!("{}.xhtml", path.connect("."))
+}
+
+/// Writes the section for a module, followed by the sections of everything
+/// inside of it. If `split`, the submodules are linked to instead.
+fn module(w: &mut Writer, current: &mut Vec<StrBuf>, item: &clean::Item, split: bool,
+          opts: &RenderOptions, progress: &mut (uint, uint)) -> io::IoResult<()> {
+    let m = match item.inner {
+        clean::ModuleItem(ref m) => m,
+        _ => return Ok(()),
+    };
+    current.push(item.name.get_ref().clone());
+    current_location_key.replace(Some(current.clone()));
+    let groups = grouped(m);
+
+    try!(write!(w, "<h1 id='{id}' class='fqn'>{kind} <a href='\\#{id}'>{name}</a></h1>",
+                id = anchor(item_type::Module, current.as_slice()),
+                kind = kind_name(item),
+                name = Escape(current.connect("::").as_slice())));
+    try!(document(w, item, 2));
+
+    for &(header, ref items) in groups.iter() {
+        try!(write!(w, "<h2 class='section-header'>{}</h2>\n<table>", header));
+        for it in items.iter() {
+            let mut path = current.clone();
+            path.push(it.name.get_ref().clone());
+            let href = if split && it.is_mod() {
+                chapter_file(path.as_slice())
+            } else {
+                format_strbuf!("\\#{}", anchor(shortty(*it), path.as_slice()))
+            };
+            try!(write!(w, "<tr><td><a class='{ty}' href='{href}'>{name}</a></td>\
+                            <td class='docblock short'>{summary}</td></tr>",
+                        ty = shortty(*it),
+                        href = href,
+                        name = Escape(it.name.get_ref().as_slice()),
+                        summary = Markdown(render::shorter(it.doc_value()))));
+        }
+        try!(write!(w, "</table>"));
+    }
+
+    for &(_, ref items) in groups.iter() {
+        for it in items.iter() {
+            if !it.is_mod() {
+                try!(entry(w, current.as_slice(), *it));
+            }
+        }
+    }
+
+    // The section covers the module and all of its items but its submodules
+    let (done, total) = *progress;
+    let done = done + 1 + m.items.iter().filter(|i| !i.is_mod()).count();
+    *progress = (done, total);
+    try!(opts.tick(done, total));
+
+    if !split {
+        for &(_, ref items) in groups.iter() {
+            for it in items.iter() {
+                if it.is_mod() {
+                    try!(module(w, current, *it, false, opts, progress));
+                }
+            }
+        }
+    }
+    current.pop();
+    Ok(())
+}
+
+/// Writes the section for an item which isn't a module.
+fn entry(w: &mut Writer, current: &[StrBuf],
+         item: &clean::Item) -> io::IoResult<()> {
+    current_location_key.replace(Some(Vec::from_slice(current)));
+    let mut path = Vec::from_slice(current);
+    path.push(item.name.get_ref().clone());
+    try!(write!(w, "<h1 id='{id}' class='fqn'>{kind} <a href='\\#{id}'>{name}</a></h1>",
+                id = anchor(shortty(item), path.as_slice()),
+                kind = kind_name(item),
+                name = Escape(path.connect("::").as_slice())));
+    try!(documented(w, item, 2));
+
+    let members = match item.inner {
+        clean::StructItem(ref s) => Some(("Fields", s.fields.as_slice())),
+        clean::EnumItem(ref e) => Some(("Variants", e.variants.as_slice())),
+        _ => None,
+    };
+    match members {
+        Some((header, members)) if members.len() > 0 => {
+            try!(write!(w, "<h2 class='fields'>{}</h2>", header));
+            for m in members.iter() {
+                try!(member(w, m, 3));
+            }
+        }
+        Some(..) | None => {}
+    }
+
+    match item.inner {
+        clean::TraitItem(ref t) if t.methods.len() > 0 => {
+            try!(write!(w, "<h2>Methods</h2>"));
+            for m in t.methods.iter() {
+                try!(member(w, m.item(), 3));
+            }
+        }
+        _ => {}
+    }
+
+    let cache = cache_key.get().unwrap();
+    match cache.impls.find(&item.id) {
+        Some(impls) if impls.len() > 0 => {
+            try!(write!(w, "<h2 id='implementations'>Implementations</h2>"));
+            for &(ref i, ref dox) in impls.iter() {
+                try!(write!(w, "<h3 class='impl'><code>{}</code></h3>",
+                            Escape(impl_header(i).as_slice())));
+                match *dox {
+                    Some(ref dox) => {
+                        try!(write!(w, "<div class='docblock'>{}</div>",
+                                    MarkdownAt(dox.as_slice(), 4)));
+                    }
+                    None => {}
+                }
+                for m in i.methods.iter() {
+                    try!(member(w, m, 4));
+                }
+            }
+        }
+        Some(..) | None => {}
+    }
+    Ok(())
+}
+
+/// Writes a field, variant or method of an item, with the headers of its docs
+/// at `level`.
+fn member(w: &mut Writer, item: &clean::Item, level: u32) -> io::IoResult<()> {
+    if signature(item).is_none() { return Ok(()) }
+    try!(write!(w, "<div class='method'>"));
+    try!(documented(w, item, level));
+    write!(w, "</div>")
+}
+
+/// Writes an item's signature followed by its docs.
+fn documented(w: &mut Writer, item: &clean::Item, level: u32) -> io::IoResult<()> {
+    match signature(item) {
+        Some(sig) => {
+            try!(write!(w, "<pre class='rust'>{}</pre>", Escape(sig.as_slice())));
+        }
+        None => {}
+    }
+    document(w, item, level)
+}
+
+fn document(w: &mut Writer, item: &clean::Item, level: u32) -> io::IoResult<()> {
+    match item.doc_value() {
+        Some(s) => write!(w, "<div class='docblock'>{}</div>", MarkdownAt(s, level)),
+        None => Ok(())
+    }
+}
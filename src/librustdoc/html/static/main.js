@@ -47,6 +47,20 @@
     resizeShortBlocks();
     $(window).on('resize', resizeShortBlocks);
 
+    $('.collapse-toggle').on('click', function() {
+        var $header = $(this).closest('.collapsible');
+        $header.toggleClass('collapsed');
+        $header.next().toggleClass('hidden');
+        $(this).html($header.hasClass('collapsed') ? '[+]' : '[&minus;]');
+    });
+    $('.trait-impl .collapse-toggle, .inline-source .collapse-toggle').click();
+    // Don't hide a method which was linked to
+    if (window.location.hash.length > 1) {
+        $(document.getElementById(window.location.hash.substring(1)))
+            .closest('.impl-items').prev('.collapsed')
+            .find('.collapse-toggle').click();
+    }
+
     function highlightSourceLines() {
         var i, from, to, match = window.location.hash.match(/^#?(\d+)(?:-(\d+))?$/);
         if (match) {
@@ -93,6 +107,46 @@
         }
     });
 
+    function switchTheme(name) {
+        // Themes stored by the docs of another crate may not exist here
+        if ($('.theme-picker option[value="' + name + '"]').length === 0) {
+            return;
+        }
+        $('#themeStyle').attr('href', rootPath + name + '.css');
+        $('meta[name="color-scheme"]').attr('content', name === 'auto' ? 'light dark' :
+                                            name === 'dark' ? 'dark' : 'light');
+        $('.theme-picker').val(name);
+    }
+    try {
+        if (window.localStorage && localStorage.getItem('rustdoc-theme')) {
+            switchTheme(localStorage.getItem('rustdoc-theme'));
+        }
+    } catch (e) {}
+
+    $('.theme-picker').on('change', function() {
+        var name = $(this).val();
+        switchTheme(name);
+        try {
+            localStorage.setItem('rustdoc-theme', name);
+        } catch (e) {}
+    });
+
+    // Offer to switch to the other versions of the crate listed in the
+    // manifest, if there are any
+    if (window.currentVersion) {
+        $.ajax({url: rootPath + 'versions.json', dataType: 'json'}).done(function(versions) {
+            var list = versions[window.currentCrate];
+            if (!list || list.length < 2) {
+                return;
+            }
+            var $selector = $('.version-selector');
+            for (var i = 0; i < list.length; i++) {
+                $selector.append($('<option>').attr('value', list[i]).text(list[i]));
+            }
+            $selector.val(window.currentVersion).removeClass('hidden');
+        });
+    }
+
     $('.version-selector').on('change', function() {
         var i, match,
             url = document.location.href,
@@ -112,8 +166,15 @@
         document.location.href = url;
     });
 
-    function initSearch(rawSearchIndex) {
-        var currentResults, index, searchIndex;
+    function initSearch(crates) {
+        var currentResults, index = [], searchIndex = [];
+        // The shards of the search index loaded so far, by crate and initial.
+        // Shards are only fetched once a search is about to happen, as
+        // parsing them all would stall every page of large crates.
+        var rawSearchIndex = {}, loadedChunks = 0, loadingChunks = false;
+        // The number of edits a name may be away from the query and still be
+        // found when it doesn't contain the query, to forgive typos.
+        var MAX_LEV_DISTANCE = 2;
         var params = getQueryStringParams();
 
         // Populate search bar with query string search term when provided,
@@ -139,6 +200,10 @@
                 results = [],
                 split = valLower.split("::");
 
+            if (query.sig) {
+                return execSigQuery(query.sig, max, searchWords);
+            }
+
             //remove empty keywords
             for (var j = 0; j < split.length; j++) {
                 split[j].toLowerCase();
@@ -167,6 +232,7 @@
             } else {
                 // gather matching search results up to a certain maximum
                 val = val.replace(/\_/g, "");
+                var matched = {};
                 for (var i = 0; i < split.length; i++) {
                     for (var j = 0; j < nSearchWords; j += 1) {
                         if (searchWords[j].indexOf(split[i]) > -1 ||
@@ -175,7 +241,9 @@
                         {
                             // filter type: ... queries
                             if (typeFilter < 0 || typeFilter === searchIndex[j].ty) {
-                                results.push({id: j, index: searchWords[j].replace(/_/g, "").indexOf(val)});
+                                results.push({id: j, index: searchWords[j].replace(/_/g, "").indexOf(val),
+                                              lev: 0});
+                                matched[j] = true;
                             }
                         }
                         if (results.length === max) {
@@ -183,6 +251,38 @@
                         }
                     }
                 }
+
+                // items may also be found by one of their aliases matching
+                // the last keyword exactly
+                var last = split[split.length - 1] || "";
+                for (var j = 0; j < nSearchWords && results.length < max; j += 1) {
+                    var aliases = searchIndex[j].aliases;
+                    if (matched[j] || !aliases || aliases.indexOf(last) < 0) {
+                        continue;
+                    }
+                    if (typeFilter < 0 || typeFilter === searchIndex[j].ty) {
+                        results.push({id: j, index: -1, lev: 0, alias: true});
+                        matched[j] = true;
+                    }
+                }
+
+                // names which don't contain the last keyword may still be a
+                // few typos away from it
+                if (last.length > MAX_LEV_DISTANCE) {
+                    for (var j = 0; j < nSearchWords && results.length < max; j += 1) {
+                        if (matched[j] ||
+                            Math.abs(searchWords[j].length - last.length) > MAX_LEV_DISTANCE)
+                        {
+                            continue;
+                        }
+                        var lev = levenshtein(searchWords[j], last);
+                        if (lev <= MAX_LEV_DISTANCE &&
+                            (typeFilter < 0 || typeFilter === searchIndex[j].ty))
+                        {
+                            results.push({id: j, index: -1, lev: lev});
+                        }
+                    }
+                }
             }
 
             var nresults = results.length;
@@ -198,14 +298,20 @@
             results.sort(function(aaa, bbb) {
                 var a, b;
 
+                // sort by distance from the query (typos go later)
+                a = aaa.lev || 0;
+                b = bbb.lev || 0;
+                if (a !== b) return a - b;
+
                 // sort by crate (non-current crate goes later)
                 a = (aaa.item.crate !== window.currentCrate);
                 b = (bbb.item.crate !== window.currentCrate);
                 if (a !== b) return a - b;
 
-                // sort by exact match (mismatch goes later)
-                a = (aaa.word !== valLower);
-                b = (bbb.word !== valLower);
+                // sort by exact match (mismatch goes later), which an alias
+                // match is as good as
+                a = (aaa.word !== valLower && !aaa.alias);
+                b = (bbb.word !== valLower && !bbb.alias);
                 if (a !== b) return a - b;
 
                 // sort by item name length (longer goes later)
@@ -261,7 +367,11 @@
                     path = result.item.path.toLowerCase(),
                     parent = result.item.parent;
 
-                var valid = validateResult(name, path, split, parent);
+                // the last keyword of a typo or alias match is already known
+                // to be close to its name, or one of its aliases
+                var keys = result.lev > 0 || result.alias ?
+                    split.slice(0, split.length - 1) : split;
+                var valid = validateResult(name, path, keys, parent);
                 if (!valid) {
                     result.id = -1;
                 }
@@ -269,6 +379,186 @@
             return results;
         }
 
+        /**
+         * Finds the items whose signature matches a `fn(A, B) -> C` query.
+         * Each type of the query must be the same as that of the item in the
+         * same position, except for `_` which matches any type. Leaving out
+         * the return type matches any return type.
+         * @param  {[Object]} sig       [The types of the query, as returned
+         *                               by `parseSig`]
+         * @param  {[type]} max         [The maximum results returned]
+         * @param  {[type]} searchWords [The list of search words]
+         * @return {[type]}             [A search index of results]
+         */
+        function execSigQuery(sig, max, searchWords) {
+            var results = [];
+
+            function matches(want, have) {
+                return want === "_" || want === have;
+            }
+
+            for (var i = 0; i < searchIndex.length && results.length < max; i += 1) {
+                var item = searchIndex[i];
+                if (!item.sig || item.sig.inputs.length !== sig.inputs.length) {
+                    continue;
+                }
+                var valid = true;
+                for (var j = 0; j < sig.inputs.length; j++) {
+                    if (!matches(sig.inputs[j], item.sig.inputs[j])) {
+                        valid = false;
+                        break;
+                    }
+                }
+                if (valid && sig.output !== undefined) {
+                    valid = matches(sig.output, item.sig.output || "()");
+                }
+                if (valid) {
+                    results.push({id: i, index: -1, lev: 0, word: searchWords[i],
+                                  item: item});
+                }
+            }
+
+            results.sort(function(aaa, bbb) {
+                var a, b;
+
+                // sort by crate (non-current crate goes later)
+                a = (aaa.item.crate !== window.currentCrate);
+                b = (bbb.item.crate !== window.currentCrate);
+                if (a !== b) return a - b;
+
+                // sort by item name (lexicographically larger goes later)
+                a = aaa.word;
+                b = bbb.word;
+                if (a !== b) return (a > b ? +1 : -1);
+
+                // sort by path (lexicographically larger goes later)
+                a = aaa.item.path;
+                b = bbb.item.path;
+                if (a !== b) return (a > b ? +1 : -1);
+
+                return 0;
+            });
+            return results;
+        }
+
+        /**
+         * The number of single character insertions, deletions and
+         * substitutions it takes to turn one string into the other.
+         */
+        function levenshtein(s1, s2) {
+            var row = [];
+            for (var j = 0; j <= s2.length; j++) {
+                row.push(j);
+            }
+            for (var i = 1; i <= s1.length; i++) {
+                var diag = row[0];
+                row[0] = i;
+                for (var j = 1; j <= s2.length; j++) {
+                    var above = row[j];
+                    if (s1.charAt(i - 1) === s2.charAt(j - 1)) {
+                        row[j] = diag;
+                    } else {
+                        row[j] = Math.min(diag, above, row[j - 1]) + 1;
+                    }
+                    diag = above;
+                }
+            }
+            return row[s2.length];
+        }
+
+        /**
+         * Reduces a type written in a query to the name it's searched for by,
+         * the same way rustdoc reduces the types in signatures: pointers,
+         * vectors, type arguments and module paths are dropped, so
+         * `&[Vec<u8>]` becomes `vec`.
+         */
+        function normalizeType(ty) {
+            var prev;
+            ty = ty.toLowerCase().replace(/^\s+|\s+$/g, "");
+            do {
+                prev = ty;
+                ty = ty.replace(/^(&|~|@|\*)\s*/, "")
+                       .replace(/^'\w+\s+/, "")
+                       .replace(/^(mut|const)\s+/, "")
+                       .replace(/^\[(.*?)(,\s*\.\.\s*\w+)?\]$/, "$1")
+                       .replace(/^\s+|\s+$/g, "");
+            } while (ty !== prev);
+            if (ty === "") {
+                return "_";
+            }
+            if (ty === "()") {
+                return ty;
+            }
+            if (ty.charAt(0) === "(") {
+                return "tuple";
+            }
+            if (ty.charAt(0) === "|" || /^(fn|proc)\b/.test(ty) ||
+                /^extern\b/.test(ty))
+            {
+                return "fn";
+            }
+            ty = ty.replace(/<.*$/, "");
+            var sep = ty.lastIndexOf("::");
+            return sep < 0 ? ty : ty.substring(sep + 2);
+        }
+
+        /**
+         * Splits a list of types at the commas which aren't nested inside
+         * other types.
+         */
+        function splitTypes(list) {
+            var types = [], depth = 0, start = 0;
+            for (var i = 0; i < list.length; i++) {
+                var c = list.charAt(i);
+                if (c === "<" || c === "(" || c === "[") {
+                    depth += 1;
+                } else if ((c === ">" && list.charAt(i - 1) !== "-") ||
+                           c === ")" || c === "]") {
+                    depth -= 1;
+                } else if (c === "," && depth === 0) {
+                    types.push(list.substring(start, i));
+                    start = i + 1;
+                }
+            }
+            if (list.substring(start).replace(/\s+/g, "") !== "") {
+                types.push(list.substring(start));
+            }
+            return types;
+        }
+
+        /**
+         * Parses a `fn(A, B) -> C` query into the types it searches for, or
+         * returns undefined if the query isn't one.
+         */
+        function parseSig(query) {
+            var start = query.match(/^\s*fn\s*\(/i);
+            if (!start) {
+                return undefined;
+            }
+            // find the parenthesis closing the parameters
+            var depth = 1, end = start[0].length;
+            for (; end < query.length && depth > 0; end++) {
+                var c = query.charAt(end);
+                if (c === "(") {
+                    depth += 1;
+                } else if (c === ")") {
+                    depth -= 1;
+                }
+            }
+            var rest = query.substring(end).match(/^\s*(->\s*(.+?))?\s*$/);
+            if (depth > 0 || !rest) {
+                return undefined;
+            }
+            var inputs = splitTypes(query.substring(start[0].length, end - 1));
+            for (var i = 0; i < inputs.length; i++) {
+                inputs[i] = normalizeType(inputs[i]);
+            }
+            return {
+                inputs: inputs,
+                output: rest[2] === undefined ? undefined : normalizeType(rest[2]),
+            };
+        }
+
         /**
          * Validate performs the following boolean logic. For example:
          * "File::open" will give IF A PARENT EXISTS => ("file" && "open")
@@ -320,8 +610,9 @@
         }
 
         function getQuery() {
-            var matches, type, query = $('.search-input').val();
+            var matches, type, sig, query = $('.search-input').val();
 
+            sig = parseSig(query);
             matches = query.match(/^(fn|mod|str(uct)?|enum|trait|t(ype)?d(ef)?)\s*:\s*/i);
             if (matches) {
                 type = matches[1].replace(/^td$/, 'typedef')
@@ -334,6 +625,7 @@
             return {
                 query: query,
                 type: type,
+                sig: sig,
                 id: query + type,
             };
         }
@@ -357,13 +649,14 @@
                 }, 20);
             });
 
-            $(document).off('keypress.searchnav');
-            $(document).on('keypress.searchnav', function(e) {
+            // Arrow keys only fire keydown events in most browsers
+            $(document).off('keydown.searchnav');
+            $(document).on('keydown.searchnav', function(e) {
                 var $active = $results.filter('.highlighted');
 
                 if (e.keyCode === 38) { // up
                     e.preventDefault();
-                    if (!$active.length || !$active.prev()) {
+                    if (!$active.length || !$active.prev().length) {
                         return;
                     }
 
@@ -390,6 +683,26 @@
             return $('<h1/>').text(content).html();
         }
 
+        // The page of the module at `path` relative to the root of the output,
+        // which with the flat layout is in the directory of its crate
+        function modulePage(path) {
+            var parts = path.split('::');
+            if (!window.flatLayout || parts.length === 1) {
+                return parts.join('/') + '/index.html';
+            }
+            return parts[0] + '/' + parts.slice(1).join('.') + '.index.html';
+        }
+
+        // The page of the item `name` of type `type` in the module at `path`
+        function itemPage(path, type, name) {
+            var parts = path.split('::'),
+                file = type + '.' + name + '.html';
+            if (!window.flatLayout || parts.length === 1) {
+                return parts.join('/') + '/' + file;
+            }
+            return parts[0] + '/' + parts.slice(1).join('.') + '.' + file;
+        }
+
         function showResults(results) {
             var output, shown, query = getQuery();
 
@@ -417,37 +730,35 @@
                     if (type === 'mod') {
                         output += item.path +
                             '::<a href="' + rootPath +
-                            item.path.replace(/::/g, '/') + '/' +
-                            name + '/index.html" class="' +
+                            modulePage(item.path + '::' + name) + '" class="' +
                             type + '">' + name + '</a>';
                     } else if (type === 'static' || type === 'reexport') {
                         output += item.path +
                             '::<a href="' + rootPath +
-                            item.path.replace(/::/g, '/') +
-                            '/index.html" class="' + type +
+                            modulePage(item.path) + '" class="' + type +
                             '">' + name + '</a>';
                     } else if (item.parent !== undefined) {
                         var myparent = item.parent;
                         var anchor = '#' + type + '.' + name;
-                        output += item.path + '::' + myparent.name +
+                        output += myparent.path + '::' + myparent.name +
                             '::<a href="' + rootPath +
-                            item.path.replace(/::/g, '/') +
-                            '/' + itemTypes[myparent.ty] +
-                            '.' + myparent.name +
-                            '.html' + anchor +
+                            itemPage(myparent.path, itemTypes[myparent.ty],
+                                     myparent.name) + anchor +
                             '" class="' + type +
                             '">' + name + '</a>';
                     } else {
                         output += item.path +
                             '::<a href="' + rootPath +
-                            item.path.replace(/::/g, '/') +
-                            '/' + type +
-                            '.' + name +
-                            '.html" class="' + type +
+                            itemPage(item.path, type, name) +
+                            '" class="' + type +
                             '">' + name + '</a>';
                     }
 
-                    output += '</td><td><span class="desc">' + item.desc +
+                    output += '</td><td>';
+                    if (item.deprecated) {
+                        output += '<span class="stability Deprecated">Deprecated</span> ';
+                    }
+                    output += '<span class="desc">' + item.desc +
                         '</span></td></tr>';
                 });
             } else {
@@ -482,6 +793,12 @@
                 return;
             }
 
+            // Searching again once the first shard of the index arrives
+            loadSearchIndex();
+            if (loadedChunks === 0) {
+                return;
+            }
+
             // Because searching is incremental by character, only the most
             // recent search query is added to the browser history.
             if (browserSupportsHistoryApi()) {
@@ -539,39 +856,61 @@
         function buildIndex(rawSearchIndex) {
             searchIndex = [];
             var searchWords = [];
-            for (var crate in rawSearchIndex) {
-                if (!rawSearchIndex.hasOwnProperty(crate)) { continue }
-
-                // an array of [(Number) item type,
-                //              (String) name,
-                //              (String) full path or empty string for previous path,
-                //              (String) description,
-                //              (optional Number) the parent path index to `paths`]
-                var items = rawSearchIndex[crate].items;
-                // an array of [(Number) item type,
-                //              (String) name]
-                var paths = rawSearchIndex[crate].paths;
-
-                // convert `paths` into an object form
-                var len = paths.length;
-                for (var i = 0; i < len; ++i) {
-                    paths[i] = {ty: paths[i][0], name: paths[i][1]};
+            for (var key in rawSearchIndex) {
+                if (!rawSearchIndex.hasOwnProperty(key)) { continue }
+
+                // each shard of the index is laid out by column, see
+                // `search_index_js` in `html/render.rs`:
+                //   t: (Array of Numbers) the types,
+                //   n: (Array of Strings) the names,
+                //   q: (Array of [Number, String]) the index of each item
+                //      whose module path differs from the previous one's,
+                //      and that path,
+                //   d: (Array of Strings) the descriptions,
+                //   i: (Array of Numbers) one more than the index of each
+                //      item's parent in `p`, or 0 for none,
+                //   x: (Array of Numbers) the deprecated items, as distances
+                //      from the previous one,
+                //   f: (Array of [Number, [String], String]) the signatures
+                //      of functions and methods, as a distance from the
+                //      previous one, the parameter types and the return type
+                //      or null for (),
+                //   a: (Array of [Number, [String]]) the other names items
+                //      are found by, as a distance from the previous item
+                //      with any and its aliases,
+                //   p: (Array of [Number, String, String]) the type, name
+                //      and module path of each parent
+                var crate = rawSearchIndex[key].crate;
+                var raw = rawSearchIndex[key].raw;
+                // skip the indexes left behind by older versions of rustdoc
+                if (!$.isArray(raw.t)) { continue }
+
+                // convert the parents into an object form
+                var paths = [];
+                for (var i = 0; i < raw.p.length; ++i) {
+                    paths.push({ty: raw.p[i][0], name: raw.p[i][1],
+                                path: raw.p[i][2]});
                 }
 
-                // convert `items` into an object form, and construct word indices.
+                // convert the items into an object form, and construct word
+                // indices.
                 //
                 // before any analysis is performed lets gather the search terms to
                 // search against apart from the rest of the data.  This is a quick
                 // operation that is cached for the life of the page state so that
                 // all other search operations have access to this cached data for
                 // faster analysis operations
-                var len = items.length;
-                var lastPath = "";
+                var start = searchIndex.length;
+                var len = raw.n.length;
+                var q = 0, path = "";
                 for (var i = 0; i < len; i += 1) {
-                    var rawRow = items[i];
-                    var row = {crate: crate, ty: rawRow[0], name: rawRow[1],
-                               path: rawRow[2] || lastPath, desc: rawRow[3],
-                               parent: paths[rawRow[4]]};
+                    if (q < raw.q.length && raw.q[q][0] === i) {
+                        path = raw.q[q][1];
+                        q += 1;
+                    }
+                    var row = {crate: crate, ty: raw.t[i],
+                               name: raw.n[i], path: path, desc: raw.d[i],
+                               parent: paths[raw.i[i] - 1]};
                     searchIndex.push(row);
                     if (typeof row.name === "string") {
                         var word = row.name.toLowerCase();
@@ -579,14 +918,83 @@
                     } else {
                         searchWords.push("");
                     }
-                    lastPath = row.path;
+                }
+
+                var at = start;
+                for (var i = 0; i < raw.x.length; ++i) {
+                    at += raw.x[i];
+                    searchIndex[at].deprecated = true;
+                }
+                at = start;
+                for (var i = 0; i < raw.f.length; ++i) {
+                    at += raw.f[i][0];
+                    searchIndex[at].sig = {inputs: raw.f[i][1], output: raw.f[i][2]};
+                }
+                // older indexes don't have aliases
+                at = start;
+                for (var i = 0; raw.a && i < raw.a.length; ++i) {
+                    at += raw.a[i][0];
+                    searchIndex[at].aliases = raw.a[i][1].map(function(alias) {
+                        return alias.toLowerCase();
+                    });
                 }
             }
             return searchWords;
         }
 
+        function loadScript(src) {
+            var script = document.createElement('script');
+            script.src = rootPath + 'search-index/' + src + '.js';
+            script.async = true;
+            document.getElementsByTagName('head')[0].appendChild(script);
+        }
+
+        // The shard of the search index that names starting like `query` are
+        // in, see `search_shard` in `html/render.rs`
+        function searchShard(query) {
+            var c = query.charAt(0).toLowerCase();
+            return /^[a-z0-9]$/.test(c) ? c : '_';
+        }
+
+        // Fetches the list of shards of the search index of each crate,
+        // unless that's already underway. Each list calls `addSearchShards`
+        // once loaded.
+        function loadSearchIndex() {
+            if (loadingChunks) {
+                return;
+            }
+            loadingChunks = true;
+            for (var i = 0; i < crates.length; i++) {
+                loadScript(crates[i]);
+            }
+        }
+
+        // Fetches the shards of a crate, starting with the one the current
+        // query's results are most likely in. Each shard calls
+        // `addSearchIndex` once loaded.
+        window.addSearchShards = function(crate, shards) {
+            var first = searchShard(getQuery().query);
+            if (shards.indexOf(first) !== -1) {
+                loadScript(crate + '/' + first);
+            }
+            for (var i = 0; i < shards.length; i++) {
+                if (shards[i] !== first) {
+                    loadScript(crate + '/' + shards[i]);
+                }
+            }
+        };
+
+        window.addSearchIndex = function(crate, shard, raw) {
+            rawSearchIndex[crate + '/' + shard] = {crate: crate, raw: raw};
+            loadedChunks += 1;
+            index = buildIndex(rawSearchIndex);
+            currentResults = null;
+            search();
+        };
+
         function startSearch() {
             var keyUpTimeout;
+            $('.search-input').on('focus', loadSearchIndex);
             $('.do-search').on('click', search);
             $('.search-input').on('keyup', function() {
                 clearTimeout(keyUpTimeout);
@@ -624,21 +1032,17 @@
             search();
         }
 
-        index = buildIndex(rawSearchIndex);
         startSearch();
 
-        // Draw a convenient sidebar of known crates if we have a listing
-        if (rootPath == '../') {
+        // Draw a convenient sidebar of known crates if we have a listing. With
+        // the flat layout, every page of a crate is a level down from the
+        // root, so only its root page gets one.
+        if (rootPath == '../' && (!window.flatLayout ||
+                                  /\/(index\.html)?$/.test(window.location.pathname))) {
             var sidebar = $('.sidebar');
             var div = $('<div>').attr('class', 'block crate');
             div.append($('<h2>').text('Crates'));
 
-            var crates = [];
-            for (var crate in rawSearchIndex) {
-                if (!rawSearchIndex.hasOwnProperty(crate)) { continue }
-                crates.push(crate);
-            }
-            crates.sort();
             for (var i = 0; i < crates.length; i++) {
                 var klass = 'crate';
                 if (crates[i] == window.currentCrate) {
@@ -34,6 +34,11 @@ impl Toc {
     fn count_entries_with_level(&self, level: u32) -> uint {
         self.entries.iter().count(|e| e.level == level)
     }
+
+    /// The number of headings in the table, at any level.
+    pub fn num_entries(&self) -> uint {
+        self.entries.iter().fold(0, |n, e| n + 1 + e.children.num_entries())
+    }
 }
 
 #[deriving(Eq)]
@@ -268,6 +273,7 @@ mod test {
              ((2, "3.1", ((4, "3.1.0.1", ))))
              )
             );
+        assert_eq!(expected.num_entries(), 15);
         assert_eq!(expected, builder.into_toc());
     }
 }
@@ -22,6 +22,7 @@ extern crate serialize;
 extern crate sync;
 extern crate getopts;
 extern crate collections;
+extern crate flate;
 extern crate testing = "test";
 extern crate time;
 #[phase(syntax, link)]
@@ -29,9 +30,12 @@ extern crate log;
 extern crate libc;
 
 use std::io;
-use std::io::{File, MemWriter};
-use std::str;
-use serialize::{json, Decodable, Encodable};
+use std::io::File;
+use serialize::{json, Decodable};
+use sync::Arc;
+
+use externalfiles::{ExternalHtml, Theme};
+use renderer::{Renderer, RenderOptions};
 
 // reexported from `clean` so it can be easily updated with the mod itself
 pub use clean::SCHEMA_VERSION;
@@ -39,20 +43,37 @@ pub use clean::SCHEMA_VERSION;
 pub mod clean;
 pub mod core;
 pub mod doctree;
+#[macro_escape]
+pub mod externalfiles;
 pub mod fold;
 pub mod html {
     pub mod highlight;
+    pub mod epub;
     pub mod escape;
+    pub mod incremental;
     pub mod item_type;
     pub mod format;
     pub mod layout;
     pub mod markdown;
+    pub mod minify;
     pub mod render;
+    pub mod single;
     pub mod toc;
 }
 pub mod markdown;
+pub mod output;
 pub mod passes;
 pub mod plugins;
+pub mod renderer;
+pub mod stability_summary;
+pub mod text {
+    pub mod diff;
+    pub mod json;
+    pub mod man;
+    pub mod markdown;
+    pub mod plain;
+    pub mod signature;
+}
 pub mod visit_ast;
 pub mod test;
 mod flock;
@@ -70,13 +91,20 @@ static PASSES: &'static [Pass] = &[
      "concatenates all document attributes into one document attribute"),
     ("strip-private", passes::strip_private,
      "strips all private items from a crate which cannot be seen externally"),
+    ("include-docs", passes::include_docs,
+     "appends the markdown files named by doc(include = \"...\") to the docs"),
 ];
 
+/// The script math is typeset with when `--math` isn't given a URL.
+static MATHJAX_URL: &'static str =
+    "https://cdn.mathjax.org/mathjax/latest/MathJax.js?config=TeX-AMS_HTML";
+
 static DEFAULT_PASSES: &'static [&'static str] = &[
     "strip-hidden",
     "strip-private",
     "collapse-docs",
     "unindent-comments",
+    "include-docs",
 ];
 
 local_data_key!(pub ctxtkey: @core::DocContext)
@@ -98,9 +126,16 @@ pub fn opts() -> Vec<getopts::OptGroup> {
         optflag("", "version", "print rustdoc's version"),
         optopt("r", "input-format", "the input type of the specified file",
                "[rust|json]"),
-        optopt("w", "output-format", "the output type to write",
-               "[html|json]"),
+        optmulti("w", "output-format", "the output types to write, several \
+                                         formats may be given separated by commas",
+                 "[html|single-html|epub|json|ndjson|markdown|man|text|ansi|diff]"),
         optopt("o", "output", "where to place the output", "PATH"),
+        optopt("", "archive", "package the output into an archive, which is \
+                               placed at the output path", "[zip|tar.gz]"),
+        optflag("", "gzip", "also write a gzipped copy of each HTML, CSS, JS and JSON \
+                             file of the output"),
+        optflag("", "gzip-only", "write the HTML, CSS, JS and JSON files of the output \
+                                  gzipped only"),
         optmulti("L", "library-path", "directory to add to crate search path",
                  "DIR"),
         optmulti("", "cfg", "pass a --cfg to rustc", ""),
@@ -111,9 +146,76 @@ pub fn opts() -> Vec<getopts::OptGroup> {
         optmulti("", "plugins", "space separated list of plugins to also load",
                  "PLUGINS"),
         optflag("", "no-defaults", "don't run the default passes"),
+        optflag("", "document-hidden-items", "document items marked `#[doc(hidden)]` as \
+                                              well, flagged as such"),
+        optflag("", "document-private-items", "document private items as well, flagged as \
+                                               such and left out of the search index"),
         optflag("", "test", "run code examples as tests"),
         optmulti("", "test-args", "arguments to pass to the test runner",
                  "ARGS"),
+        optmulti("", "html-in-header",
+                 "files to include inline in the <head> section of every generated page",
+                 "FILES"),
+        optmulti("", "html-before-content",
+                 "files to include inline between <body> and the content of every \
+                 generated page",
+                 "FILES"),
+        optmulti("", "html-after-content",
+                 "files to include inline between the content and </body> of every \
+                 generated page",
+                 "FILES"),
+        optmulti("", "extend-css",
+                 "CSS files whose rules are added to those of the theme on every \
+                 generated page",
+                 "FILES"),
+        optflag("", "incremental", "only render the HTML pages which changed since the \
+                                    last run into the same output directory"),
+        optflag("", "inline-source", "show the source of short items on their pages in the \
+                                      HTML output"),
+        optopt("", "logo", "the logo of the HTML output, as a URL or a local file which is \
+                            copied into the output", "URL|FILE"),
+        optopt("", "favicon", "the favicon of the HTML output, as a URL or a local file \
+                               which is copied into the output", "URL|FILE"),
+        optflag("", "sitemap", "write a sitemap of the HTML output and a robots.txt pointing \
+                                to it, which needs the URL given by --canonical-url"),
+        optflag("", "offline-assets", "keep the HTML output from loading anything over the \
+                                       network, embedding the logo and favicon"),
+        optflag("", "minify", "shrink the HTML output by dropping comments and needless \
+                               whitespace"),
+        optflagopt("", "math", "typeset math written as `$...$` or in `math` code blocks \
+                                of the HTML output with the script at URL (MathJax from \
+                                its CDN by default)",
+                   "URL"),
+        optopt("", "signature-width", "the width past which signatures in the HTML output \
+                                       are wrapped onto a line per argument (default 100)",
+               "COLUMNS"),
+        optopt("", "item-order", "the order to list the items of modules in, either \
+                                  `alphabetical` (the default) or `source`",
+               "ORDER"),
+        optopt("", "page-layout", "how to lay out the pages of the HTML output, either \
+                                   `nested` in a directory per module (the default) or \
+                                   `flat` in the directory of the crate",
+               "LAYOUT"),
+        optmulti("", "theme", "CSS files to offer as additional themes of the HTML output",
+                 "FILES"),
+        optmulti("", "extern-html-root-url", "the URL the documentation of an external \
+                                              crate is at, for links to its items",
+                 "CRATE=URL"),
+        optopt("", "default-theme", "the theme to show the HTML output in by default",
+               "NAME"),
+        optmulti("", "html-page",
+                 "files to include in generated pages of one kind only, where KIND is \
+                 `module`, `item` or `source` and PLACE is `in-header`, \
+                 `before-content` or `after-content`",
+                 "KIND:PLACE:FILE"),
+        optopt("", "html-template", "file to lay out generated pages with instead of the \
+                                     built-in template", "FILE"),
+        optopt("", "crate-version", "the version of the crate being documented",
+               "VERSION"),
+        optopt("", "canonical-url", "the URL the documentation is published at, which \
+                                     pages name as their canonical location", "URL"),
+        optopt("", "baseline", "JSON output of an earlier version of the crate, \
+                                for the diff output to compare against", "FILE"),
         optmulti("", "markdown-css", "CSS files to include via <link> in a rendered Markdown file",
                  "FILES"),
         optmulti("", "markdown-in-header",
@@ -177,7 +279,7 @@ pub fn main_args(args: &[StrBuf]) -> int {
     let should_test = matches.opt_present("test");
     let markdown_input = input.ends_with(".md") || input.ends_with(".markdown");
 
-    let output = matches.opt_str("o").map(|s| Path::new(s));
+    let mut output = matches.opt_str("o").map(|s| Path::new(s));
     let cfgs = matches.opt_strs("cfg");
 
     match (should_test, markdown_input) {
@@ -211,6 +313,31 @@ pub fn main_args(args: &[StrBuf]) -> int {
         return 0;
     }
 
+    // Multiple output formats may be requested at once, either by repeating
+    // the flag or as a comma-separated list. These are resolved before the
+    // crate is analyzed so a typo doesn't cost a full compilation.
+    let mut formats = Vec::new();
+    for s in matches.opt_strs("w").iter() {
+        for format in s.as_slice().split(',') {
+            formats.push(format.trim().to_strbuf());
+        }
+    }
+    if formats.len() == 0 {
+        formats.push(renderer::DEFAULT_FORMAT.to_strbuf());
+    }
+
+    let mut renderers = Vec::new();
+    for format in formats.iter() {
+        match renderer::find(format.as_slice()) {
+            Some(r) => renderers.push(r),
+            None => {
+                println!("unknown output format: {} (expected one of: {})",
+                         format, renderer::formats().connect(", "));
+                return 1;
+            }
+        }
+    }
+
     let (krate, res) = match acquire_input(input, &matches) {
         Ok(pair) => pair,
         Err(s) => {
@@ -221,23 +348,202 @@ pub fn main_args(args: &[StrBuf]) -> int {
 
     info!("going to format");
     let started = time::precise_time_ns();
-    match matches.opt_str("w").as_ref().map(|s| s.as_slice()) {
-        Some("html") | None => {
-            match html::render::run(krate, output.unwrap_or(Path::new("doc"))) {
-                Ok(()) => {}
-                Err(e) => fail!("failed to generate documentation: {}", e),
+    let external_html = match ExternalHtml::load(
+            matches.opt_strs("html-in-header")
+                   .move_iter()
+                   .map(|x| x.to_strbuf())
+                   .collect::<Vec<_>>()
+                   .as_slice(),
+            matches.opt_strs("html-before-content")
+                   .move_iter()
+                   .map(|x| x.to_strbuf())
+                   .collect::<Vec<_>>()
+                   .as_slice(),
+            matches.opt_strs("html-after-content")
+                   .move_iter()
+                   .map(|x| x.to_strbuf())
+                   .collect::<Vec<_>>()
+                   .as_slice()) {
+        Some(eh) => eh,
+        None => return 3,
+    };
+    let external_html = match external_html.load_pages(
+            matches.opt_strs("html-page")
+                   .move_iter()
+                   .map(|x| x.to_strbuf())
+                   .collect::<Vec<_>>()
+                   .as_slice()) {
+        Some(eh) => eh,
+        None => return 3,
+    };
+    let external_html = match external_html.load_css(
+            matches.opt_strs("extend-css")
+                   .move_iter()
+                   .map(|x| x.to_strbuf())
+                   .collect::<Vec<_>>()
+                   .as_slice()) {
+        Some(eh) => eh,
+        None => return 3,
+    };
+    let mut themes = Vec::new();
+    for file in matches.opt_strs("theme").iter() {
+        match Theme::load(file.as_slice()) {
+            Some(theme) => themes.push(theme),
+            None => return 3,
+        }
+    }
+    let mut extern_urls = Vec::new();
+    for arg in matches.opt_strs("extern-html-root-url").iter() {
+        match arg.as_slice().find('=') {
+            Some(i) => {
+                extern_urls.push((arg.as_slice().slice_to(i).to_strbuf(),
+                                  arg.as_slice().slice_from(i + 1).to_strbuf()));
+            }
+            None => {
+                println!("--extern-html-root-url expects CRATE=URL, not `{}`", *arg);
+                return 1;
             }
         }
-        Some("json") => {
-            match json_output(krate, res, output.unwrap_or(Path::new("doc.json"))) {
-                Ok(()) => {}
-                Err(e) => fail!("failed to write json: {}", e),
+    }
+    let template = match matches.opt_str("html-template") {
+        Some(file) => Some(load_or_return!(file.as_slice(), 3, 3)),
+        None => None,
+    };
+    let crate_version = matches.opt_str("crate-version").map(|s| s.to_strbuf());
+    let baseline = match matches.opt_str("baseline") {
+        Some(file) => {
+            match json_input(file.as_slice()) {
+                Ok((krate, _)) => Some(krate),
+                Err(s) => {
+                    println!("couldn't load the baseline: {}", s);
+                    return 1;
+                }
             }
         }
+        None => None,
+    };
+
+    // When archiving, the output path names the archive, which is laid out
+    // like the output directory of several renderers.
+    let archive = match matches.opt_str("archive").as_ref().map(|s| s.as_slice()) {
+        Some("zip") => Some((output::Zip, "doc.zip")),
+        Some("tar.gz") => Some((output::TarGz, "doc.tar.gz")),
         Some(s) => {
-            println!("unknown output format: {}", s);
+            println!("unknown archive format: {}", s);
             return 1;
         }
+        None => None,
+    };
+    let mut sink = match archive {
+        Some((format, name)) => {
+            let dst = output.take().unwrap_or(Path::new(name));
+            box output::ArchiveSink::new(format, dst) as Box<output::OutputSink>
+        }
+        None => box output::DirectorySink::new(Path::new(".")) as Box<output::OutputSink>,
+    };
+    if matches.opt_present("gzip") || matches.opt_present("gzip-only") {
+        sink = box output::GzipSink::new(sink, matches.opt_present("gzip-only"))
+                   as Box<output::OutputSink>;
+    }
+
+    // With a single renderer the output path is used as-is, but when several
+    // are run it names a directory which they all place their output into.
+    let dests = if renderers.len() == 1 && archive.is_none() {
+        let dest = renderers.get(0).output_filename().unwrap_or("doc");
+        vec!(output.unwrap_or(Path::new(dest)))
+    } else {
+        let dir = output.unwrap_or(Path::new("doc"));
+        renderers.iter().map(|r| {
+            match r.output_filename() {
+                Some(file) => dir.join(file),
+                None => dir.clone(),
+            }
+        }).collect()
+    };
+
+    // External crate docs are looked for next to the HTML output, if any.
+    let doc_root = match renderers.iter().position(|r| r.output_filename().is_none()) {
+        Some(i) => dests.get(i).clone(),
+        None => dests.get(0).dir_path(),
+    };
+    let mut cache = html::render::build_cache(&krate, &doc_root,
+                                              matches.opt_present("document-private-items"));
+    for name in html::render::set_extern_urls(&mut cache, &krate,
+                                              extern_urls.as_slice()).iter() {
+        println!("warning: --extern-html-root-url names `{}`, which isn't a dependency",
+                 *name);
+    }
+    let cache = Arc::new(cache);
+
+    let mut opts = RenderOptions::new(Path::new("."));
+    opts.external_html = external_html;
+    opts.themes = themes;
+    opts.default_theme = matches.opt_str("default-theme").map(|s| s.to_strbuf());
+    opts.template = template;
+    opts.crate_version = crate_version;
+    opts.canonical_url = matches.opt_str("canonical-url").map(|s| s.to_strbuf());
+    opts.logo = matches.opt_str("logo").map(|s| s.to_strbuf());
+    opts.favicon = matches.opt_str("favicon").map(|s| s.to_strbuf());
+    opts.sitemap = matches.opt_present("sitemap");
+    opts.plugin_output = res;
+    opts.baseline = baseline;
+    opts.incremental = matches.opt_present("incremental");
+    opts.inline_source = matches.opt_present("inline-source");
+    opts.offline_assets = matches.opt_present("offline-assets");
+    opts.minify = matches.opt_present("minify");
+    if matches.opt_present("math") {
+        opts.math = Some(matches.opt_str("math").map(|s| s.to_strbuf())
+                                .unwrap_or(MATHJAX_URL.to_strbuf()));
+    }
+    match matches.opt_str("signature-width") {
+        Some(s) => {
+            match from_str(s.as_slice()) {
+                Some(width) => opts.signature_width = width,
+                None => {
+                    println!("--signature-width expects a number, not `{}`", s);
+                    return 1;
+                }
+            }
+        }
+        None => {}
+    }
+    match matches.opt_str("item-order") {
+        Some(s) => {
+            opts.item_order = match s.as_slice() {
+                "alphabetical" => renderer::Alphabetical,
+                "source" => renderer::SourceOrder,
+                _ => {
+                    println!("unknown item order: {}", s);
+                    return 1;
+                }
+            };
+        }
+        None => {}
+    }
+    match matches.opt_str("page-layout") {
+        Some(s) => {
+            opts.page_layout = match s.as_slice() {
+                "nested" => renderer::Nested,
+                "flat" => renderer::Flat,
+                _ => {
+                    println!("unknown page layout: {}", s);
+                    return 1;
+                }
+            };
+        }
+        None => {}
+    }
+    for (renderer, dest) in renderers.mut_iter().zip(dests.move_iter()) {
+        opts.dest = dest;
+        opts.phase(renderer.name());
+        match renderer.render(krate.clone(), cache.clone(), &opts, &mut *sink) {
+            Ok(()) => {}
+            Err(e) => fail!("failed to generate {} output: {}", renderer.name(), e),
+        }
+    }
+    match sink.finish() {
+        Ok(()) => {}
+        Err(e) => fail!("failed to write output: {}", e),
     }
     let ended = time::precise_time_ns();
     info!("Took {:.03f}s", (ended as f64 - started as f64) / 1e9f64);
@@ -323,6 +629,12 @@ fn rust_input(cratefile: &str, matches: &getopts::Matches) -> Output {
     }
     if default_passes {
         for name in DEFAULT_PASSES.iter().rev() {
+            if *name == "strip-private" && matches.opt_present("document-private-items") {
+                continue
+            }
+            if *name == "strip-hidden" && matches.opt_present("document-hidden-items") {
+                continue
+            }
             passes.unshift(name.to_owned());
         }
     }
@@ -393,47 +705,3 @@ fn json_input(input: &str) -> Result<Output, StrBuf> {
         }
     }
 }
-
-/// Outputs the crate/plugin json as a giant json blob at the specified
-/// destination.
-fn json_output(krate: clean::Crate, res: Vec<plugins::PluginJson> ,
-               dst: Path) -> io::IoResult<()> {
-    // {
-    //   "schema": version,
-    //   "crate": { parsed crate ... },
-    //   "plugins": { output of plugins ... }
-    // }
-    let mut json = box collections::TreeMap::new();
-    json.insert("schema".to_owned(), json::String(SCHEMA_VERSION.to_owned()));
-    let plugins_json = box res.move_iter()
-                              .filter_map(|opt| {
-                                  match opt {
-                                      None => None,
-                                      Some((string, json)) => {
-                                          Some((string.to_owned(), json))
-                                      }
-                                  }
-                              }).collect();
-
-    // FIXME #8335: yuck, Rust -> str -> JSON round trip! No way to .encode
-    // straight to the Rust JSON representation.
-    let crate_json_str = {
-        let mut w = MemWriter::new();
-        {
-            let mut encoder = json::Encoder::new(&mut w as &mut io::Writer);
-            krate.encode(&mut encoder).unwrap();
-        }
-        str::from_utf8(w.unwrap().as_slice()).unwrap().to_owned()
-    };
-    let crate_json = match json::from_str(crate_json_str) {
-        Ok(j) => j,
-        Err(e) => fail!("Rust generated JSON is invalid: {:?}", e)
-    };
-
-    json.insert("crate".to_owned(), crate_json);
-    json.insert("plugins".to_owned(), json::Object(plugins_json));
-
-    let mut file = try!(File::create(&dst));
-    try!(json::Object(json).to_writer(&mut file));
-    Ok(())
-}
@@ -0,0 +1,672 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Destinations for rendered documentation
+//!
+//! Renderers never touch the filesystem directly. Instead every file they
+//! produce is created through an `OutputSink`, with a path relative to the
+//! root of the output. This way the same renderer can write into a directory,
+//! straight into a compressed archive, or into memory for testing.
+
+use collections::TreeMap;
+use std::cmp;
+use std::io;
+use std::io::{fs, File, MemWriter};
+use std::mem;
+use std::strbuf::StrBuf;
+use sync::{Arc, Mutex};
+
+use flate;
+
+/// Somewhere the files produced by renderers are placed.
+pub trait OutputSink {
+    /// Creates the file at `path`, relative to the root of the output, along
+    /// with any directories leading up to it. The file is complete once the
+    /// returned writer has been dropped. Errors which only come up completing
+    /// the file are reported by `finish`.
+    fn create(&mut self, path: &Path) -> io::IoResult<Box<Writer:Send>>;
+
+    /// Reads the contents of a file already in the output, such as one which
+    /// is shared by the documentation of several crates, or `None` if there
+    /// is no such file.
+    fn read(&mut self, path: &Path) -> io::IoResult<Option<Vec<u8>>>;
+
+    /// The directory on disk the output is placed into, if any.
+    fn root(&self) -> Option<Path> { None }
+
+    /// Whether the file at `path` is already in the output on disk.
+    fn exists(&self, path: &Path) -> bool {
+        self.root().map_or(false, |root| root.join(path).exists())
+    }
+
+    /// Completes the output, once every renderer is done with it, along with
+    /// any files which weren't yet.
+    fn finish(&mut self) -> io::IoResult<()> { Ok(()) }
+}
+
+/// Creates the file at `path` in `sink` with the given contents.
+pub fn write(sink: &mut OutputSink, path: &Path, contents: &[u8]) -> io::IoResult<()> {
+    let mut w = try!(sink.create(path));
+    w.write(contents)
+}
+
+/// Writes output into a directory on disk.
+pub struct DirectorySink {
+    root: Path,
+}
+
+impl DirectorySink {
+    pub fn new(root: Path) -> DirectorySink {
+        DirectorySink { root: root }
+    }
+}
+
+impl OutputSink for DirectorySink {
+    fn create(&mut self, path: &Path) -> io::IoResult<Box<Writer:Send>> {
+        let path = self.root.join(path);
+        let dir = path.dir_path();
+        if !dir.exists() {
+            try!(fs::mkdir_recursive(&dir, io::UserRWX));
+        }
+        Ok(box try!(File::create(&path)) as Box<Writer:Send>)
+    }
+
+    fn read(&mut self, path: &Path) -> io::IoResult<Option<Vec<u8>>> {
+        let path = self.root.join(path);
+        if !path.exists() { return Ok(None) }
+        File::open(&path).read_to_end().map(|v| Some(v))
+    }
+
+    fn root(&self) -> Option<Path> { Some(self.root.clone()) }
+}
+
+type Files = Arc<Mutex<TreeMap<StrBuf, Vec<u8>>>>;
+
+/// Keeps all output in memory, keyed by path. Clones share the same files.
+#[deriving(Clone)]
+pub struct MemorySink {
+    files: Files,
+    /// The files created so far, which are committed along with the sink if
+    /// they weren't already
+    pending: Arc<Mutex<Vec<Arc<Mutex<PendingFile>>>>>,
+    /// Whether the path of each file has to fit into the header of a tar
+    /// archive, which is checked as it's committed
+    tar_names: bool,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink {
+            files: Arc::new(Mutex::new(TreeMap::new())),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            tar_names: false,
+        }
+    }
+
+    /// The paths of all files in the output, in sorted order.
+    pub fn paths(&self) -> Vec<StrBuf> {
+        self.files.lock().keys().map(|k| k.clone()).collect()
+    }
+
+    /// The contents of the file at `path`, if it has been created.
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.lock().find(&key(&Path::new(path))).map(|v| v.clone())
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn create(&mut self, path: &Path) -> io::IoResult<Box<Writer:Send>> {
+        let file = Arc::new(Mutex::new(PendingFile {
+            path: key(path),
+            buf: MemWriter::new(),
+            files: self.files.clone(),
+            tar_names: self.tar_names,
+            committed: None,
+        }));
+        self.pending.lock().push(file.clone());
+        Ok(box PendingWriter { file: file } as Box<Writer:Send>)
+    }
+
+    fn read(&mut self, path: &Path) -> io::IoResult<Option<Vec<u8>>> {
+        Ok(self.contents(key(path).as_slice()))
+    }
+
+    /// Commits any files which weren't yet. Fails with the first error
+    /// committing any of the files.
+    fn finish(&mut self) -> io::IoResult<()> {
+        for file in self.pending.lock().iter() {
+            try!(file.lock().commit());
+        }
+        Ok(())
+    }
+}
+
+/// The name of the file at `path` within an archive or a `MemorySink`. Paths
+/// which aren't UTF-8 have the invalid parts replaced, as the names of the
+/// files are strings.
+fn key(path: &Path) -> StrBuf {
+    format_strbuf!("{}", path.display())
+}
+
+/// A file being written to a `MemorySink`, which is added to it when done.
+struct PendingFile {
+    path: StrBuf,
+    buf: MemWriter,
+    files: Files,
+    tar_names: bool,
+    /// The result of committing the file, once it has been
+    committed: Option<io::IoResult<()>>,
+}
+
+impl PendingFile {
+    /// Adds the complete file to the sink, if its path is one the sink can
+    /// take. Only the first call does so, later ones return the same result.
+    fn commit(&mut self) -> io::IoResult<()> {
+        match self.committed {
+            Some(ref result) => return result.clone(),
+            None => {}
+        }
+        let result = if self.tar_names {
+            tar_name(self.path.as_slice()).map(|_| ())
+        } else {
+            Ok(())
+        };
+        if result.is_ok() {
+            let buf = mem::replace(&mut self.buf, MemWriter::new());
+            self.files.lock().insert(self.path.clone(), buf.unwrap());
+        }
+        self.committed = Some(result.clone());
+        result
+    }
+}
+
+/// The writer handed out for a `PendingFile`. The file is committed once
+/// this is dropped, or at the latest when the sink is finished, which is
+/// where any error committing it is reported.
+struct PendingWriter {
+    file: Arc<Mutex<PendingFile>>,
+}
+
+impl Writer for PendingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        self.file.lock().buf.write(buf)
+    }
+}
+
+impl Drop for PendingWriter {
+    fn drop(&mut self) {
+        // The error, if any, is kept for `MemorySink::finish` to report
+        let _ = self.file.lock().commit();
+    }
+}
+
+/// The kinds of archive which an `ArchiveSink` can write.
+pub enum ArchiveFormat {
+    /// A zip archive with deflated entries
+    Zip,
+    /// A gzipped tarball
+    TarGz,
+}
+
+/// Writes all output into a single compressed archive once it's finished.
+pub struct ArchiveSink {
+    format: ArchiveFormat,
+    dst: Path,
+    files: MemorySink,
+}
+
+impl ArchiveSink {
+    /// Creates a sink which writes an archive of the given format to `dst`.
+    pub fn new(format: ArchiveFormat, dst: Path) -> ArchiveSink {
+        let mut files = MemorySink::new();
+        files.tar_names = match format {
+            Zip => false,
+            TarGz => true,
+        };
+        ArchiveSink { format: format, dst: dst, files: files }
+    }
+}
+
+impl OutputSink for ArchiveSink {
+    fn create(&mut self, path: &Path) -> io::IoResult<Box<Writer:Send>> {
+        self.files.create(path)
+    }
+
+    fn read(&mut self, path: &Path) -> io::IoResult<Option<Vec<u8>>> {
+        self.files.read(path)
+    }
+
+    fn finish(&mut self) -> io::IoResult<()> {
+        try!(self.files.finish());
+        let files = self.files.files.lock();
+        let mut out = try!(File::create(&self.dst));
+        match self.format {
+            Zip => {
+                let mut zip = ZipWriter::new(out);
+                for (name, contents) in files.iter() {
+                    try!(zip.add(name.as_slice(), contents.as_slice(), true));
+                }
+                zip.finish().map(|_| ())
+            }
+            TarGz => {
+                let mut tar = MemWriter::new();
+                for (name, contents) in files.iter() {
+                    try!(tar_entry(&mut tar, name.as_slice(), contents.as_slice()));
+                }
+                // Two empty blocks mark the end of the archive
+                try!(tar.write([0u8, ..1024]));
+                gzip(&mut out, tar.get_ref())
+            }
+        }
+    }
+}
+
+/// The extensions of the files which a `GzipSink` compresses.
+static GZIP_EXTENSIONS: &'static [&'static str] = &["html", "css", "js", "json", "svg", "md"];
+
+/// Wraps another sink, adding a gzipped copy of each text file next to it
+/// with `.gz` appended to its name, for web servers to hand to browsers which
+/// accept it. With `only` set, text files are only written gzipped.
+pub struct GzipSink {
+    inner: Box<OutputSink>,
+    only: bool,
+    /// The text files created so far, which are finished along with the sink
+    /// if they weren't already
+    files: Vec<Arc<Mutex<GzipFile>>>,
+}
+
+impl GzipSink {
+    pub fn new(inner: Box<OutputSink>, only: bool) -> GzipSink {
+        GzipSink { inner: inner, only: only, files: Vec::new() }
+    }
+}
+
+/// The path of the gzipped copy of the file at `path`, if it's compressed.
+fn gzip_path(path: &Path) -> Option<Path> {
+    let compressed = path.extension_str().map_or(false, |ext| {
+        GZIP_EXTENSIONS.iter().any(|e| *e == ext)
+    });
+    if !compressed { return None }
+    path.filename_str().map(|name| path.with_filename(format!("{}.gz", name)))
+}
+
+impl OutputSink for GzipSink {
+    fn create(&mut self, path: &Path) -> io::IoResult<Box<Writer:Send>> {
+        let gz = match gzip_path(path) {
+            Some(gz) => gz,
+            None => return self.inner.create(path),
+        };
+        let plain = if self.only { None } else { Some(try!(self.inner.create(path))) };
+        let file = Arc::new(Mutex::new(GzipFile {
+            plain: plain,
+            gz: try!(self.inner.create(&gz)),
+            buf: MemWriter::new(),
+            finished: None,
+        }));
+        self.files.push(file.clone());
+        Ok(box GzipWriter { file: file } as Box<Writer:Send>)
+    }
+
+    fn read(&mut self, path: &Path) -> io::IoResult<Option<Vec<u8>>> {
+        match gzip_path(path) {
+            Some(ref gz) if self.only => {
+                match try!(self.inner.read(gz)) {
+                    Some(data) => gunzip(data.as_slice()).map(|v| Some(v)),
+                    None => Ok(None),
+                }
+            }
+            Some(..) | None => self.inner.read(path),
+        }
+    }
+
+    fn root(&self) -> Option<Path> { self.inner.root() }
+
+    fn exists(&self, path: &Path) -> bool {
+        match gzip_path(path) {
+            Some(ref gz) if self.only => self.inner.exists(gz),
+            Some(..) | None => self.inner.exists(path),
+        }
+    }
+
+    /// Compresses any text files which weren't yet, and completes the output
+    /// wrapped. Fails with the first error compressing any of the files.
+    fn finish(&mut self) -> io::IoResult<()> {
+        for file in self.files.iter() {
+            try!(file.lock().finish());
+        }
+        self.inner.finish()
+    }
+}
+
+/// A text file being written to a `GzipSink`, which is compressed once it's
+/// complete.
+struct GzipFile {
+    plain: Option<Box<Writer:Send>>,
+    gz: Box<Writer:Send>,
+    buf: MemWriter,
+    /// The result of compressing the file, once it has been
+    finished: Option<io::IoResult<()>>,
+}
+
+impl GzipFile {
+    /// Writes out the gzipped copy of the complete file. Only the first call
+    /// does so, later ones return the same result.
+    fn finish(&mut self) -> io::IoResult<()> {
+        match self.finished {
+            Some(ref result) => return result.clone(),
+            None => {}
+        }
+        let buf = mem::replace(&mut self.buf, MemWriter::new());
+        let result = gzip(&mut *self.gz, buf.get_ref());
+        self.finished = Some(result.clone());
+        result
+    }
+}
+
+/// The writer handed out for a `GzipFile`. The file is compressed once this
+/// is dropped, or at the latest when the sink is finished, which is where any
+/// error compressing it is reported.
+struct GzipWriter {
+    file: Arc<Mutex<GzipFile>>,
+}
+
+impl Writer for GzipWriter {
+    fn write(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        let mut file = self.file.lock();
+        match file.plain {
+            Some(ref mut w) => try!(w.write(buf)),
+            None => {}
+        }
+        file.buf.write(buf)
+    }
+}
+
+impl Drop for GzipWriter {
+    fn drop(&mut self) {
+        // The error, if any, is kept for `GzipSink::finish` to report
+        let _ = self.file.lock().finish();
+    }
+}
+
+/// A writer of zip archives.
+pub struct ZipWriter<W> {
+    w: W,
+    /// Bytes written so far
+    offset: u32,
+    /// Central directory entries of the files added so far
+    directory: MemWriter,
+    entries: u16,
+}
+
+impl<W: Writer> ZipWriter<W> {
+    pub fn new(w: W) -> ZipWriter<W> {
+        ZipWriter { w: w, offset: 0, directory: MemWriter::new(), entries: 0 }
+    }
+
+    /// Adds a file to the archive, compressing it if `deflate` is set.
+    pub fn add(&mut self, name: &str, data: &[u8], deflate: bool) -> io::IoResult<()> {
+        let crc = crc32(data);
+        let name = name.as_bytes();
+        let compressed = if deflate { flate::deflate_bytes(data) } else { None };
+        let (method, stored) = match compressed {
+            Some(ref c) if c.as_slice().len() < data.len() => (8, c.as_slice()),
+            Some(..) | None => (0, data),
+        };
+
+        // Local file header
+        try!(self.w.write_le_u32(0x04034b50));
+        try!(self.w.write_le_u16(20)); // version needed to extract
+        try!(self.w.write_le_u16(0)); // flags
+        try!(self.w.write_le_u16(method));
+        try!(self.w.write_le_u16(0)); // modification time
+        try!(self.w.write_le_u16(0x21)); // modification date: 1980-01-01
+        try!(self.w.write_le_u32(crc));
+        try!(self.w.write_le_u32(stored.len() as u32));
+        try!(self.w.write_le_u32(data.len() as u32));
+        try!(self.w.write_le_u16(name.len() as u16));
+        try!(self.w.write_le_u16(0)); // extra field length
+        try!(self.w.write(name));
+        try!(self.w.write(stored));
+
+        // Central directory header
+        let d = &mut self.directory;
+        try!(d.write_le_u32(0x02014b50));
+        try!(d.write_le_u16(20)); // version made by
+        try!(d.write_le_u16(20)); // version needed to extract
+        try!(d.write_le_u16(0));
+        try!(d.write_le_u16(method));
+        try!(d.write_le_u16(0));
+        try!(d.write_le_u16(0x21));
+        try!(d.write_le_u32(crc));
+        try!(d.write_le_u32(stored.len() as u32));
+        try!(d.write_le_u32(data.len() as u32));
+        try!(d.write_le_u16(name.len() as u16));
+        try!(d.write_le_u16(0)); // extra field length
+        try!(d.write_le_u16(0)); // comment length
+        try!(d.write_le_u16(0)); // disk number
+        try!(d.write_le_u16(0)); // internal attributes
+        try!(d.write_le_u32(0)); // external attributes
+        try!(d.write_le_u32(self.offset));
+        try!(d.write(name));
+
+        self.offset += 30 + name.len() as u32 + stored.len() as u32;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Writes the central directory, completing the archive, and returns the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::IoResult<W> {
+        let directory = self.directory.unwrap();
+        try!(self.w.write(directory.as_slice()));
+        try!(self.w.write_le_u32(0x06054b50));
+        try!(self.w.write_le_u16(0)); // number of this disk
+        try!(self.w.write_le_u16(0)); // disk with the central directory
+        try!(self.w.write_le_u16(self.entries));
+        try!(self.w.write_le_u16(self.entries));
+        try!(self.w.write_le_u32(directory.len() as u32));
+        try!(self.w.write_le_u32(self.offset));
+        try!(self.w.write_le_u16(0)); // comment length
+        Ok(self.w)
+    }
+}
+
+/// Writes the header and contents of a file in a ustar archive.
+/// Splits `name` into the prefix and name of a tar header. Long names are
+/// split at a directory separator, and those too long either way fail.
+fn tar_name<'a>(name: &'a str) -> io::IoResult<(&'a str, &'a str)> {
+    if name.len() <= 100 {
+        return Ok(("", name))
+    }
+    match name.slice_to(cmp::min(name.len(), 156)).rfind('/') {
+        Some(i) if name.len() - i - 1 <= 100 => {
+            Ok((name.slice_to(i), name.slice_from(i + 1)))
+        }
+        _ => {
+            Err(io::IoError {
+                kind: io::InvalidInput,
+                desc: "path too long for a tar archive",
+                detail: Some(name.to_owned()),
+            })
+        }
+    }
+}
+
+fn tar_entry(w: &mut Writer, name: &str, data: &[u8]) -> io::IoResult<()> {
+    let (prefix, name) = try!(tar_name(name));
+
+    fn field(header: &mut [u8], at: uint, s: &[u8]) {
+        header.mut_slice(at, at + s.len()).copy_from(s);
+    }
+    let mut header = [0u8, ..512];
+    field(header, 0, name.as_bytes());
+    field(header, 100, "0000644".as_bytes()); // mode
+    field(header, 108, "0000000".as_bytes()); // uid
+    field(header, 116, "0000000".as_bytes()); // gid
+    field(header, 124, format!("{:011o}", data.len()).as_bytes());
+    field(header, 136, "00000000000".as_bytes()); // modification time
+    field(header, 148, "        ".as_bytes()); // checksum, while summing
+    field(header, 156, "0".as_bytes()); // regular file
+    field(header, 257, "ustar".as_bytes());
+    field(header, 263, "00".as_bytes());
+    field(header, 345, prefix.as_bytes());
+    let sum = header.iter().fold(0u, |n, &b| n + b as uint);
+    field(header, 148, format!("{:06o}\0 ", sum).as_bytes());
+    try!(w.write(header));
+    try!(w.write(data));
+    let padding = (512 - data.len() % 512) % 512;
+    w.write(Vec::from_elem(padding, 0u8).as_slice())
+}
+
+/// Writes `data` compressed in the gzip format.
+fn gzip(w: &mut Writer, data: &[u8]) -> io::IoResult<()> {
+    let compressed = match flate::deflate_bytes(data) {
+        Some(c) => c,
+        None => {
+            return Err(io::IoError {
+                kind: io::OtherIoError,
+                desc: "failed to compress output",
+                detail: None,
+            })
+        }
+    };
+    // Magic, deflate, no flags, no modification time, unknown OS
+    try!(w.write([0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]));
+    try!(w.write(compressed.as_slice()));
+    try!(w.write_le_u32(crc32(data)));
+    w.write_le_u32(data.len() as u32)
+}
+
+/// Decompresses `data`, as written by `gzip`.
+fn gunzip(data: &[u8]) -> io::IoResult<Vec<u8>> {
+    let invalid = io::IoError {
+        kind: io::InvalidInput,
+        desc: "not a gzipped file rustdoc can read",
+        detail: None,
+    };
+    // Only the header written by `gzip` is understood, without any flags
+    if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 ||
+       data[3] != 0 {
+        return Err(invalid)
+    }
+    match flate::inflate_bytes(data.slice(10, data.len() - 8)) {
+        Some(v) => Ok(Vec::from_slice(v.as_slice())),
+        None => Err(invalid),
+    }
+}
+
+/// Computes the CRC-32 checksum used by zip and gzip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+    for &b in data.iter() {
+        crc ^= b as u32;
+        for _ in range(0, 8) {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::io::TempDir;
+    use std::strbuf::StrBuf;
+
+    use super::{OutputSink, DirectorySink, MemorySink, GzipSink, ArchiveSink, TarGz};
+    use super::{crc32, write};
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32([]), 0);
+        assert_eq!(crc32("123456789".as_bytes()), 0xcbf43926);
+    }
+
+    #[test]
+    fn memory() {
+        let mut sink = MemorySink::new();
+        write(&mut sink, &Path::new("b/c.html"), "c".as_bytes()).unwrap();
+        {
+            let mut w = sink.create(&Path::new("./a.html")).unwrap();
+            w.write("a".as_bytes()).unwrap();
+            w.write("a".as_bytes()).unwrap();
+        }
+        assert_eq!(sink.paths(), vec!("a.html".to_strbuf(), "b/c.html".to_strbuf()));
+        assert_eq!(sink.contents("a.html"), Some(Vec::from_slice("aa".as_bytes())));
+        assert_eq!(sink.read(&Path::new("b/c.html")).unwrap(),
+                   Some(Vec::from_slice("c".as_bytes())));
+        assert_eq!(sink.read(&Path::new("d.html")).unwrap(), None);
+    }
+
+    #[test]
+    fn non_utf8_paths() {
+        let mut sink = MemorySink::new();
+        write(&mut sink, &Path::new(bytes!("a", 0xff, ".html")), "a".as_bytes()).unwrap();
+        assert_eq!(sink.paths(), vec!("a\ufffd.html".to_strbuf()));
+    }
+
+    #[test]
+    fn gzip() {
+        let files = MemorySink::new();
+        let mut sink = GzipSink::new(box files.clone() as Box<OutputSink>, true);
+        write(&mut sink, &Path::new("a/b.html"), "<p>b</p>".as_bytes()).unwrap();
+        write(&mut sink, &Path::new("c.woff"), "c".as_bytes()).unwrap();
+        assert_eq!(files.paths(), vec!("a/b.html.gz".to_strbuf(), "c.woff".to_strbuf()));
+        assert_eq!(sink.read(&Path::new("a/b.html")).unwrap(),
+                   Some(Vec::from_slice("<p>b</p>".as_bytes())));
+    }
+
+    #[test]
+    fn gzip_only_exists() {
+        let dir = TempDir::new("rustdoc-gzip").unwrap();
+        let files = DirectorySink::new(dir.path().clone());
+        let mut sink = GzipSink::new(box files as Box<OutputSink>, true);
+        write(&mut sink, &Path::new("a.html"), "a".as_bytes()).unwrap();
+        assert!(sink.exists(&Path::new("a.html")));
+        assert!(!sink.exists(&Path::new("b.html")));
+    }
+
+    /// A sink whose files can't be written to.
+    struct BrokenSink;
+
+    struct BrokenFile;
+
+    impl Writer for BrokenFile {
+        fn write(&mut self, _: &[u8]) -> io::IoResult<()> {
+            Err(io::standard_error(io::OtherIoError))
+        }
+    }
+
+    impl OutputSink for BrokenSink {
+        fn create(&mut self, _: &Path) -> io::IoResult<Box<Writer:Send>> {
+            Ok(box BrokenFile as Box<Writer:Send>)
+        }
+
+        fn read(&mut self, _: &Path) -> io::IoResult<Option<Vec<u8>>> { Ok(None) }
+    }
+
+    #[test]
+    fn gzip_errors() {
+        let mut sink = GzipSink::new(box BrokenSink as Box<OutputSink>, true);
+        // Only the compressed copy is written, once the file is complete
+        write(&mut sink, &Path::new("a.html"), "a".as_bytes()).unwrap();
+        assert!(sink.finish().is_err());
+    }
+
+    #[test]
+    fn archive_errors() {
+        let dir = TempDir::new("rustdoc-archive").unwrap();
+        let mut sink = ArchiveSink::new(TarGz, dir.path().join("doc.tar.gz"));
+        let mut name = StrBuf::from_char(120, 'a');
+        name.push_str(".html");
+        write(&mut sink, &Path::new(name.as_slice()), "a".as_bytes()).unwrap();
+        assert!(sink.finish().is_err());
+    }
+}
@@ -11,6 +11,7 @@
 use collections::HashSet;
 use rustc::util::nodemap::NodeSet;
 use std::cmp;
+use std::io::File;
 use std::strbuf::StrBuf;
 use std::uint;
 use syntax::ast;
@@ -278,6 +279,67 @@ pub fn collapse_docs(krate: clean::Crate) -> plugins::PluginResult {
     (krate, None)
 }
 
+/// Appends the markdown files named by `#[doc(include = "file.md")]` to the
+/// docs of the items with the attribute, so that long docs can be kept in
+/// files of their own. Paths are relative to the directory of the source file
+/// the item is in. This runs once the doc comments are collapsed, which the
+/// files follow as they're written.
+pub fn include_docs(krate: clean::Crate) -> plugins::PluginResult {
+    struct Includer;
+    impl fold::DocFolder for Includer {
+        fn fold_item(&mut self, i: Item) -> Option<Item> {
+            let mut i = i;
+            let mut included = StrBuf::new();
+            for attr in i.attrs.iter() {
+                let list = match *attr {
+                    clean::List(ref x, ref list) if "doc" == x.as_slice() => list,
+                    _ => continue,
+                };
+                for inner in list.iter() {
+                    let file = match *inner {
+                        clean::NameValue(ref x, ref file) if "include" == x.as_slice() => file,
+                        _ => continue,
+                    };
+                    let dir = Path::new(i.source.filename.as_slice()).dir_path();
+                    let path = dir.join(file.as_slice());
+                    match File::open(&path).read_to_str() {
+                        Ok(s) => {
+                            if included.len() > 0 { included.push_char('\n') }
+                            included.push_str(s.as_slice());
+                        }
+                        Err(e) => {
+                            println!("warning: couldn't include `{}` in the docs: {}",
+                                     path.display(), e);
+                        }
+                    }
+                }
+            }
+            if included.len() > 0 {
+                let pos = i.attrs.iter().position(|a| match *a {
+                    clean::NameValue(ref x, _) => "doc" == x.as_slice(),
+                    _ => false,
+                });
+                match pos {
+                    Some(pos) => {
+                        let doc = match *i.attrs.get(pos) {
+                            clean::NameValue(_, ref s) => {
+                                format_strbuf!("{}\n\n{}", s.as_slice().trim_right(), included)
+                            }
+                            _ => unreachable!(),
+                        };
+                        *i.attrs.get_mut(pos) = clean::NameValue("doc".to_strbuf(), doc);
+                    }
+                    None => i.attrs.push(clean::NameValue("doc".to_strbuf(), included)),
+                }
+            }
+            self.fold_item_recur(i)
+        }
+    }
+    let mut includer = Includer;
+    let krate = includer.fold_crate(krate);
+    (krate, None)
+}
+
 pub fn unindent(s: &str) -> StrBuf {
     let lines = s.lines_any().collect::<Vec<&str> >();
     let mut saw_first_line = false;
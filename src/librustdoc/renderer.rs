@@ -0,0 +1,418 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Output backends for rustdoc
+//!
+//! Every output format rustdoc knows how to produce is implemented as a
+//! `Renderer`. The driver collects the command line configuration into a
+//! `RenderOptions` and hands it, along with the cleaned crate, to whichever
+//! renderer was selected.
+
+use collections::HashMap;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::strbuf::StrBuf;
+use std::sync::atomics::{AtomicBool, SeqCst};
+use sync::Arc;
+
+use clean;
+use externalfiles::{ExternalHtml, Theme};
+use html::epub::EpubRenderer;
+use html::render::{Cache, HtmlRenderer};
+use html::single::SinglePageRenderer;
+use output::OutputSink;
+use plugins::PluginJson;
+use text::diff::DiffRenderer;
+use text::json::{JsonRenderer, NdjsonRenderer};
+use text::man::ManRenderer;
+use text::markdown::MarkdownRenderer;
+use text::plain::TextRenderer;
+
+/// The names of all output formats built into rustdoc.
+pub static FORMATS: &'static [&'static str] = &["html", "single-html", "epub", "json",
+                                               "ndjson", "markdown", "man", "text",
+                                               "ansi", "diff"];
+
+/// The output format used when none is requested.
+pub static DEFAULT_FORMAT: &'static str = "html";
+
+/// Creates a fresh instance of a renderer registered with `register`.
+pub type RendererConstructor = fn() -> Box<Renderer>;
+
+local_data_key!(registry: RefCell<HashMap<StrBuf, RendererConstructor>>)
+
+/// Configuration shared by all of rustdoc's output backends.
+#[deriving(Clone)]
+pub struct RenderOptions {
+    /// Where the output should be placed within the `OutputSink`. Depending
+    /// on the backend this is either a directory or a single file.
+    pub dest: Path,
+    /// HTML supplied by the user to be injected into every generated page.
+    /// Backends which don't emit HTML are free to ignore this.
+    pub external_html: ExternalHtml,
+    /// Stylesheets supplied by the user to offer as themes, in addition to
+    /// any a backend has built in.
+    pub themes: Vec<Theme>,
+    /// The name of the theme to show the output in by default.
+    pub default_theme: Option<StrBuf>,
+    /// A template to lay out the pages of the output with, for backends
+    /// which have a template of their own.
+    pub template: Option<StrBuf>,
+    /// The version of the crate being documented, if one was given.
+    pub crate_version: Option<StrBuf>,
+    /// The URL the output is published at, for backends which can tell
+    /// search engines the canonical location of each page.
+    pub canonical_url: Option<StrBuf>,
+    /// The logo and favicon to brand the output with, overriding those the
+    /// crate gives. Each is either a URL or the path of a local file.
+    pub logo: Option<StrBuf>,
+    pub favicon: Option<StrBuf>,
+    /// Whether backends which know the URL the output is published at should
+    /// also write a sitemap and `robots.txt` for search engines.
+    pub sitemap: bool,
+    /// Output of the plugins which were run over the crate, for backends
+    /// which are able to record it.
+    pub plugin_output: Vec<PluginJson>,
+    /// An earlier version of the crate, for backends which compare against
+    /// one.
+    pub baseline: Option<clean::Crate>,
+    /// Whether backends which support it should only render the parts of
+    /// the output which changed since the last run into the same place.
+    pub incremental: bool,
+    /// Whether backends which render sources should also show the source of
+    /// short items where they're documented.
+    pub inline_source: bool,
+    /// Whether the output must not refer to anything on the network, so it
+    /// can be read offline. Images given by a local path are embedded.
+    pub offline_assets: bool,
+    /// Whether backends which emit HTML should shrink it by dropping comments
+    /// and needless whitespace.
+    pub minify: bool,
+    /// The URL of the script which typesets math in the browser, for
+    /// backends which leave math to one, if math is to be rendered.
+    pub math: Option<StrBuf>,
+    /// The width in characters past which backends which can wrap long
+    /// signatures do so.
+    pub signature_width: uint,
+    /// The order the items of each kind are listed in within a module.
+    pub item_order: ItemOrder,
+    /// How backends which write a page per item lay the pages out.
+    pub page_layout: PageLayout,
+    /// Where to report the progress of rendering to, if anywhere.
+    pub progress: Option<Sender<Progress>>,
+    /// Checked by renderers between pages, to stop early if set.
+    pub cancel: CancelToken,
+}
+
+impl RenderOptions {
+    /// Creates a set of options writing to `dest` with everything else left
+    /// at its default value.
+    pub fn new(dest: Path) -> RenderOptions {
+        RenderOptions {
+            dest: dest,
+            external_html: ExternalHtml::empty(),
+            themes: Vec::new(),
+            default_theme: None,
+            template: None,
+            crate_version: None,
+            canonical_url: None,
+            logo: None,
+            sitemap: false,
+            favicon: None,
+            plugin_output: Vec::new(),
+            baseline: None,
+            incremental: false,
+            inline_source: false,
+            offline_assets: false,
+            minify: false,
+            math: None,
+            signature_width: 100,
+            item_order: Alphabetical,
+            page_layout: Nested,
+            progress: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Reports the start of a new phase of rendering.
+    pub fn phase(&self, name: &'static str) {
+        self.report(Phase(name));
+    }
+
+    /// Reports that `done` of the `total` units of work a renderer has to do
+    /// are finished. Renderers call this between each unit, and should stop
+    /// with the returned error if rendering has been cancelled.
+    pub fn tick(&self, done: uint, total: uint) -> io::IoResult<()> {
+        self.report(Rendered(done, total));
+        if self.cancel.is_cancelled() { Err(cancelled()) } else { Ok(()) }
+    }
+
+    fn report(&self, progress: Progress) {
+        match self.progress {
+            // Whoever was listening may have lost interest, which is fine
+            Some(ref tx) => { let _ = tx.send_opt(progress); }
+            None => {}
+        }
+    }
+}
+
+/// The order in which the items of a module are listed. Items are grouped by
+/// their kind either way; this only orders the items within each group.
+#[deriving(Clone, Eq, Show)]
+pub enum ItemOrder {
+    /// Sorted by name
+    Alphabetical,
+    /// In the order they're declared in the source
+    SourceOrder,
+}
+
+/// How the pages of the items of a crate are laid out in the output.
+#[deriving(Clone, Eq, Show)]
+pub enum PageLayout {
+    /// A directory per module, holding the pages of the items within it, as
+    /// in `krate/a/b/struct.Foo.html`
+    Nested,
+    /// Every page in the directory of the crate, named after the path to the
+    /// module it's in, as in `krate/a.b.struct.Foo.html`
+    Flat,
+}
+
+/// Progress of a render, as sent to `RenderOptions::progress`.
+#[deriving(Clone, Show)]
+pub enum Progress {
+    /// Rendering has moved on to a new phase, such as a new output format.
+    Phase(&'static str),
+    /// The first number of units of work out of the second have been done.
+    /// For most backends, a unit of work is an item of the crate.
+    Rendered(uint, uint),
+}
+
+/// A flag which may be set from another task to cancel a render in progress.
+#[deriving(Clone)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken { flag: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Asks any renders using this token to stop as soon as possible.
+    pub fn cancel(&self) {
+        self.flag.store(true, SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(SeqCst)
+    }
+}
+
+/// The error a renderer fails with when it has been cancelled.
+pub fn cancelled() -> io::IoError {
+    io::IoError {
+        kind: io::OtherIoError,
+        desc: "rendering was cancelled",
+        detail: None,
+    }
+}
+
+/// Counts the units of work in rendering `item`: the item itself and,
+/// if it is a module, everything within it.
+pub fn count_items(item: &clean::Item) -> uint {
+    match item.inner {
+        clean::ModuleItem(ref m) => {
+            m.items.iter().fold(1, |n, i| n + count_items(i))
+        }
+        _ => 1,
+    }
+}
+
+/// An error which occurred while rendering documentation. Each backend may
+/// use its own error type, so long as it can describe itself to the driver.
+pub trait RenderError: fmt::Show {
+    /// A short description of the kind of failure which occurred.
+    fn description<'a>(&'a self) -> &'a str;
+}
+
+impl RenderError for io::IoError {
+    fn description<'a>(&'a self) -> &'a str { self.desc }
+}
+
+pub type RenderResult = Result<(), Box<RenderError>>;
+
+/// A backend which turns a cleaned crate into some form of documentation.
+///
+/// Several renderers may be run over the same crate in one invocation, so the
+/// crate handed to `render` is always the full cleaned crate and the `Cache`
+/// crawled from it is shared among all of them.
+///
+/// Rendering the same crate with the same options must give the same output
+/// byte-for-byte, so that it can be diffed and cached. Renderers thus write no
+/// timestamps, and sort anything they take from the cache's hash maps.
+pub trait Renderer {
+    /// The name this renderer is selected by, e.g. `html`.
+    fn name(&self) -> &'static str;
+
+    /// The name of the file this renderer writes its output to, or `None` if
+    /// it writes a tree of files into a directory.
+    fn output_filename(&self) -> Option<&'static str>;
+
+    /// Renders the documentation for `krate` as configured by `opts`,
+    /// creating all files through `sink`.
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult;
+}
+
+/// Boxes up a backend-specific error so it may be returned from `render`.
+pub fn error<E: RenderError + Send>(e: E) -> Box<RenderError> {
+    box e as Box<RenderError>
+}
+
+/// Makes an external renderer available under the output format `name`, so
+/// tools embedding rustdoc can add their own backends without patching the
+/// driver. A registered renderer takes precedence over a built-in one of the
+/// same name.
+///
+/// The registry is task-local, so renderers must be registered on the task
+/// which goes on to run `main_args`.
+pub fn register(name: &str, constructor: RendererConstructor) {
+    if registry.get().is_none() {
+        registry.replace(Some(RefCell::new(HashMap::new())));
+    }
+    let map = registry.get().unwrap();
+    map.borrow_mut().insert(name.to_strbuf(), constructor);
+}
+
+/// Returns the names of all available output formats, built-in ones first.
+pub fn formats() -> Vec<StrBuf> {
+    let mut ret: Vec<StrBuf> = FORMATS.iter().map(|s| s.to_strbuf()).collect();
+    match registry.get() {
+        Some(map) => {
+            let mut extra: Vec<StrBuf> = map.borrow().keys().filter(|k| {
+                !FORMATS.iter().any(|f| *f == k.as_slice())
+            }).map(|k| k.clone()).collect();
+            extra.sort();
+            ret.push_all_move(extra);
+        }
+        None => {}
+    }
+    ret
+}
+
+/// Looks up the renderer for an output format, as given to
+/// `--output-format`, consulting registered renderers before the built-in
+/// ones.
+pub fn find(name: &str) -> Option<Box<Renderer>> {
+    match registry.get() {
+        Some(map) => {
+            match map.borrow().find_equiv(&name) {
+                Some(constructor) => return Some((*constructor)()),
+                None => {}
+            }
+        }
+        None => {}
+    }
+    match name {
+        "html" => Some(box HtmlRenderer as Box<Renderer>),
+        "single-html" => Some(box SinglePageRenderer as Box<Renderer>),
+        "epub" => Some(box EpubRenderer as Box<Renderer>),
+        "json" => Some(box JsonRenderer as Box<Renderer>),
+        "ndjson" => Some(box NdjsonRenderer as Box<Renderer>),
+        "markdown" => Some(box MarkdownRenderer as Box<Renderer>),
+        "man" => Some(box ManRenderer as Box<Renderer>),
+        "text" => Some(box TextRenderer { color: false } as Box<Renderer>),
+        "ansi" => Some(box TextRenderer { color: true } as Box<Renderer>),
+        "diff" => Some(box DiffRenderer as Box<Renderer>),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::strbuf::StrBuf;
+    use sync::Arc;
+    use syntax::ast;
+
+    use clean;
+    use doctree;
+    use html::render::build_cache;
+    use output::{OutputSink, MemorySink};
+    use super::{RenderOptions, find};
+
+    fn item(name: &str, id: ast::NodeId, inner: clean::ItemEnum) -> clean::Item {
+        clean::Item {
+            source: clean::Span {
+                filename: "lib.rs".to_strbuf(),
+                loline: 1, locol: 0, hiline: 1, hicol: 0,
+            },
+            name: Some(name.to_strbuf()),
+            attrs: vec!(clean::NameValue("doc".to_strbuf(), "Docs".to_strbuf())),
+            inner: inner,
+            visibility: Some(ast::Public),
+            id: id,
+        }
+    }
+
+    fn module(name: &str, id: ast::NodeId, items: Vec<clean::Item>) -> clean::Item {
+        item(name, id, clean::ModuleItem(clean::Module { items: items, is_crate: false }))
+    }
+
+    fn field(name: &str, id: ast::NodeId) -> clean::Item {
+        item(name, id, clean::StructFieldItem(clean::TypedStructField(
+            clean::Primitive(ast::TyBool))))
+    }
+
+    /// Renders `krate` in `format` into memory, returning every file of the
+    /// output with its contents.
+    fn render(format: &str, krate: &clean::Crate) -> Vec<(StrBuf, Vec<u8>)> {
+        // Each cache has hash maps with keys of their own, so anything which
+        // depends on their order differs between the runs
+        let cache = Arc::new(build_cache(krate, &Path::new("doc"), false));
+        let mut opts = RenderOptions::new(Path::new("doc"));
+        opts.sitemap = true;
+        opts.canonical_url = Some("https://example.com/".to_strbuf());
+        let mut sink = MemorySink::new();
+        find(format).unwrap().render(krate.clone(), cache, &opts, &mut sink).unwrap();
+        sink.finish().unwrap();
+        sink.paths().move_iter().map(|path| {
+            let contents = sink.contents(path.as_slice()).unwrap();
+            (path, contents)
+        }).collect()
+    }
+
+    #[test]
+    fn same_output_across_runs() {
+        let strukt = item("S", 4, clean::StructItem(clean::Struct {
+            struct_type: doctree::Plain,
+            generics: clean::Generics { lifetimes: Vec::new(), type_params: Vec::new() },
+            fields: vec!(field("x", 5), field("y", 6), field("z", 7)),
+            fields_stripped: false,
+        }));
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", 0, vec!(module("a", 1, Vec::new()),
+                                                 module("b", 2, Vec::new()),
+                                                 module("c", 3, Vec::new()),
+                                                 strukt))),
+            externs: Vec::new(),
+        };
+        for format in ["html", "markdown"].iter() {
+            assert_eq!(render(*format, &krate), render(*format, &krate));
+        }
+        // The parts of the HTML output which are built from hash maps
+        let html = render("html", &krate);
+        for file in ["doc/search-index/krate/s.js", "doc/krate/sitemap.xml",
+                     "doc/krate/all.html"].iter() {
+            assert!(html.iter().any(|&(ref path, _)| path.as_slice() == *file));
+        }
+    }
+}
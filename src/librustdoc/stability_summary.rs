@@ -0,0 +1,179 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Summary of the stability of the items of a crate
+//!
+//! Counts how many items of each module are marked with each stability level,
+//! so that the progress of a crate towards being stable can be followed
+//! module by module.
+
+use std::strbuf::StrBuf;
+use syntax::attr;
+
+use clean;
+
+/// The number of items at each stability level.
+#[deriving(Clone, Eq, Show)]
+pub struct Counts {
+    pub deprecated: uint,
+    pub experimental: uint,
+    pub unstable: uint,
+    pub stable: uint,
+    pub frozen: uint,
+    pub locked: uint,
+    /// Items without a stability attribute
+    pub unmarked: uint,
+}
+
+impl Counts {
+    pub fn zero() -> Counts {
+        Counts {
+            deprecated: 0,
+            experimental: 0,
+            unstable: 0,
+            stable: 0,
+            frozen: 0,
+            locked: 0,
+            unmarked: 0,
+        }
+    }
+
+    /// Counts one more item at the stability level `level`.
+    pub fn record(&mut self, level: Option<attr::StabilityLevel>) {
+        match level {
+            Some(attr::Deprecated) => self.deprecated += 1,
+            Some(attr::Experimental) => self.experimental += 1,
+            Some(attr::Unstable) => self.unstable += 1,
+            Some(attr::Stable) => self.stable += 1,
+            Some(attr::Frozen) => self.frozen += 1,
+            Some(attr::Locked) => self.locked += 1,
+            None => self.unmarked += 1,
+        }
+    }
+
+    pub fn add(&self, other: &Counts) -> Counts {
+        Counts {
+            deprecated: self.deprecated + other.deprecated,
+            experimental: self.experimental + other.experimental,
+            unstable: self.unstable + other.unstable,
+            stable: self.stable + other.stable,
+            frozen: self.frozen + other.frozen,
+            locked: self.locked + other.locked,
+            unmarked: self.unmarked + other.unmarked,
+        }
+    }
+
+    /// The count of each level, along with its name, from the least stable
+    /// level to the most, followed by the count of unmarked items.
+    pub fn levels(&self) -> [(&'static str, uint), ..7] {
+        [("Deprecated", self.deprecated),
+         ("Experimental", self.experimental),
+         ("Unstable", self.unstable),
+         ("Stable", self.stable),
+         ("Frozen", self.frozen),
+         ("Locked", self.locked),
+         ("Unmarked", self.unmarked)]
+    }
+
+    pub fn total(&self) -> uint {
+        self.deprecated + self.experimental + self.unstable + self.stable +
+            self.frozen + self.locked + self.unmarked
+    }
+}
+
+/// The stability of the items of a module and of its submodules.
+pub struct ModuleSummary {
+    /// The path to the module, starting with the name of the crate
+    pub path: Vec<StrBuf>,
+    /// The counts of the items directly in the module
+    pub counts: Counts,
+    pub submodules: Vec<ModuleSummary>,
+}
+
+impl ModuleSummary {
+    /// The counts of the items in the module and all of its submodules.
+    pub fn total(&self) -> Counts {
+        self.submodules.iter().fold(self.counts.clone(), |c, m| c.add(&m.total()))
+    }
+}
+
+/// Summarizes the stability of the items of a crate. Impls aren't counted
+/// themselves, and neither are the fields, variants and methods of items.
+pub fn summarize(krate: &clean::Crate) -> ModuleSummary {
+    let mut summary = ModuleSummary {
+        path: vec!(krate.name.clone()),
+        counts: Counts::zero(),
+        submodules: Vec::new(),
+    };
+    match krate.module {
+        Some(clean::Item { inner: clean::ModuleItem(ref m), .. }) => {
+            summarize_module(&mut summary, m);
+        }
+        _ => {}
+    }
+    summary
+}
+
+fn summarize_module(summary: &mut ModuleSummary, m: &clean::Module) {
+    for item in m.items.iter() {
+        let name = match item.name {
+            Some(ref name) => name,
+            None => continue,
+        };
+        match item.inner {
+            clean::ModuleItem(ref m) => {
+                let mut path = summary.path.clone();
+                path.push(name.clone());
+                let mut sub = ModuleSummary {
+                    path: path,
+                    counts: Counts::zero(),
+                    submodules: Vec::new(),
+                };
+                summarize_module(&mut sub, m);
+                summary.submodules.push(sub);
+            }
+            clean::ImplItem(..) | clean::ViewItemItem(..) => {}
+            _ => summary.counts.record(item.stability().map(|s| s.level)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syntax::attr;
+    use super::{Counts, ModuleSummary};
+
+    fn module(name: &str, levels: &[Option<attr::StabilityLevel>],
+              submodules: Vec<ModuleSummary>) -> ModuleSummary {
+        let mut counts = Counts::zero();
+        for &level in levels.iter() {
+            counts.record(level);
+        }
+        ModuleSummary {
+            path: vec!(name.to_strbuf()),
+            counts: counts,
+            submodules: submodules,
+        }
+    }
+
+    #[test]
+    fn totals() {
+        let m = module("a", [Some(attr::Stable), None], vec!(
+            module("b", [Some(attr::Deprecated), Some(attr::Stable)], Vec::new()),
+            module("c", [], vec!(module("d", [Some(attr::Locked)], Vec::new())))));
+        let total = m.total();
+        assert_eq!(total.stable, 2);
+        assert_eq!(total.deprecated, 1);
+        assert_eq!(total.locked, 1);
+        assert_eq!(total.unmarked, 1);
+        assert_eq!(total.total(), 5);
+        assert_eq!(m.submodules.get(1).total().total(), 1);
+    }
+}
@@ -83,6 +83,7 @@ pub fn run(input: &str,
     let krate = v.clean();
     let (krate, _) = passes::unindent_comments(krate);
     let (krate, _) = passes::collapse_docs(krate);
+    let (krate, _) = passes::include_docs(krate);
 
     let mut collector = Collector::new(krate.name.to_strbuf(),
                                        libs,
@@ -0,0 +1,363 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! API diff rendering backend
+//!
+//! Compares the public API of a crate against a baseline version of the same
+//! crate, as loaded from the JSON output of an earlier run, and reports the
+//! items which were added, removed, or changed their signature or stability.
+//!
+//! `api` and `diff` are exposed separately from the renderer so tools
+//! checking compatibility between versions can work with the changes
+//! directly.
+
+use collections::TreeMap;
+use std::io;
+use std::io::BufferedWriter;
+use std::strbuf::StrBuf;
+use sync::Arc;
+use syntax::ast;
+use syntax::attr;
+
+use clean;
+use fold::DocFolder;
+use html::render::{Cache, HoardStripper, build_cache, cache_key, current_location_key};
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header, kind_name};
+
+/// Reports the differences between the public API of a crate and that of
+/// `RenderOptions::baseline`.
+pub struct DiffRenderer;
+
+impl Renderer for DiffRenderer {
+    fn name(&self) -> &'static str { "diff" }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("api-diff.txt") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// Everything about a public item which is part of the API.
+#[deriving(Clone, Eq, Show)]
+pub struct Entry {
+    pub kind: &'static str,
+    pub signature: Option<StrBuf>,
+    /// The stability level of the item, if it has one
+    pub stability: Option<StrBuf>,
+}
+
+/// The public API of a crate, keyed by the path and kind of each item.
+pub type Api = TreeMap<(StrBuf, &'static str), Entry>;
+
+/// A difference between two versions of an API.
+#[deriving(Clone, Eq, Show)]
+pub enum Change {
+    Added(StrBuf, Entry),
+    Removed(StrBuf, Entry),
+    /// The item at the path changed from the first entry to the second
+    Changed(StrBuf, Entry, Entry),
+}
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink) -> io::IoResult<()> {
+    let baseline = match opts.baseline {
+        Some(ref b) => b.clone(),
+        None => {
+            return Err(io::IoError {
+                kind: io::InvalidInput,
+                desc: "the diff output requires a baseline crate",
+                detail: None,
+            })
+        }
+    };
+    let name = krate.name.clone();
+    let new = api(krate, cache);
+    let old_cache = Arc::new(baseline_cache(&baseline));
+    let old = api(baseline, old_cache);
+    let changes = diff(&old, &new);
+    // Both versions are crawled in full before anything is written, so the
+    // progress is that of reporting the changes
+    try!(opts.tick(0, changes.len()));
+
+    let mut w = BufferedWriter::new(try!(sink.create(&opts.dest)));
+    try!(write!(&mut w, "API changes to {}\n\n", name));
+    if changes.len() == 0 {
+        try!(write!(&mut w, "No changes.\n"));
+    }
+    for (i, change) in changes.iter().enumerate() {
+        try!(opts.tick(i + 1, changes.len()));
+        match *change {
+            Added(ref path, ref e) => {
+                try!(write!(&mut w, "+ {} {}\n", e.kind, path));
+                try!(details(&mut w, "+", e));
+            }
+            Removed(ref path, ref e) => {
+                try!(write!(&mut w, "- {} {}\n", e.kind, path));
+                try!(details(&mut w, "-", e));
+            }
+            Changed(ref path, ref old, ref new) => {
+                try!(write!(&mut w, "~ {} {}\n", new.kind, path));
+                if old.signature != new.signature {
+                    try!(line(&mut w, "-", &old.signature));
+                    try!(line(&mut w, "+", &new.signature));
+                }
+                if old.stability != new.stability {
+                    try!(write!(&mut w, "    stability: {} -> {}\n",
+                                level(&old.stability), level(&new.stability)));
+                }
+            }
+        }
+    }
+    w.flush()
+}
+
+fn details(w: &mut Writer, sign: &str, e: &Entry) -> io::IoResult<()> {
+    try!(line(w, sign, &e.signature));
+    match e.stability {
+        Some(ref s) => write!(w, "    stability: {}\n", *s),
+        None => Ok(()),
+    }
+}
+
+fn line(w: &mut Writer, sign: &str, sig: &Option<StrBuf>) -> io::IoResult<()> {
+    match *sig {
+        Some(ref s) => write!(w, "  {} {}\n", sign, *s),
+        None => Ok(()),
+    }
+}
+
+fn level<'a>(s: &'a Option<StrBuf>) -> &'a str {
+    match *s {
+        Some(ref s) => s.as_slice(),
+        None => "none",
+    }
+}
+
+/// Builds the cache of the baseline crate. The analysis of the crate being
+/// documented is set aside meanwhile, since the public items and paths it
+/// knows of are those of the current version rather than the baseline.
+pub fn baseline_cache(baseline: &clean::Crate) -> Cache {
+    let analysis = ::analysiskey.replace(None);
+    let cache = build_cache(baseline, &Path::new("."), false);
+    ::analysiskey.replace(analysis);
+    cache
+}
+
+/// Collects the public API of a crate, along with the methods of the impls
+/// recorded for it in `cache`.
+pub fn api(krate: clean::Crate, cache: Arc<Cache>) -> Api {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut api = TreeMap::new();
+    match krate.module {
+        Some(mut item) => {
+            item.name = Some(krate.name);
+            collect(&mut api, &mut Vec::new(), &item);
+        }
+        None => {}
+    }
+    api
+}
+
+fn collect(api: &mut Api, current: &mut Vec<StrBuf>, item: &clean::Item) {
+    let name = match item.name {
+        Some(ref s) => s.clone(),
+        None => return,
+    };
+    current_location_key.replace(Some(current.clone()));
+    let mut path = current.clone();
+    path.push(name);
+    let path = path.connect("::");
+    insert(api, path.clone(), item);
+
+    match item.inner {
+        clean::ModuleItem(ref m) => {
+            current.push(item.name.get_ref().clone());
+            for it in m.items.iter() {
+                if it.visibility == Some(ast::Public) {
+                    collect(api, current, it);
+                }
+            }
+            current.pop();
+        }
+        clean::StructItem(ref s) => {
+            for f in s.fields.iter() {
+                if f.visibility == Some(ast::Public) {
+                    member(api, path.as_slice(), f);
+                }
+            }
+        }
+        clean::EnumItem(ref e) => {
+            for v in e.variants.iter() {
+                member(api, path.as_slice(), v);
+            }
+        }
+        clean::TraitItem(ref t) => {
+            for m in t.methods.iter() {
+                member(api, path.as_slice(), m.item());
+            }
+        }
+        _ => {}
+    }
+
+    let cache = cache_key.get().unwrap();
+    match cache.impls.find(&item.id) {
+        Some(impls) => {
+            for &(ref i, _) in impls.iter() {
+                let header = impl_header(i);
+                api.insert((header.clone(), "impl"), Entry {
+                    kind: "impl",
+                    signature: Some(header.clone()),
+                    stability: None,
+                });
+                let prefix = format!("<{}>", header);
+                for m in i.methods.iter() {
+                    // Methods of trait impls are public along with the trait
+                    if i.trait_.is_some() || m.visibility == Some(ast::Public) {
+                        member(api, prefix.as_slice(), m);
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+}
+
+/// Records a field, variant or method of the item at `parent`.
+fn member(api: &mut Api, parent: &str, item: &clean::Item) {
+    match item.name {
+        Some(ref name) => insert(api, format_strbuf!("{}::{}", parent, *name), item),
+        None => {}
+    }
+}
+
+fn insert(api: &mut Api, path: StrBuf, item: &clean::Item) {
+    let kind = kind_name(item);
+    api.insert((path, kind), Entry {
+        kind: kind,
+        signature: signature(item),
+        stability: attr::find_stability(item.attrs.iter())
+                        .map(|s| format_strbuf!("{}", s.level)),
+    });
+}
+
+/// Compares two versions of an API, returning the changes ordered by path.
+pub fn diff(old: &Api, new: &Api) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (&(ref path, kind), o) in old.iter() {
+        match new.find(&(path.clone(), kind)) {
+            Some(n) if n != o => {
+                changes.push(Changed(path.clone(), o.clone(), n.clone()));
+            }
+            Some(..) => {}
+            None => changes.push(Removed(path.clone(), o.clone())),
+        }
+    }
+    for (&(ref path, kind), n) in new.iter() {
+        if !old.contains_key(&(path.clone(), kind)) {
+            changes.push(Added(path.clone(), n.clone()));
+        }
+    }
+    changes.sort_by(|a, b| changed_path(a).cmp(&changed_path(b)));
+    changes
+}
+
+fn changed_path<'a>(c: &'a Change) -> &'a str {
+    match *c {
+        Added(ref p, _) | Removed(ref p, _) | Changed(ref p, _, _) => p.as_slice(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use collections::TreeMap;
+    use sync::Arc;
+    use syntax::ast;
+
+    use clean;
+    use super::{Api, Entry, Added, Removed, Changed, api, baseline_cache, diff};
+
+    fn entry(sig: &str, stability: Option<&str>) -> Entry {
+        Entry {
+            kind: "Function",
+            signature: Some(sig.to_strbuf()),
+            stability: stability.map(|s| s.to_strbuf()),
+        }
+    }
+
+    fn api(items: &[(&str, Entry)]) -> Api {
+        let mut api = TreeMap::new();
+        for &(path, ref e) in items.iter() {
+            api.insert((path.to_strbuf(), e.kind), e.clone());
+        }
+        api
+    }
+
+    #[test]
+    fn changes() {
+        let old = api([("a::f", entry("pub fn f()", None)),
+                       ("a::g", entry("pub fn g()", None)),
+                       ("a::h", entry("pub fn h()", Some("Unstable")))]);
+        let new = api([("a::e", entry("pub fn e()", None)),
+                       ("a::f", entry("pub fn f(x: int)", None)),
+                       ("a::h", entry("pub fn h()", Some("Stable")))]);
+        assert_eq!(diff(&old, &new), vec!(
+            Added("a::e".to_strbuf(), entry("pub fn e()", None)),
+            Changed("a::f".to_strbuf(), entry("pub fn f()", None),
+                    entry("pub fn f(x: int)", None)),
+            Removed("a::g".to_strbuf(), entry("pub fn g()", None)),
+            Changed("a::h".to_strbuf(), entry("pub fn h()", Some("Unstable")),
+                    entry("pub fn h()", Some("Stable")))));
+        assert_eq!(diff(&new, &new), Vec::new());
+    }
+
+    fn module(name: &str, id: ast::NodeId, visibility: ast::Visibility,
+              items: Vec<clean::Item>) -> clean::Item {
+        clean::Item {
+            source: clean::Span {
+                filename: "lib.rs".to_strbuf(),
+                loline: 1, locol: 0, hiline: 1, hicol: 0,
+            },
+            name: Some(name.to_strbuf()),
+            attrs: vec!(clean::NameValue("doc".to_strbuf(), "Docs".to_strbuf())),
+            inner: clean::ModuleItem(clean::Module { items: items, is_crate: false }),
+            visibility: Some(visibility),
+            id: id,
+        }
+    }
+
+    fn krate(visibility: ast::Visibility) -> clean::Crate {
+        clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", 0, ast::Public, vec!(
+                module("a", 1, ast::Public, vec!(module("b", 2, visibility, Vec::new())))))),
+            externs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn visibility_changes() {
+        let old = krate(ast::Public);
+        let old = api(old.clone(), Arc::new(baseline_cache(&old)));
+        let new = krate(ast::Inherited);
+        let new = api(new.clone(), Arc::new(baseline_cache(&new)));
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        match *changes.get(0) {
+            Removed(ref path, _) => assert_eq!(path.as_slice(), "krate::a::b"),
+            ref c => fail!("unexpected change {}", c),
+        }
+    }
+}
@@ -0,0 +1,574 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! JSON rendering backend
+//!
+//! Writes a single `doc.json` containing one object with the keys:
+//!
+//! * `format_version` - the version of this layout, see `FORMAT_VERSION`
+//! * `schema` - the version of the `clean` data structures in use
+//! * `crate` - the encoded `clean::Crate`, which can be read back in with
+//!   `--input-format json`
+//! * `plugins` - the output of any plugins which were run
+//! * `items` - a tree of the documented items, mirroring the hierarchy of
+//!   the HTML output, for consumption by other tools
+//! * `search_index` - the entries of the HTML search index, each an object
+//!   with the item's `kind`, `name`, module `path`, one-line summary `desc`,
+//!   whether it's `deprecated`, the other names it's found by as `aliases`
+//!   (from `#[doc(alias = "...")]`) and `parent`, the index into `paths` of the
+//!   type or trait a method belongs to (or `null`), and for functions and
+//!   methods the `sig`, an object with the names of the `inputs` and `output`
+//!   types as searched for (or `null`)
+//! * `paths` - the types and traits referred to by `search_index`, each an
+//!   object with their `kind` and full `path`
+//! * `external_paths` - the items of other crates which this crate refers
+//!   to, each an object with their `kind` and full `path`, and the name of
+//!   the `crate` they are in along with its documentation `location`: a URL,
+//!   `"local"` if it is documented next to this crate, or `null` if unknown
+//!
+//! Each node in the `items` tree is an object with the keys:
+//!
+//! * `name` - the item's name, or `null` for items such as impls
+//! * `kind` - the same short name of the item's kind used in HTML file names,
+//!   e.g. `struct` or `fn`
+//! * `path` - a list of the components of the item's full path
+//! * `visibility` - `"public"`, `"inherited"` or `null`
+//! * `docs` - the item's documentation as markdown, or `null`
+//! * `signature` - the item's declaration as plain text, or `null`
+//! * `stability` - `null`, or an object with the stability `level` and the
+//!   `text` given with it
+//! * `span` - an object with the source `file` and the `lo` and `hi`
+//!   positions in it, each a `[line, column]` pair
+//! * `items` - the module contents, fields, variants or trait methods
+//! * `impls` - for structs and enums only, a list of objects each with the
+//!   impl's `header`, whether it is a `trait` impl, its `docs` and its
+//!   method `items`
+//!
+//! For crates too large to comfortably hold the whole document in memory,
+//! the `ndjson` format writes `doc.ndjson` instead, which has one JSON object
+//! per line. The first line holds the `format_version` and `schema`, as
+//! above, and the `name` of the crate. Every following line is a node of the
+//! `items` tree, written out as soon as it is reached, without its `items`
+//! key. The nodes are written in depth-first order, so an item's contents
+//! immediately follow it and can be told apart by their `path`.
+
+use collections::TreeMap;
+use std::io;
+use std::io::{MemWriter, BufferedWriter};
+use std::str;
+use std::strbuf::StrBuf;
+use serialize::json;
+use serialize::json::ToJson;
+use serialize::Encodable;
+use sync::Arc;
+use syntax::ast;
+use syntax::ast_util;
+use syntax::attr;
+
+use clean;
+use clean::SCHEMA_VERSION;
+use fold::DocFolder;
+use html::item_type::{ItemType, shortty};
+use html::render::{Cache, HoardStripper, Remote, Local, Unknown};
+use html::render::{cache_key, current_location_key, search_paths};
+use output::OutputSink;
+use plugins::PluginJson;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header};
+
+/// The version of the layout of the JSON output, described in the module
+/// documentation. This is bumped whenever the layout changes at all, even if
+/// the change only adds new keys, so consumers can tell exactly what to expect.
+pub static FORMAT_VERSION: uint = 5;
+
+/// Renders the crate and the output of any plugins as a single json document.
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn name(&self) -> &'static str { "json" }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("doc.json") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        json_output(krate, cache, opts.plugin_output.clone(),
+                    sink, &opts.dest).map_err(renderer::error)
+    }
+}
+
+/// Renders the documented items as newline-delimited json, streaming each
+/// item out as it is reached.
+pub struct NdjsonRenderer;
+
+impl Renderer for NdjsonRenderer {
+    fn name(&self) -> &'static str { "ndjson" }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("doc.ndjson") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        ndjson_output(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// Outputs the crate/plugin json as a giant json blob at the specified
+/// destination.
+fn json_output(krate: clean::Crate, cache: Arc<Cache>, res: Vec<PluginJson>,
+               sink: &mut OutputSink, dst: &Path) -> io::IoResult<()> {
+    let json = document(krate, cache, res);
+    let mut file = try!(sink.create(dst));
+    try!(json.to_writer(&mut file));
+    Ok(())
+}
+
+/// Builds the JSON document for a crate and the output of its plugins.
+fn document(krate: clean::Crate, cache: Arc<Cache>,
+            res: Vec<PluginJson>) -> json::Json {
+    // {
+    //   "format_version": version,
+    //   "schema": version,
+    //   "crate": { parsed crate ... },
+    //   "plugins": { output of plugins ... },
+    //   "items": { tree of documented items ... },
+    //   "search_index": [ search index entries ... ],
+    //   "paths": [ parents of search index entries ... ],
+    //   "external_paths": [ items of other crates ... ]
+    // }
+    let mut json = box TreeMap::new();
+    json.insert("format_version".to_owned(), FORMAT_VERSION.to_json());
+    json.insert("schema".to_owned(), json::String(SCHEMA_VERSION.to_owned()));
+    let plugins_json = box res.move_iter()
+                              .filter_map(|opt| {
+                                  match opt {
+                                      None => None,
+                                      Some((string, json)) => {
+                                          Some((string.to_owned(), json))
+                                      }
+                                  }
+                              }).collect();
+
+    // FIXME #8335: yuck, Rust -> str -> JSON round trip! No way to .encode
+    // straight to the Rust JSON representation.
+    let crate_json_str = {
+        let mut w = MemWriter::new();
+        {
+            let mut encoder = json::Encoder::new(&mut w as &mut io::Writer);
+            krate.encode(&mut encoder).unwrap();
+        }
+        str::from_utf8(w.unwrap().as_slice()).unwrap().to_owned()
+    };
+    let crate_json = match json::from_str(crate_json_str) {
+        Ok(j) => j,
+        Err(e) => fail!("Rust generated JSON is invalid: {:?}", e)
+    };
+
+    json.insert("crate".to_owned(), crate_json);
+    json.insert("plugins".to_owned(), json::Object(plugins_json));
+    json.insert("search_index".to_owned(), search_index(&*cache));
+    json.insert("paths".to_owned(), paths(&*cache));
+    json.insert("external_paths".to_owned(), external_paths(&krate, &*cache));
+    json.insert("items".to_owned(), items(krate, cache));
+    json::Object(json)
+}
+
+fn ndjson_output(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+                 sink: &mut OutputSink) -> io::IoResult<()> {
+    let mut w = BufferedWriter::new(try!(sink.create(&opts.dest)));
+    let mut header = box TreeMap::new();
+    header.insert("format_version".to_owned(), FORMAT_VERSION.to_json());
+    header.insert("schema".to_owned(), json::String(SCHEMA_VERSION.to_owned()));
+    header.insert("name".to_owned(), krate.name.to_json());
+    try!(json::Object(header).to_writer(&mut w));
+    try!(w.write_str("\n"));
+
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    match krate.module {
+        Some(mut item) => {
+            item.name = Some(krate.name);
+            let mut progress = (0, renderer::count_items(&item));
+            try!(tick(opts, &mut progress));
+            try!(stream(&mut w, &mut Vec::new(), &item, opts, &mut progress));
+        }
+        None => {}
+    }
+    w.flush()
+}
+
+/// Writes out an item and then everything inside of it, one line each.
+/// `progress` is the number of items done so far and the total, counting the
+/// items of modules but not their fields, variants and methods.
+fn stream(w: &mut Writer, current: &mut Vec<StrBuf>, item: &clean::Item,
+          opts: &RenderOptions, progress: &mut (uint, uint)) -> io::IoResult<()> {
+    try!(json::Object(fields(current, item)).to_writer(w));
+    try!(w.write_str("\n"));
+    if item.is_mod() {
+        current.push(item.name.get_ref().clone());
+    }
+    for child in children(item).move_iter() {
+        if item.is_mod() {
+            try!(tick(opts, progress));
+        }
+        try!(stream(w, current, child, opts, progress));
+    }
+    if item.is_mod() {
+        current.pop();
+    }
+    Ok(())
+}
+
+fn tick(opts: &RenderOptions, progress: &mut (uint, uint)) -> io::IoResult<()> {
+    let (done, total) = *progress;
+    *progress = (done + 1, total);
+    opts.tick(done + 1, total)
+}
+
+/// Builds the tree of documented items. Like the HTML output, this only
+/// includes what is reachable from the crate root, with impls attached to
+/// the types they are for.
+fn items(krate: clean::Crate, cache: Arc<Cache>) -> json::Json {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    match krate.module {
+        Some(mut item) => {
+            item.name = Some(krate.name);
+            item_json(&mut Vec::new(), &item)
+        }
+        None => json::Null,
+    }
+}
+
+fn item_json(current: &mut Vec<StrBuf>, item: &clean::Item) -> json::Json {
+    let mut obj = fields(current, item);
+    if item.is_mod() {
+        current.push(item.name.get_ref().clone());
+    }
+    let children = children(item).move_iter().map(|i| item_json(current, i))
+                                 .collect();
+    if item.is_mod() {
+        current.pop();
+    }
+    obj.insert("items".to_owned(), json::List(children));
+    json::Object(obj)
+}
+
+/// Serializes everything about an item except for the items it contains.
+fn fields(current: &mut Vec<StrBuf>, item: &clean::Item) -> Box<json::Object> {
+    // The signature printers need to know which module we're in
+    current_location_key.replace(Some(current.clone()));
+    let mut path = current.clone();
+    match item.name {
+        Some(ref name) => path.push(name.clone()),
+        None => {}
+    }
+
+    let mut obj = box TreeMap::new();
+    obj.insert("name".to_owned(), item.name.to_json());
+    obj.insert("kind".to_owned(),
+               shortty(item).to_static_str().to_owned().to_json());
+    obj.insert("path".to_owned(), path.to_json());
+    obj.insert("visibility".to_owned(), match item.visibility {
+        Some(ast::Public) => "public".to_owned().to_json(),
+        Some(ast::Inherited) => "inherited".to_owned().to_json(),
+        None => json::Null,
+    });
+    obj.insert("docs".to_owned(), item.doc_value().map(|s| s.to_owned()).to_json());
+    obj.insert("signature".to_owned(), signature(item).to_json());
+    obj.insert("stability".to_owned(), stability(item));
+    obj.insert("span".to_owned(), span(&item.source));
+
+    match item.inner {
+        clean::StructItem(..) | clean::EnumItem(..) => {
+            obj.insert("impls".to_owned(), impls(current, item));
+        }
+        _ => {}
+    }
+    obj
+}
+
+/// The items contained in an item: the contents of a module, the fields of a
+/// struct, the variants of an enum or the methods of a trait.
+fn children<'a>(item: &'a clean::Item) -> Vec<&'a clean::Item> {
+    match item.inner {
+        clean::ModuleItem(ref m) => m.items.iter().collect(),
+        clean::StructItem(ref s) => s.fields.iter().collect(),
+        clean::EnumItem(ref e) => e.variants.iter().collect(),
+        clean::TraitItem(ref t) => t.methods.iter().map(|m| m.item()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Serializes the inherent and trait impls recorded in the cache for a type.
+fn impls(current: &mut Vec<StrBuf>, item: &clean::Item) -> json::Json {
+    let cache = cache_key.get().unwrap();
+    let impls = match cache.impls.find(&item.id) {
+        Some(v) => v,
+        None => return json::List(Vec::new()),
+    };
+    json::List(impls.iter().map(|&(ref i, ref dox)| {
+        current_location_key.replace(Some(current.clone()));
+        let mut obj = box TreeMap::new();
+        obj.insert("header".to_owned(), impl_header(i).to_json());
+        obj.insert("trait".to_owned(), i.trait_.is_some().to_json());
+        obj.insert("docs".to_owned(), dox.to_json());
+        obj.insert("items".to_owned(), json::List(i.methods.iter().map(|m| {
+            item_json(current, m)
+        }).collect()));
+        json::Object(obj)
+    }).collect())
+}
+
+/// Serializes the search index built up while crawling the crate, with the
+/// parents of methods referring to entries of `paths`.
+fn search_index(cache: &Cache) -> json::Json {
+    let (nodeid_to_pathid, _) = search_paths(cache);
+    json::List(cache.search_index.iter().map(|item| {
+        let mut obj = box TreeMap::new();
+        obj.insert("kind".to_owned(), item.ty.to_static_str().to_owned().to_json());
+        obj.insert("name".to_owned(), item.name.to_json());
+        obj.insert("path".to_owned(), item.path.to_json());
+        obj.insert("desc".to_owned(), item.desc.to_json());
+        obj.insert("deprecated".to_owned(), item.deprecated.to_json());
+        obj.insert("aliases".to_owned(), item.aliases.to_json());
+        obj.insert("parent".to_owned(), item.parent.map(|nodeid| {
+            *nodeid_to_pathid.find(&nodeid).unwrap()
+        }).to_json());
+        obj.insert("sig".to_owned(), match item.sig {
+            Some(ref sig) => {
+                let mut o = box TreeMap::new();
+                o.insert("inputs".to_owned(), sig.inputs.to_json());
+                o.insert("output".to_owned(), sig.output.to_json());
+                json::Object(o)
+            }
+            None => json::Null,
+        });
+        json::Object(obj)
+    }).collect())
+}
+
+/// Serializes the paths of the parents in the search index, in the same order
+/// as the HTML search index.
+fn paths(cache: &Cache) -> json::Json {
+    let (_, pathid_to_nodeid) = search_paths(cache);
+    json::List(pathid_to_nodeid.iter().map(|&nodeid| {
+        let &(ref fqp, short) = cache.paths.find(&ast_util::local_def(nodeid)).unwrap();
+        json::Object(path(fqp.as_slice(), short))
+    }).collect())
+}
+
+/// Serializes the paths of all items in other crates which are known to the
+/// cache, sorted by path.
+fn external_paths(krate: &clean::Crate, cache: &Cache) -> json::Json {
+    let mut paths = cache.paths.iter().filter(|&(did, _)| {
+        did.krate != ast::LOCAL_CRATE
+    }).collect::<Vec<_>>();
+    // Ties are broken by id, as the cache's map has no order of its own
+    paths.sort_by(|&(a, &(ref p1, _)), &(b, &(ref p2, _))| {
+        (p1, a.krate, a.node).cmp(&(p2, b.krate, b.node))
+    });
+    json::List(paths.move_iter().map(|(did, &(ref fqp, short))| {
+        let name = krate.externs.iter().find(|&&(n, _)| n == did.krate)
+                                .map(|&(_, ref e)| e.name.clone());
+        let location = match cache.extern_locations.find(&did.krate) {
+            Some(&Remote(ref s)) => s.to_json(),
+            Some(&Local) => "local".to_owned().to_json(),
+            Some(&Unknown) | None => json::Null,
+        };
+        let mut obj = path(fqp.as_slice(), short);
+        obj.insert("crate".to_owned(), name.to_json());
+        obj.insert("location".to_owned(), location);
+        json::Object(obj)
+    }).collect())
+}
+
+fn path(fqp: &[StrBuf], short: ItemType) -> Box<json::Object> {
+    let mut obj = box TreeMap::new();
+    obj.insert("kind".to_owned(), short.to_static_str().to_owned().to_json());
+    obj.insert("path".to_owned(), Vec::from_slice(fqp).to_json());
+    obj
+}
+
+fn stability(item: &clean::Item) -> json::Json {
+    match attr::find_stability(item.attrs.iter()) {
+        Some(stability) => {
+            let mut obj = box TreeMap::new();
+            obj.insert("level".to_owned(),
+                       stability.level.to_str().to_json());
+            obj.insert("text".to_owned(),
+                       stability.text.map(|s| s.get().to_owned()).to_json());
+            json::Object(obj)
+        }
+        None => json::Null,
+    }
+}
+
+fn span(span: &clean::Span) -> json::Json {
+    let mut obj = box TreeMap::new();
+    obj.insert("file".to_owned(), span.filename.to_json());
+    obj.insert("lo".to_owned(),
+               json::List(vec!(span.loline.to_json(), span.locol.to_json())));
+    obj.insert("hi".to_owned(),
+               json::List(vec!(span.hiline.to_json(), span.hicol.to_json())));
+    json::Object(obj)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{document, item_json, search_index, paths};
+    use serialize::json;
+    use std::strbuf::StrBuf;
+    use sync::Arc;
+    use syntax::ast;
+
+    use clean;
+    use doctree;
+    use html::render::{build_cache, cache_key};
+
+    // These tests pin down the layout of the output for the current
+    // `FORMAT_VERSION`. If one of them fails because the layout changed, bump
+    // `FORMAT_VERSION`, update the module documentation, and then update the
+    // expectations here.
+
+    fn keys(json: json::Json) -> Vec<StrBuf> {
+        match json {
+            json::Object(obj) => obj.keys().map(|k| k.to_strbuf()).collect(),
+            _ => fail!("expected an object"),
+        }
+    }
+
+    fn module(name: &str, items: Vec<clean::Item>) -> clean::Item {
+        clean::Item {
+            source: clean::Span {
+                filename: "lib.rs".to_strbuf(),
+                loline: 1, locol: 0, hiline: 1, hicol: 0,
+            },
+            name: Some(name.to_strbuf()),
+            attrs: Vec::new(),
+            inner: clean::ModuleItem(clean::Module { items: items, is_crate: false }),
+            visibility: Some(ast::Public),
+            id: 0,
+        }
+    }
+
+    #[test]
+    fn item_layout() {
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: None,
+            externs: Vec::new(),
+        };
+        cache_key.replace(Some(Arc::new(build_cache(&krate, &Path::new("."), false))));
+        let mut item = module("a", vec!(module("b", Vec::new())));
+        item.source = clean::Span {
+            filename: "a.rs".to_strbuf(),
+            loline: 1, locol: 2, hiline: 3, hicol: 4,
+        };
+        item.attrs = vec!(clean::NameValue("doc".to_strbuf(), "Docs".to_strbuf()),
+                          clean::NameValue("stable".to_strbuf(), "since 0.10".to_strbuf()));
+        let json = item_json(&mut vec!("krate".to_strbuf()), &item).to_str();
+        assert_eq!(json.as_slice(), concat!(
+            "{\"docs\":\"Docs\",",
+            "\"items\":[{\"docs\":null,\"items\":[],\"kind\":\"mod\",\"name\":\"b\",",
+            "\"path\":[\"krate\",\"a\",\"b\"],\"signature\":\"pub mod b\",",
+            "\"span\":{\"file\":\"lib.rs\",\"hi\":[1,0],\"lo\":[1,0]},",
+            "\"stability\":null,\"visibility\":\"public\"}],",
+            "\"kind\":\"mod\",\"name\":\"a\",\"path\":[\"krate\",\"a\"],",
+            "\"signature\":\"pub mod a\",",
+            "\"span\":{\"file\":\"a.rs\",\"hi\":[3,4],\"lo\":[1,2]},",
+            "\"stability\":{\"level\":\"Stable\",\"text\":\"since 0.10\"},",
+            "\"visibility\":\"public\"}"));
+    }
+
+    #[test]
+    fn top_level_keys() {
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", Vec::new())),
+            externs: Vec::new(),
+        };
+        let cache = Arc::new(build_cache(&krate, &Path::new("."), false));
+        let keys = keys(document(krate, cache, Vec::new()));
+        let expected = ["crate", "external_paths", "format_version", "items",
+                        "paths", "plugins", "schema", "search_index"];
+        assert_eq!(keys, expected.iter().map(|s| s.to_strbuf()).collect());
+    }
+
+    #[test]
+    fn same_output_across_runs() {
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", vec!(module("a", Vec::new()), module("b", Vec::new()),
+                                              module("c", Vec::new())))),
+            externs: Vec::new(),
+        };
+        // Each cache has hash maps with keys of their own, so anything which
+        // depends on their order differs between the runs
+        let run = || {
+            let cache = Arc::new(build_cache(&krate, &Path::new("."), false));
+            document(krate.clone(), cache, Vec::new()).to_str()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn item_keys() {
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: None,
+            externs: Vec::new(),
+        };
+        cache_key.replace(Some(Arc::new(build_cache(&krate, &Path::new("."), false))));
+        let keys = keys(item_json(&mut Vec::new(), &module("a", Vec::new())));
+        let expected = ["docs", "items", "kind", "name", "path", "signature",
+                        "span", "stability", "visibility"];
+        assert_eq!(keys, expected.iter().map(|s| s.to_strbuf()).collect());
+    }
+
+    #[test]
+    fn index_entry_keys() {
+        let field = clean::Item {
+            name: Some("x".to_strbuf()),
+            inner: clean::StructFieldItem(clean::TypedStructField(clean::Primitive(ast::TyBool))),
+            id: 2,
+            .. module("x", Vec::new())
+        };
+        let strukt = clean::Item {
+            name: Some("S".to_strbuf()),
+            inner: clean::StructItem(clean::Struct {
+                struct_type: doctree::Plain,
+                generics: clean::Generics { lifetimes: Vec::new(), type_params: Vec::new() },
+                fields: vec!(field),
+                fields_stripped: false,
+            }),
+            id: 1,
+            .. module("S", Vec::new())
+        };
+        let krate = clean::Crate {
+            name: "krate".to_strbuf(),
+            module: Some(module("krate", vec!(strukt))),
+            externs: Vec::new(),
+        };
+        let cache = build_cache(&krate, &Path::new("."), false);
+        let entry = match search_index(&cache) {
+            json::List(list) => list.move_iter().last().unwrap(),
+            _ => fail!("expected a list"),
+        };
+        let expected = ["aliases", "deprecated", "desc", "kind", "name", "parent", "path",
+                        "sig"];
+        assert_eq!(keys(entry), expected.iter().map(|s| s.to_strbuf()).collect());
+        let entry = match paths(&cache) {
+            json::List(list) => list.move_iter().next().unwrap(),
+            _ => fail!("expected a list"),
+        };
+        let expected = ["kind", "path"];
+        assert_eq!(keys(entry), expected.iter().map(|s| s.to_strbuf()).collect());
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Man page rendering backend
+//!
+//! Renders one roff page per module into `<dest>/man3`, named after the
+//! module's full path (e.g. `std::vec.3`). Each page has the customary NAME,
+//! SYNOPSIS and DESCRIPTION sections, followed by a section per kind of item
+//! in the module with each item's signature and documentation.
+
+use std::fmt;
+use std::io;
+use std::io::BufferedWriter;
+use std::strbuf::StrBuf;
+use sync::Arc;
+
+use clean;
+use fold::DocFolder;
+use html::item_type;
+use html::item_type::{ItemType, shortty};
+use html::render::{Cache, HoardStripper, cache_key, current_location_key};
+use html::render;
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header};
+
+/// Renders a crate as a set of man pages, one per module.
+pub struct ManRenderer;
+
+impl Renderer for ManRenderer {
+    fn name(&self) -> &'static str { "man" }
+
+    fn output_filename(&self) -> Option<&'static str> { None }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// The sections of a module's page, in order, for each kind of item.
+static SECTIONS: &'static [(ItemType, &'static str)] = &[
+    (item_type::Macro, "MACROS"),
+    (item_type::Struct, "STRUCTS"),
+    (item_type::Enum, "ENUMS"),
+    (item_type::Static, "STATICS"),
+    (item_type::ForeignFunction, "FOREIGN FUNCTIONS"),
+    (item_type::ForeignStatic, "FOREIGN STATICS"),
+    (item_type::Trait, "TRAITS"),
+    (item_type::Function, "FUNCTIONS"),
+    (item_type::Typedef, "TYPE DEFINITIONS"),
+];
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink) -> io::IoResult<()> {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut item = match krate.module {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    item.name = Some(krate.name);
+
+    let dst = opts.dest.join("man3");
+    let version = opts.crate_version.as_ref().map(|s| s.as_slice()).unwrap_or("");
+    let mut current = Vec::new();
+    let mut progress = (0, renderer::count_items(&item));
+    module(sink, &dst, version, &mut current, &item, opts, &mut progress)
+}
+
+/// Writes the page of a module and then those of its submodules. `progress`
+/// is the number of items done so far and the total.
+fn module(sink: &mut OutputSink, dst: &Path, version: &str, current: &mut Vec<StrBuf>,
+          item: &clean::Item, opts: &RenderOptions,
+          progress: &mut (uint, uint)) -> io::IoResult<()> {
+    let m = match item.inner {
+        clean::ModuleItem(ref m) => m,
+        _ => return Ok(()),
+    };
+    current.push(item.name.get_ref().clone());
+    current_location_key.replace(Some(current.clone()));
+    let name = current.connect("::");
+
+    let file = dst.join(format!("{}.3", name));
+    let mut w = BufferedWriter::new(try!(sink.create(&file)));
+    try!(write!(&mut w, ".TH \"{}\" 3 \"\" \"{}\" \"Rust Library Documentation\"\n",
+                Roff(name.to_upper().as_slice()), Roff(version)));
+
+    try!(write!(&mut w, ".SH NAME\n{}", Roff(name.as_slice())));
+    let summary = render::shorter(item.doc_value());
+    if summary.len() > 0 {
+        try!(write!(&mut w, " \\\\- {}",
+                    Roff(summary.words().collect::<Vec<&str>>().connect(" ").as_slice())));
+    }
+    try!(write!(&mut w, "\n"));
+
+    // The synopsis lists the signatures of everything documented on the page
+    let items = m.items.iter().filter(|i| {
+        SECTIONS.iter().any(|&(ty, _)| ty == shortty(*i))
+    }).collect::<Vec<&clean::Item>>();
+    if items.len() > 0 {
+        try!(write!(&mut w, ".SH SYNOPSIS\n.nf\n"));
+        for it in items.iter() {
+            match signature(*it) {
+                Some(sig) => try!(write!(&mut w, "{}\n", Roff(sig.as_slice()))),
+                None => {}
+            }
+        }
+        try!(write!(&mut w, ".fi\n"));
+    }
+
+    match item.doc_value() {
+        Some(s) => {
+            try!(write!(&mut w, ".SH DESCRIPTION\n"));
+            try!(paragraphs(&mut w, s));
+        }
+        None => {}
+    }
+
+    for &(ty, header) in SECTIONS.iter() {
+        let mut items = m.items.iter().filter(|i| shortty(*i) == ty)
+                               .collect::<Vec<&clean::Item>>();
+        if items.len() == 0 { continue }
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        try!(write!(&mut w, ".SH {}\n", header));
+        for it in items.iter() {
+            try!(entry(&mut w, *it));
+        }
+    }
+
+    let mut submodules = m.items.iter().filter(|i| i.is_mod())
+                                .collect::<Vec<&clean::Item>>();
+    submodules.sort_by(|a, b| a.name.cmp(&b.name));
+    if submodules.len() > 0 {
+        try!(write!(&mut w, ".SH SEE ALSO\n"));
+        for (i, sub) in submodules.iter().enumerate() {
+            try!(write!(&mut w, "{}.BR {}::{} (3)\n",
+                        if i > 0 { ".br\n" } else { "" },
+                        Roff(name.as_slice()),
+                        Roff(sub.name.get_ref().as_slice())));
+        }
+    }
+    try!(w.flush());
+
+    // The page covers the module and all of its items but its submodules
+    let (done, total) = *progress;
+    let done = done + 1 + m.items.iter().filter(|i| !i.is_mod()).count();
+    *progress = (done, total);
+    try!(opts.tick(done, total));
+
+    for sub in submodules.iter() {
+        try!(module(sink, dst, version, current, *sub, opts, progress));
+    }
+    current.pop();
+    Ok(())
+}
+
+/// Writes the documentation of a single item in a module's page, along with
+/// its members and methods.
+fn entry(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    try!(write!(w, ".SS {}\n", Roff(item.name.get_ref().as_slice())));
+    try!(documented(w, item));
+
+    let members = match item.inner {
+        clean::StructItem(ref s) => s.fields.as_slice(),
+        clean::EnumItem(ref e) => e.variants.as_slice(),
+        _ => &[],
+    };
+    for member in members.iter() {
+        try!(member_entry(w, member));
+    }
+
+    match item.inner {
+        clean::TraitItem(ref t) => {
+            for m in t.methods.iter() {
+                try!(member_entry(w, m.item()));
+            }
+        }
+        _ => {}
+    }
+
+    let cache = cache_key.get().unwrap();
+    match cache.impls.find(&item.id) {
+        Some(impls) => {
+            for &(ref i, _) in impls.iter() {
+                try!(write!(w, ".TP\n.B {}\n", Roff(impl_header(i).as_slice())));
+                for m in i.methods.iter() {
+                    try!(member_entry(w, m));
+                }
+            }
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Writes a field, variant or method as an indented paragraph.
+fn member_entry(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    if signature(item).is_none() { return Ok(()) }
+    try!(write!(w, ".RS\n"));
+    try!(documented(w, item));
+    write!(w, ".RE\n")
+}
+
+/// Writes an item's signature in a no-fill block followed by its docs.
+fn documented(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    match signature(item) {
+        Some(sig) => try!(write!(w, ".nf\n.B {}\n.fi\n", Roff(sig.as_slice()))),
+        None => {}
+    }
+    match item.doc_value() {
+        Some(s) => paragraphs(w, s),
+        None => Ok(())
+    }
+}
+
+/// Writes documentation text, starting a new roff paragraph at each blank
+/// line and preserving code blocks verbatim.
+fn paragraphs(w: &mut Writer, s: &str) -> io::IoResult<()> {
+    let mut in_code = false;
+    try!(write!(w, ".PP\n"));
+    for line in s.lines() {
+        if line.trim_left().starts_with("```") || line.trim_left().starts_with("~~~") {
+            in_code = !in_code;
+            try!(write!(w, "{}\n", if in_code { ".nf\n.RS" } else { ".RE\n.fi" }));
+        } else if !in_code && line.trim().len() == 0 {
+            try!(write!(w, ".PP\n"));
+        } else {
+            try!(write!(w, "{}\n", Roff(line)));
+        }
+    }
+    if in_code {
+        try!(write!(w, ".RE\n.fi\n"));
+    }
+    Ok(())
+}
+
+/// Wrapper struct which escapes text for inclusion in a roff document.
+struct Roff<'a>(&'a str);
+
+impl<'a> fmt::Show for Roff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Roff(s) = *self;
+        // A leading control character would be interpreted as a request
+        if s.starts_with(".") || s.starts_with("'") {
+            try!(f.buf.write("\\&".as_bytes()));
+        }
+        for c in s.chars() {
+            match c {
+                '\\' => try!(f.buf.write("\\e".as_bytes())),
+                '-' => try!(f.buf.write("\\-".as_bytes())),
+                c => try!(f.buf.write_char(c)),
+            }
+        }
+        Ok(())
+    }
+}
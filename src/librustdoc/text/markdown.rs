@@ -0,0 +1,292 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Markdown rendering backend
+//!
+//! Renders a crate to a tree of markdown files which mirrors the hierarchy of
+//! the HTML output: every module becomes a directory with an `index.md`, and
+//! every other named item gets its own `<type>.<name>.md` file next to it.
+//! Documentation is already written in markdown, so it's emitted verbatim,
+//! which makes the output suitable for feeding into wikis and static site
+//! generators.
+
+use std::io;
+use std::io::BufferedWriter;
+use std::strbuf::StrBuf;
+use sync::Arc;
+
+use clean;
+use fold::DocFolder;
+use html::format::VisSpace;
+use html::escape::StripTags;
+use html::item_type;
+use html::item_type::{ItemType, shortty};
+use html::render::{Cache, HoardStripper, cache_key, current_location_key};
+use html::render;
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header, kind_name};
+
+/// Renders a crate as a tree of markdown files.
+pub struct MarkdownRenderer;
+
+impl Renderer for MarkdownRenderer {
+    fn name(&self) -> &'static str { "markdown" }
+
+    fn output_filename(&self) -> Option<&'static str> { None }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink).map_err(renderer::error)
+    }
+}
+
+/// The order in which groups of items are listed on a module's page, along
+/// with the heading of each group.
+static SECTIONS: &'static [(ItemType, &'static str)] = &[
+    (item_type::ViewItem, "Reexports"),
+    (item_type::Module, "Modules"),
+    (item_type::Macro, "Macros"),
+    (item_type::Struct, "Structs"),
+    (item_type::Enum, "Enums"),
+    (item_type::Static, "Statics"),
+    (item_type::ForeignFunction, "Foreign Functions"),
+    (item_type::ForeignStatic, "Foreign Statics"),
+    (item_type::Trait, "Traits"),
+    (item_type::Function, "Functions"),
+    (item_type::Typedef, "Type Definitions"),
+];
+
+struct Context<'a> {
+    /// Path of the module currently being rendered
+    current: Vec<StrBuf>,
+    /// Directory the current module's files are placed into
+    dst: Path,
+    opts: &'a RenderOptions,
+    sink: &'a mut OutputSink,
+    /// Number of items rendered so far, out of `total`
+    done: uint,
+    total: uint,
+}
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink) -> io::IoResult<()> {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut item = match krate.module {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    item.name = Some(krate.name);
+
+    let mut cx = Context {
+        current: Vec::new(),
+        dst: opts.dest.clone(),
+        opts: opts,
+        sink: sink,
+        done: 0,
+        total: renderer::count_items(&item),
+    };
+    cx.item(&item)
+}
+
+impl<'a> Context<'a> {
+    fn item(&mut self, item: &clean::Item) -> io::IoResult<()> {
+        self.done += 1;
+        try!(self.opts.tick(self.done, self.total));
+        let name = match item.name {
+            Some(ref s) => s.clone(),
+            None => return Ok(()),
+        };
+        match item.inner {
+            clean::ModuleItem(ref m) => {
+                self.current.push(name.clone());
+                self.dst.push(name.as_slice());
+                let dst = self.dst.join("index.md");
+                try!(self.page(dst, item));
+                for it in m.items.iter() {
+                    try!(self.item(it));
+                }
+                self.dst.pop();
+                self.current.pop();
+                Ok(())
+            }
+            clean::ViewItemItem(..) => Ok(()),
+            _ => {
+                let dst = self.dst.join(item_path(item));
+                self.page(dst, item)
+            }
+        }
+    }
+
+    fn page(&mut self, dst: Path, item: &clean::Item) -> io::IoResult<()> {
+        current_location_key.replace(Some(self.current.clone()));
+
+        let mut w = BufferedWriter::new(try!(self.sink.create(&dst)));
+        let mut path = self.current.clone();
+        if !item.is_mod() {
+            path.push(item.name.get_ref().clone());
+        }
+        try!(write!(&mut w, "\\# {} `{}`\n\n", kind_name(item),
+                    path.connect("::")));
+        match signature(item) {
+            Some(ref sig) if !item.is_mod() => {
+                try!(write!(&mut w, "```rust\n{}\n```\n\n", *sig));
+            }
+            Some(..) | None => {}
+        }
+        try!(document(&mut w, item));
+
+        match item.inner {
+            clean::ModuleItem(ref m) => try!(module(&mut w, m.items.as_slice())),
+            clean::StructItem(ref s) => {
+                try!(members(&mut w, "Fields", s.fields.as_slice()));
+                try!(methods(&mut w, item));
+            }
+            clean::EnumItem(ref e) => {
+                try!(members(&mut w, "Variants", e.variants.as_slice()));
+                try!(methods(&mut w, item));
+            }
+            clean::TraitItem(ref t) => {
+                let required = t.methods.iter().filter(|m| m.is_req())
+                                        .collect::<Vec<&clean::TraitMethod>>();
+                let provided = t.methods.iter().filter(|m| !m.is_req())
+                                        .collect::<Vec<&clean::TraitMethod>>();
+                try!(trait_methods(&mut w, "Required Methods", required.as_slice()));
+                try!(trait_methods(&mut w, "Provided Methods", provided.as_slice()));
+            }
+            _ => {}
+        }
+        w.flush()
+    }
+}
+
+/// The file an item's page is rendered to, relative to its module.
+fn item_path(item: &clean::Item) -> StrBuf {
+    match item.inner {
+        clean::ModuleItem(..) => format_strbuf!("{}/index.md", *item.name.get_ref()),
+        _ => format_strbuf!("{}.{}.md", shortty(item), *item.name.get_ref()),
+    }
+}
+
+fn document(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    match item.doc_value() {
+        Some(s) => write!(w, "{}\n\n", s.trim()),
+        None => Ok(())
+    }
+}
+
+/// Writes the single-line summary of an item's documentation, if it has any.
+fn summary(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    let s = render::shorter(item.doc_value());
+    if s.len() == 0 { return Ok(()) }
+    write!(w, " - {}", s.words().collect::<Vec<&str>>().connect(" "))
+}
+
+fn module(w: &mut Writer, items: &[clean::Item]) -> io::IoResult<()> {
+    for &(ty, header) in SECTIONS.iter() {
+        let mut items = items.iter().filter(|i| shortty(*i) == ty).collect::<Vec<_>>();
+        if items.len() == 0 { continue }
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+
+        try!(write!(w, "\\#\\# {}\n\n", header));
+        for item in items.iter() {
+            match item.inner {
+                clean::ViewItemItem(ref v) => {
+                    let html = match v.inner {
+                        clean::ExternCrate(ref name, _, _) => {
+                            format!("extern crate {};", *name)
+                        }
+                        clean::Import(ref import) => {
+                            format!("{}{}", VisSpace(item.visibility), *import)
+                        }
+                    };
+                    try!(write!(w, "* `{}`\n", StripTags(html.as_slice())));
+                }
+                _ => {
+                    try!(write!(w, "* [{name}]({href})", name = *item.name.get_ref(),
+                                href = item_path(*item)));
+                    try!(summary(w, *item));
+                    try!(write!(w, "\n"));
+                }
+            }
+        }
+        try!(write!(w, "\n"));
+    }
+    Ok(())
+}
+
+/// Lists the fields of a struct or the variants of an enum.
+fn members(w: &mut Writer, header: &str,
+           items: &[clean::Item]) -> io::IoResult<()> {
+    let items = items.iter().filter_map(|i| signature(i).map(|s| (i, s)))
+                     .collect::<Vec<_>>();
+    if items.len() == 0 { return Ok(()) }
+    try!(write!(w, "\\#\\# {}\n\n", header));
+    for &(item, ref sig) in items.iter() {
+        try!(write!(w, "* `{}`", *sig));
+        try!(summary(w, item));
+        try!(write!(w, "\n"));
+    }
+    write!(w, "\n")
+}
+
+fn trait_methods(w: &mut Writer, header: &str,
+                 methods: &[&clean::TraitMethod]) -> io::IoResult<()> {
+    if methods.len() == 0 { return Ok(()) }
+    try!(write!(w, "\\#\\# {}\n\n", header));
+    for m in methods.iter() {
+        try!(method(w, m.item()));
+    }
+    Ok(())
+}
+
+fn method(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    match signature(item) {
+        Some(sig) => try!(write!(w, "\\#\\#\\# `{}`\n\n", sig)),
+        None => {}
+    }
+    document(w, item)
+}
+
+/// Lists the inherent methods and trait implementations of a type, as found
+/// in the cache.
+fn methods(w: &mut Writer, item: &clean::Item) -> io::IoResult<()> {
+    let cache = cache_key.get().unwrap();
+    let impls = match cache.impls.find(&item.id) {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+    let inherent = impls.iter().filter(|p| p.ref0().trait_.is_none())
+                        .collect::<Vec<&(clean::Impl, Option<StrBuf>)>>();
+    let traits = impls.iter().filter(|p| p.ref0().trait_.is_some())
+                      .collect::<Vec<&(clean::Impl, Option<StrBuf>)>>();
+
+    if inherent.len() > 0 {
+        try!(write!(w, "\\#\\# Methods\n\n"));
+        for & &(ref i, _) in inherent.iter() {
+            for m in i.methods.iter() {
+                try!(method(w, m));
+            }
+        }
+    }
+    if traits.len() > 0 {
+        try!(write!(w, "\\#\\# Trait Implementations\n\n"));
+        for & &(ref i, ref dox) in traits.iter() {
+            try!(write!(w, "\\#\\#\\# `{}`\n\n", impl_header(i)));
+            match *dox {
+                Some(ref dox) => try!(write!(w, "{}\n\n", dox.as_slice().trim())),
+                None => {}
+            }
+        }
+    }
+    Ok(())
+}
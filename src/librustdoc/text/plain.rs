@@ -0,0 +1,300 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Plain-text rendering backend
+//!
+//! Renders the documentation of a whole crate into a single text file, with
+//! one section per item headed by the item's full path, so the output can be
+//! grepped or paged through in a terminal. The `ansi` flavor additionally
+//! colors signatures and code examples with ANSI escape codes, using the same
+//! classification of tokens as the syntax highlighting of the HTML output.
+//!
+//! `item` renders the section of a single item, and may be used to show the
+//! documentation of just one item.
+
+use std::io;
+use std::io::BufferedWriter;
+use std::strbuf::StrBuf;
+use sync::Arc;
+
+use clean;
+use fold::DocFolder;
+use html::escape::StripTags;
+use html::highlight;
+use html::render::{Cache, HoardStripper, cache_key, current_location_key};
+use output::OutputSink;
+use renderer;
+use renderer::{Renderer, RenderOptions, RenderResult};
+use text::signature::{signature, impl_header, kind_name};
+
+/// Renders a crate as plain text, optionally colored for display in a
+/// terminal.
+pub struct TextRenderer {
+    /// Whether to color the output with ANSI escape codes
+    pub color: bool,
+}
+
+impl Renderer for TextRenderer {
+    fn name(&self) -> &'static str { if self.color { "ansi" } else { "text" } }
+
+    fn output_filename(&self) -> Option<&'static str> { Some("doc.txt") }
+
+    fn render(&mut self, krate: clean::Crate, cache: Arc<Cache>,
+              opts: &RenderOptions, sink: &mut OutputSink) -> RenderResult {
+        run(krate, cache, opts, sink, self.color).map_err(renderer::error)
+    }
+}
+
+fn run(krate: clean::Crate, cache: Arc<Cache>, opts: &RenderOptions,
+       sink: &mut OutputSink, color: bool) -> io::IoResult<()> {
+    let krate = HoardStripper::new(&*cache).fold_crate(krate);
+    cache_key.replace(Some(cache));
+    let mut root = match krate.module {
+        Some(i) => i,
+        None => return Ok(()),
+    };
+    root.name = Some(krate.name);
+
+    let mut w = BufferedWriter::new(try!(sink.create(&opts.dest)));
+    let mut progress = (0, renderer::count_items(&root));
+    try!(walk(&mut w, &mut Vec::new(), &root, color, opts, &mut progress));
+    w.flush()
+}
+
+/// Writes the section of every documented item in a module, depth-first.
+/// `progress` is the number of items done so far and the total.
+fn walk(w: &mut Writer, current: &mut Vec<StrBuf>, item: &clean::Item,
+        color: bool, opts: &RenderOptions,
+        progress: &mut (uint, uint)) -> io::IoResult<()> {
+    let (done, total) = *progress;
+    *progress = (done + 1, total);
+    try!(opts.tick(done + 1, total));
+    match item.inner {
+        _ if item.name.is_none() => Ok(()),
+        clean::ViewItemItem(..) => Ok(()),
+        clean::ModuleItem(ref m) => {
+            try!(self::item(w, current.as_slice(), item, color));
+            current.push(item.name.get_ref().clone());
+            for it in m.items.iter() {
+                try!(walk(w, current, it, color, opts, progress));
+            }
+            current.pop();
+            Ok(())
+        }
+        _ => self::item(w, current.as_slice(), item, color),
+    }
+}
+
+/// Writes the documentation of a single item which is located in the module
+/// at `current`, along with its fields, variants and methods. The cache must
+/// have been placed in `cache_key` beforehand.
+pub fn item(w: &mut Writer, current: &[StrBuf], item: &clean::Item,
+            color: bool) -> io::IoResult<()> {
+    current_location_key.replace(Some(Vec::from_slice(current)));
+    let mut path = Vec::from_slice(current);
+    path.push(item.name.get_ref().clone());
+    let title = format!("{} {}", kind_name(item), path.connect("::"));
+    if color {
+        try!(write!(w, "\x1b[1m{}\x1b[0m\n", title));
+    } else {
+        try!(write!(w, "{}\n", title));
+    }
+    try!(write!(w, "{}\n\n", "=".repeat(title.char_len())));
+    try!(documented(w, item, 0, color));
+
+    let members = match item.inner {
+        clean::StructItem(ref s) => Some(("Fields", s.fields.as_slice())),
+        clean::EnumItem(ref e) => Some(("Variants", e.variants.as_slice())),
+        _ => None,
+    };
+    match members {
+        Some((header, members)) if members.len() > 0 => {
+            try!(heading(w, header, color));
+            for m in members.iter() {
+                try!(documented(w, m, 4, color));
+            }
+        }
+        Some(..) | None => {}
+    }
+
+    match item.inner {
+        clean::TraitItem(ref t) if t.methods.len() > 0 => {
+            try!(heading(w, "Methods", color));
+            for m in t.methods.iter() {
+                try!(documented(w, m.item(), 4, color));
+            }
+        }
+        _ => {}
+    }
+
+    let cache = cache_key.get().unwrap();
+    match cache.impls.find(&item.id) {
+        Some(impls) => {
+            for &(ref i, ref dox) in impls.iter() {
+                try!(heading(w, impl_header(i).as_slice(), color));
+                match *dox {
+                    Some(ref dox) => try!(docs(w, dox.as_slice(), 4, color)),
+                    None => {}
+                }
+                for m in i.methods.iter() {
+                    try!(documented(w, m, 4, color));
+                }
+            }
+        }
+        None => {}
+    }
+    write!(w, "\n")
+}
+
+fn heading(w: &mut Writer, s: &str, color: bool) -> io::IoResult<()> {
+    if color {
+        write!(w, "\x1b[1m{}\x1b[0m\n\n", s)
+    } else {
+        write!(w, "{}\n\n", s)
+    }
+}
+
+/// Writes an item's signature followed by its docs, indented by `indent`.
+fn documented(w: &mut Writer, item: &clean::Item, indent: uint,
+              color: bool) -> io::IoResult<()> {
+    match signature(item) {
+        Some(sig) => {
+            try!(code(w, sig.as_slice(), indent, color));
+            try!(write!(w, "\n"));
+        }
+        None => {}
+    }
+    match item.doc_value() {
+        Some(s) => docs(w, s, indent + 4, color),
+        None => Ok(())
+    }
+}
+
+/// Writes documentation text, indented by `indent`. Code blocks are
+/// highlighted when coloring, and the lines which rustdoc hides from code
+/// blocks in the HTML output are left out.
+fn docs(w: &mut Writer, s: &str, indent: uint, color: bool) -> io::IoResult<()> {
+    let pad = " ".repeat(indent);
+    let mut block: Option<StrBuf> = None;
+    for line in s.lines() {
+        let fence = line.trim_left().starts_with("```") ||
+                    line.trim_left().starts_with("~~~");
+        block = match (block, fence) {
+            (None, true) => Some(StrBuf::new()),
+            (Some(code), true) => {
+                try!(self::code(w, code.as_slice(), indent + 4, color));
+                None
+            }
+            (Some(mut code), false) => {
+                let trimmed = line.trim_left();
+                if !(trimmed == "#" || trimmed.starts_with("# ")) {
+                    code.push_str(line);
+                    code.push_char('\n');
+                }
+                Some(code)
+            }
+            (None, false) => {
+                let pad = if line.len() > 0 { pad.as_slice() } else { "" };
+                try!(write!(w, "{}{}\n", pad, line));
+                None
+            }
+        };
+    }
+    match block {
+        Some(code) => try!(self::code(w, code.as_slice(), indent + 4, color)),
+        None => {}
+    }
+    write!(w, "\n")
+}
+
+/// Writes a snippet of Rust code, indented by `indent`.
+fn code(w: &mut Writer, s: &str, indent: uint, color: bool) -> io::IoResult<()> {
+    let pad = " ".repeat(indent);
+    let text = if color {
+        ansi(highlight::highlight(s, None).as_slice())
+    } else {
+        s.to_strbuf()
+    };
+    for line in text.as_slice().trim_right().lines() {
+        try!(write!(w, "{}{}\n", pad, line));
+    }
+    Ok(())
+}
+
+/// Converts the HTML produced by `highlight` into text colored with ANSI
+/// escape codes.
+fn ansi(html: &str) -> StrBuf {
+    static SPAN: &'static str = "<span class='";
+    let mut out = StrBuf::new();
+    let mut colors: Vec<&'static str> = Vec::new();
+    let mut rest = html;
+    loop {
+        let next = match rest.find('<') {
+            Some(i) => i,
+            None => break,
+        };
+        out.push_str(rest.slice_to(next));
+        rest = rest.slice_from(next);
+        if rest.starts_with(SPAN) {
+            let class = rest.slice_from(SPAN.len());
+            let class = class.slice_to(class.find('\'').unwrap_or(0));
+            let code = color(class);
+            colors.push(code);
+            out.push_str(code);
+        } else if rest.starts_with("</span>") {
+            colors.pop();
+            out.push_str("\x1b[0m");
+            match colors.last() {
+                Some(c) => out.push_str(*c),
+                None => {}
+            }
+        } else {
+            // Any other tags are left in place for `StripTags` to remove
+            out.push_char('<');
+            rest = rest.slice_from(1);
+            continue
+        }
+        rest = match rest.find('>') {
+            Some(end) => rest.slice_from(end + 1),
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    format_strbuf!("{}", StripTags(out.as_slice()))
+}
+
+/// The escape code used for each class of token emitted by `highlight`.
+fn color(class: &str) -> &'static str {
+    match class {
+        "kw" | "kw-2" => "\x1b[35m",
+        "string" => "\x1b[32m",
+        "number" | "boolval" => "\x1b[33m",
+        "comment" => "\x1b[90m",
+        "macro" | "macro-nonterminal" => "\x1b[36m",
+        "attribute" => "\x1b[33m",
+        "self" => "\x1b[31m",
+        "prelude-ty" | "prelude-val" => "\x1b[34m",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ansi;
+
+    #[test]
+    fn ansi_spans() {
+        assert_eq!(ansi("<pre class='rust '>a <span class='kw'>fn</span>\
+                        </pre>").as_slice(),
+                   "a \x1b[35mfn\x1b[0m");
+        assert_eq!(ansi("x &lt; <span class='unknown'>y</span>").as_slice(),
+                   "x < y\x1b[0m");
+    }
+}
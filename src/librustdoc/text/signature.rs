@@ -0,0 +1,121 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Plain-text item signatures
+//!
+//! The pretty-printers in `html::format` all emit HTML. Rather than
+//! duplicating them, backends producing other formats render through them and
+//! strip the markup back out again. Note that, just like when rendering HTML,
+//! the printers expect `cache_key` and `current_location_key` to be set.
+
+use std::strbuf::StrBuf;
+
+use clean;
+use html::escape::StripTags;
+use html::format::{VisSpace, Method, FnStyleSpace};
+
+/// Returns the declaration of `item` as it would appear in source, without
+/// any body, or `None` if the item has no meaningful signature.
+pub fn signature(item: &clean::Item) -> Option<StrBuf> {
+    let name = match item.name {
+        Some(ref s) => s.as_slice(),
+        None => "",
+    };
+    let vis = VisSpace(item.visibility);
+    let html = match item.inner {
+        clean::ModuleItem(..) => format!("{}mod {}", vis, name),
+        clean::FunctionItem(ref f) | clean::ForeignFunctionItem(ref f) => {
+            format!("{}{}fn {}{}{}", vis, FnStyleSpace(f.fn_style), name,
+                    f.generics, f.decl)
+        }
+        clean::TyMethodItem(ref m) => {
+            format!("{}fn {}{}{}", FnStyleSpace(m.fn_style), name,
+                    m.generics, Method(&m.self_, &m.decl))
+        }
+        clean::MethodItem(ref m) => {
+            format!("{}{}fn {}{}{}", vis, FnStyleSpace(m.fn_style), name,
+                    m.generics, Method(&m.self_, &m.decl))
+        }
+        clean::StructItem(ref s) => {
+            format!("{}struct {}{}", vis, name, s.generics)
+        }
+        clean::EnumItem(ref e) => format!("{}enum {}{}", vis, name, e.generics),
+        clean::TraitItem(ref t) => {
+            let mut parents = StrBuf::new();
+            for (i, p) in t.parents.iter().enumerate() {
+                parents.push_str(if i == 0 { ": " } else { " + " });
+                parents.push_str(format!("{}", *p));
+            }
+            format!("{}trait {}{}{}", vis, name, t.generics, parents)
+        }
+        clean::TypedefItem(ref t) => {
+            format!("type {}{} = {};", name, t.generics, t.type_)
+        }
+        clean::StaticItem(ref s) | clean::ForeignStaticItem(ref s) => {
+            format!("{}static {}{}: {}", vis,
+                    match s.mutability {
+                        clean::Mutable => "mut ",
+                        clean::Immutable => "",
+                    }, name, s.type_)
+        }
+        clean::StructFieldItem(clean::TypedStructField(ref ty)) => {
+            format!("{}{}: {}", vis, name, *ty)
+        }
+        clean::VariantItem(ref v) => {
+            match v.kind {
+                clean::TupleVariant(ref tys) => {
+                    let tys = tys.iter().map(|t| format!("{}", *t))
+                                 .collect::<Vec<~str>>();
+                    format!("{}({})", name, tys.connect(", "))
+                }
+                clean::CLikeVariant | clean::StructVariant(..) => {
+                    name.to_owned()
+                }
+            }
+        }
+        clean::MacroItem(..) => format!("macro_rules! {}", name),
+        clean::StructFieldItem(clean::HiddenStructField) |
+        clean::ImplItem(..) | clean::ViewItemItem(..) => return None,
+    };
+    Some(format_strbuf!("{}", StripTags(html.as_slice())))
+}
+
+/// Returns the header of an impl block, e.g. `impl<T> Clone for Vec<T>`.
+pub fn impl_header(i: &clean::Impl) -> StrBuf {
+    let html = match i.trait_ {
+        Some(ref t) => format!("impl{} {} for {}", i.generics, *t, i.for_),
+        None => format!("impl{} {}", i.generics, i.for_),
+    };
+    format_strbuf!("{}", StripTags(html.as_slice()))
+}
+
+/// Returns the human-readable name of the kind of `item`, as used in page
+/// titles.
+pub fn kind_name(item: &clean::Item) -> &'static str {
+    match item.inner {
+        clean::ModuleItem(ref m) if m.is_crate => "Crate",
+        clean::ModuleItem(..)          => "Module",
+        clean::StructItem(..)          => "Struct",
+        clean::EnumItem(..)            => "Enum",
+        clean::FunctionItem(..)        => "Function",
+        clean::TypedefItem(..)         => "Type Definition",
+        clean::StaticItem(..)          => "Static",
+        clean::TraitItem(..)           => "Trait",
+        clean::ImplItem(..)            => "Implementation",
+        clean::ViewItemItem(..)        => "Reexport",
+        clean::TyMethodItem(..)        => "Required Method",
+        clean::MethodItem(..)          => "Method",
+        clean::StructFieldItem(..)     => "Struct Field",
+        clean::VariantItem(..)         => "Variant",
+        clean::ForeignFunctionItem(..) => "Foreign Function",
+        clean::ForeignStaticItem(..)   => "Foreign Static",
+        clean::MacroItem(..)           => "Macro",
+    }
+}
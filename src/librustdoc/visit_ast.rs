@@ -134,14 +134,23 @@ impl<'a> RustdocVisitor<'a> {
         if item.vis != ast::Public {
             return om.view_items.push(item.clone());
         }
-        let please_inline = item.attrs.iter().any(|item| {
-            match item.meta_item_list() {
-                Some(list) => {
-                    list.iter().any(|i| i.name().get() == "inline")
+        // Re-exports of items which aren't public otherwise are documented in
+        // place of the `use`, and `#[doc(inline)]` asks for the same of public
+        // items. `#[doc(no_inline)]` keeps re-exports of public items listed
+        // as a `use`, even if they're also marked `#[doc(inline)]`. Items
+        // which aren't public are still documented in place of the `use`, as
+        // they have no page of their own for it to link to.
+        let doc_flag = |name: &str| {
+            item.attrs.iter().any(|item| {
+                match item.meta_item_list() {
+                    Some(list) => {
+                        list.iter().any(|i| i.name().get() == name)
+                    }
+                    None => false,
                 }
-                None => false,
-            }
-        });
+            })
+        };
+        let please_inline = doc_flag("inline") && !doc_flag("no_inline");
         let item = match item.node {
             ast::ViewItemUse(ref vpath) => {
                 match self.visit_view_path(*vpath, om, please_inline) {